@@ -0,0 +1,722 @@
+//! Emitting YAML
+//!
+
+use crate::types::*;
+
+use std::fmt::Write;
+
+/// Options for emitting YAML
+///
+/// ```
+/// # use marked_yaml::*;
+/// let options = EmitOptions {
+///     indent: 4,
+///     sort_keys: true,
+///     ..EmitOptions::default()
+/// };
+/// let node = parse_yaml(0, "b: {c: d}\na: 1\n").unwrap();
+/// assert_eq!(node.to_yaml_string_pretty(&options), "a: 1\nb:\n    c: d\n");
+/// ```
+#[derive(Clone, Debug)]
+pub struct EmitOptions {
+    /// How many spaces to indent nested block collections by
+    ///
+    /// Indentation of less than two is treated as two, since that is the
+    /// least which leaves room for the `-` of a sequence entry.  Defaults
+    /// to two.
+    pub indent: usize,
+    /// If set, collections which contain only scalars are written in flow
+    /// style (e.g. `[a, b]`) if that is no wider than this many characters.
+    /// Empty collections are always written in flow style.  Defaults to
+    /// `None`, meaning that only empty collections use flow style.
+    pub flow_width: Option<usize>,
+    /// If true, mapping entries are written sorted by key rather than in
+    /// the order they appear in the mapping.  Defaults to false.
+    pub sort_keys: bool,
+    /// If true, scalars which were quoted keep their quotes, rather than
+    /// being written plain where that is unambiguous.  Single quoted
+    /// scalars are double quoted if they contain characters which single
+    /// quotes cannot represent.  Block scalars are not yet emitted as
+    /// such, and are quoted as needed.  Defaults to false.
+    pub scalar_styles: bool,
+}
+
+impl Default for EmitOptions {
+    fn default() -> Self {
+        Self {
+            indent: 2,
+            flow_width: None,
+            sort_keys: false,
+            scalar_styles: false,
+        }
+    }
+}
+
+impl Node {
+    /// Render this node as a YAML document
+    ///
+    /// The output is laid out according to the given options.  Spans are
+    /// not consulted at all, so the output need not resemble the text the
+    /// node was parsed from, but parsing the output gives an equal node.
+    /// Scalars are written plain where that is unambiguous, and are double
+    /// quoted otherwise.  The one exception is that empty scalars which may
+    /// be coerced are nulls, and are written as `~` so that they still read
+    /// back as nulls rather than as empty strings.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "{name: web, ports: [80, 443], note: 'a: b'}").unwrap();
+    /// let options = EmitOptions {
+    ///     flow_width: Some(20),
+    ///     ..EmitOptions::default()
+    /// };
+    /// assert_eq!(
+    ///     node.to_yaml_string_pretty(&options),
+    ///     "name: web\nports: [80, 443]\nnote: \"a: b\"\n"
+    /// );
+    /// ```
+    pub fn to_yaml_string_pretty(&self, options: &EmitOptions) -> String {
+        let mut emitter = Emitter {
+            options,
+            indent: options.indent.max(2),
+            out: String::new(),
+        };
+        match self {
+            Node::Scalar(msn) => {
+                emitter.scalar(msn, false);
+                emitter.out.push('\n');
+            }
+            node => match emitter.flow(node) {
+                Some(flow) => {
+                    emitter.out.push_str(&flow);
+                    emitter.out.push('\n');
+                }
+                None => {
+                    if is_set(node) {
+                        emitter.out.push_str("!!set\n");
+                    }
+                    emitter.block(node, 0, false);
+                }
+            },
+        }
+        emitter.out
+    }
+}
+
+struct Emitter<'a> {
+    options: &'a EmitOptions,
+    indent: usize,
+    out: String,
+}
+
+impl Emitter<'_> {
+    fn pad(&mut self, column: usize) {
+        self.out.push_str(&" ".repeat(column));
+    }
+
+    fn entries<'m>(&self, map: &'m MarkedMappingNode) -> Vec<(&'m MarkedScalarNode, &'m Node)> {
+        let mut entries: Vec<_> = map.iter().collect();
+        if self.options.sort_keys {
+            entries.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+        }
+        entries
+    }
+
+    // Write a block collection whose first line starts at `column`.  If
+    // `inline` is set then the cursor is already there (after a `- `) and
+    // no padding is needed for the first line.
+    fn block(&mut self, node: &Node, column: usize, mut inline: bool) {
+        match node {
+            Node::Scalar(_) => unreachable!(),
+            Node::Sequence(seq) => {
+                for item in seq.iter() {
+                    if !inline {
+                        self.pad(column);
+                    }
+                    inline = false;
+                    self.out.push('-');
+                    self.pad(self.indent - 1);
+                    self.item(item, column + self.indent);
+                }
+            }
+            Node::Mapping(map) => {
+                for (key, value) in self.entries(map) {
+                    if !inline {
+                        self.pad(column);
+                    }
+                    inline = false;
+                    self.scalar(key, false);
+                    self.out.push(':');
+                    if let Node::Scalar(msn) = value {
+                        self.out.push(' ');
+                        self.scalar(msn, false);
+                        self.out.push('\n');
+                    } else if let Some(flow) = self.flow(value) {
+                        self.out.push(' ');
+                        self.out.push_str(&flow);
+                        self.out.push('\n');
+                    } else {
+                        if is_set(value) {
+                            self.out.push_str(" !!set");
+                        }
+                        self.out.push('\n');
+                        self.block(value, column + self.indent, false);
+                    }
+                }
+            }
+        }
+    }
+
+    // Write a node whose first line has already been started, finishing
+    // with a newline.  Block collections continue at `column`.
+    fn item(&mut self, node: &Node, column: usize) {
+        if let Node::Scalar(msn) = node {
+            self.scalar(msn, false);
+            self.out.push('\n');
+        } else if let Some(flow) = self.flow(node) {
+            self.out.push_str(&flow);
+            self.out.push('\n');
+        } else if is_set(node) {
+            // The tag must be on a line of its own, so the entries can't
+            // start on this one
+            self.out.push_str("!!set\n");
+            self.block(node, column, false);
+        } else {
+            self.block(node, column, true);
+        }
+    }
+
+    // Render a collection in flow style, if the options say that it should
+    // be.  Empty collections are always rendered in flow style.
+    fn flow(&self, node: &Node) -> Option<String> {
+        let tag = if is_set(node) { "!!set " } else { "" };
+        match node {
+            Node::Scalar(_) => return None,
+            Node::Sequence(seq) if seq.is_empty() => return Some("[]".to_string()),
+            Node::Mapping(map) if map.is_empty() => return Some(format!("{tag}{{}}")),
+            _ => {}
+        }
+        let width = self.options.flow_width?;
+        let mut flow = Emitter {
+            options: self.options,
+            indent: self.indent,
+            out: String::new(),
+        };
+        match node {
+            Node::Scalar(_) => unreachable!(),
+            Node::Sequence(seq) => {
+                flow.out.push('[');
+                for (i, item) in seq.iter().enumerate() {
+                    if i > 0 {
+                        flow.out.push_str(", ");
+                    }
+                    flow.scalar(item.as_scalar()?, true);
+                }
+                flow.out.push(']');
+            }
+            Node::Mapping(map) => {
+                flow.out.push_str(tag);
+                flow.out.push('{');
+                for (i, (key, value)) in self.entries(map).into_iter().enumerate() {
+                    if i > 0 {
+                        flow.out.push_str(", ");
+                    }
+                    flow.scalar(key, true);
+                    flow.out.push_str(": ");
+                    flow.scalar(value.as_scalar()?, true);
+                }
+                flow.out.push('}');
+            }
+        }
+        if flow.out.chars().count() <= width {
+            Some(flow.out)
+        } else {
+            None
+        }
+    }
+
+    // Write a node in flow style however big it is, as complex mapping keys
+    // are written
+    fn inline(&mut self, node: &Node) {
+        match node {
+            Node::Scalar(msn) => self.scalar(msn, true),
+            Node::Sequence(seq) => {
+                self.out.push('[');
+                for (i, item) in seq.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.inline(item);
+                }
+                self.out.push(']');
+            }
+            Node::Mapping(map) => {
+                if map.is_set() {
+                    self.out.push_str("!!set ");
+                }
+                self.out.push('{');
+                for (i, (key, value)) in self.entries(map).into_iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.scalar(key, true);
+                    self.out.push_str(": ");
+                    self.inline(value);
+                }
+                self.out.push('}');
+            }
+        }
+    }
+
+    fn scalar(&mut self, scalar: &MarkedScalarNode, in_flow: bool) {
+        if scalar.complex_key().is_some() {
+            // The value is already the key written in flow style
+            self.out.push_str(scalar.as_str());
+            return;
+        }
+        if scalar.is_binary() {
+            self.out.push_str("!!binary ");
+        }
+        let value = scalar.as_str();
+        let style = if self.options.scalar_styles {
+            scalar.style()
+        } else {
+            ScalarStyle::Plain
+        };
+        if style == ScalarStyle::SingleQuoted && can_be_single_quoted(value) {
+            self.out.push('\'');
+            self.out.push_str(&value.replace('\'', "''"));
+            self.out.push('\'');
+            return;
+        }
+        let quoted = matches!(style, ScalarStyle::SingleQuoted | ScalarStyle::DoubleQuoted);
+        if !quoted && value.is_empty() && scalar.may_coerce() {
+            self.out.push('~');
+            return;
+        }
+        if !quoted
+            && can_be_plain(value, in_flow)
+            && (scalar.may_coerce() || !may_not_be_string(value))
+        {
+            self.out.push_str(value);
+            return;
+        }
+        self.out.push('"');
+        for ch in value.chars() {
+            match ch {
+                '"' => self.out.push_str("\\\""),
+                '\\' => self.out.push_str("\\\\"),
+                '\n' => self.out.push_str("\\n"),
+                '\t' => self.out.push_str("\\t"),
+                '\r' => self.out.push_str("\\r"),
+                '\0' => self.out.push_str("\\0"),
+                '\u{2028}' => self.out.push_str("\\L"),
+                '\u{2029}' => self.out.push_str("\\P"),
+                '\u{feff}' => self.out.push_str("\\ufeff"),
+                ch if ch.is_control() => {
+                    let _ = write!(self.out, "\\x{:02x}", ch as u32);
+                }
+                ch => self.out.push(ch),
+            }
+        }
+        self.out.push('"');
+    }
+}
+
+// The value which a complex mapping key loads as, which is the key written
+// in flow style as the emitter would write it
+pub(crate) fn complex_key_text(key: &Node) -> String {
+    let options = EmitOptions::default();
+    let mut emitter = Emitter {
+        options: &options,
+        indent: options.indent,
+        out: String::new(),
+    };
+    emitter.inline(key);
+    emitter.out
+}
+
+// Sets are the only collections which need a tag
+fn is_set(node: &Node) -> bool {
+    node.as_mapping().is_some_and(MarkedMappingNode::is_set)
+}
+
+// Whether the text can be written as a plain scalar and read back unchanged
+fn can_be_plain(value: &str, in_flow: bool) -> bool {
+    let mut chars = value.chars();
+    let first = match chars.next() {
+        Some(first) => first,
+        None => return false,
+    };
+    let second = chars.next();
+    if "[]{},#&*!|>'\"%@`".contains(first)
+        || ("-?:".contains(first) && matches!(second, None | Some(' ')))
+    {
+        return false;
+    }
+    if value.starts_with("---") || value.starts_with("...") {
+        return false;
+    }
+    if value.starts_with(' ') || value.ends_with(&[' ', ':'][..]) {
+        return false;
+    }
+    if value.contains(": ") || value.contains(" #") {
+        return false;
+    }
+    if in_flow && value.contains(&['[', ']', '{', '}', ',', ':'][..]) {
+        return false;
+    }
+    !value
+        .chars()
+        .any(|c| c.is_control() || matches!(c, '\u{2028}' | '\u{2029}' | '\u{feff}'))
+}
+
+// Whether the text can be written in single quotes and read back unchanged.
+// Single quotes have no escapes, and line breaks within them are folded.
+fn can_be_single_quoted(value: &str) -> bool {
+    !value
+        .chars()
+        .any(|c| c.is_control() || matches!(c, '\u{2028}' | '\u{2029}' | '\u{feff}'))
+}
+
+// Whether a YAML reader might take the text as something other than a
+// string if it were written plain.  This errs on the side of caution, so
+// includes the YAML 1.1 booleans as well as the core schema's values.
+fn may_not_be_string(value: &str) -> bool {
+    let lower = value.to_ascii_lowercase();
+    if is_yaml11_boolean(value) || matches!(lower.as_str(), "~" | "null" | "true" | "false") {
+        return true;
+    }
+    let unsigned = lower.trim_start_matches(&['+', '-'][..]);
+    matches!(unsigned, ".inf" | ".nan")
+        || (value.parse::<f64>().is_ok() && value.chars().any(|c| c.is_ascii_digit()))
+        || unsigned.starts_with("0x")
+        || unsigned.starts_with("0o")
+        || unsigned.starts_with("0b")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_yaml, parse_yaml_with_options, LoaderOptions};
+
+    const DOC: &str = r#"
+name: web
+ports: [80, 443]
+servers:
+  - host: a.example.com
+    tags: [fast]
+  - host: b.example.com
+    tags: []
+  - [nested, list]
+empty: {}
+"#;
+
+    fn round_trips(node: &Node, options: &EmitOptions) -> String {
+        let text = node.to_yaml_string_pretty(options);
+        let options = LoaderOptions {
+            allow_non_mapping_top_level: true,
+            allow_complex_keys: true,
+            ..LoaderOptions::default()
+        };
+        let reparsed = parse_yaml_with_options(0, &text, options).unwrap();
+        assert_eq!(&reparsed, node, "{}", text);
+        text
+    }
+
+    #[test]
+    fn indent_widths() {
+        let node = parse_yaml(0, DOC).unwrap();
+        assert_eq!(
+            round_trips(&node, &EmitOptions::default()),
+            r#"name: web
+ports:
+  - 80
+  - 443
+servers:
+  - host: a.example.com
+    tags:
+      - fast
+  - host: b.example.com
+    tags: []
+  - - nested
+    - list
+empty: {}
+"#
+        );
+        let options = EmitOptions {
+            indent: 4,
+            ..EmitOptions::default()
+        };
+        assert_eq!(
+            round_trips(&node, &options),
+            r#"name: web
+ports:
+    -   80
+    -   443
+servers:
+    -   host: a.example.com
+        tags:
+            -   fast
+    -   host: b.example.com
+        tags: []
+    -   -   nested
+        -   list
+empty: {}
+"#
+        );
+        let options = EmitOptions {
+            indent: 0,
+            ..EmitOptions::default()
+        };
+        assert_eq!(
+            node.to_yaml_string_pretty(&options),
+            node.to_yaml_string_pretty(&EmitOptions::default())
+        );
+    }
+
+    #[test]
+    fn flow_and_sorting() {
+        let node = parse_yaml(0, DOC).unwrap();
+        let options = EmitOptions {
+            flow_width: Some(14),
+            sort_keys: true,
+            ..EmitOptions::default()
+        };
+        assert_eq!(
+            node.to_yaml_string_pretty(&options),
+            r#"empty: {}
+name: web
+ports: [80, 443]
+servers:
+  - host: a.example.com
+    tags: [fast]
+  - host: b.example.com
+    tags: []
+  - [nested, list]
+"#
+        );
+        let node = parse_yaml(0, "{b: 1, a: 2}").unwrap();
+        assert_eq!(node.to_yaml_string_pretty(&options), "{a: 2, b: 1}\n");
+    }
+
+    #[test]
+    fn quoting() {
+        let node = parse_yaml(
+            0,
+            r#"
+plain: NO
+quoted: "NO"
+number: '12'
+colon: "a: b"
+comment: "a #b"
+indicator: "- item"
+dash: -1
+empty: ""
+spaces: " padded "
+escapes: "tab\there\nnew \"line\" \\ \x07"
+"key: with colon": value
+flow: ["a,b", "[c]", "d"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            round_trips(&node, &EmitOptions::default()),
+            r#"plain: NO
+quoted: "NO"
+number: "12"
+colon: "a: b"
+comment: "a #b"
+indicator: "- item"
+dash: -1
+empty: ""
+spaces: " padded "
+escapes: "tab\there\nnew \"line\" \\ \x07"
+"key: with colon": value
+flow:
+  - a,b
+  - "[c]"
+  - d
+"#
+        );
+        let options = EmitOptions {
+            flow_width: Some(80),
+            ..EmitOptions::default()
+        };
+        let flow = node.as_mapping().unwrap().get_node("flow").unwrap();
+        assert_eq!(round_trips(flow, &options), "[\"a,b\", \"[c]\", d]\n");
+    }
+
+    #[test]
+    fn scalar_text_is_preserved() {
+        // Scalars are never normalised, so numbers keep their exact spelling
+        let node = parse_yaml(
+            0,
+            "octal: 010\nversion: 1.0\nquoted: \"1.0\"\nexp: 1e3\nhex: 0x1F\n",
+        )
+        .unwrap();
+        let map = node.as_mapping().unwrap();
+        assert_eq!(map.get_scalar("octal").unwrap().as_str(), "010");
+        assert_eq!(map.get_scalar("version").unwrap().as_str(), "1.0");
+        assert_eq!(
+            round_trips(&node, &EmitOptions::default()),
+            "octal: 010\nversion: 1.0\nquoted: \"1.0\"\nexp: 1e3\nhex: 0x1F\n"
+        );
+    }
+
+    #[test]
+    fn scalar_styles() {
+        let mut node = parse_yaml(
+            0,
+            "single: 'it''s'\ndouble: \"quoted\"\nplain: value\nlist: ['a', \"b\"]\n",
+        )
+        .unwrap();
+        let options = EmitOptions {
+            scalar_styles: true,
+            ..EmitOptions::default()
+        };
+        let map = node.as_mapping_mut().unwrap();
+        map.get_scalar_mut("double").unwrap().set_str("edited");
+        assert_eq!(
+            round_trips(&node, &options),
+            "single: 'it''s'\ndouble: \"edited\"\nplain: value\nlist:\n  - 'a'\n  - \"b\"\n"
+        );
+        assert_eq!(
+            round_trips(&node, &EmitOptions::default()),
+            "single: it's\ndouble: edited\nplain: value\nlist:\n  - a\n  - b\n"
+        );
+
+        let mut scalar = MarkedScalarNode::new(Span::new_blank(), "tab\there");
+        scalar.set_style(ScalarStyle::SingleQuoted);
+        assert_eq!(
+            round_trips(&Node::from(scalar.clone()), &options),
+            "\"tab\\there\"\n"
+        );
+        scalar.set_style(ScalarStyle::DoubleQuoted);
+        scalar.set_str("plain enough");
+        assert_eq!(
+            round_trips(&Node::from(scalar), &options),
+            "\"plain enough\"\n"
+        );
+    }
+
+    #[test]
+    fn binary_scalars() {
+        let node = parse_yaml(
+            0,
+            "data: !!binary aGVsbG8=\nblock: !!binary |\n  aGVs\n  bG8=\n",
+        )
+        .unwrap();
+        let emitted = round_trips(&node, &EmitOptions::default());
+        assert_eq!(
+            emitted,
+            "data: !!binary aGVsbG8=\nblock: !!binary \"aGVs\\nbG8=\\n\"\n"
+        );
+        let reloaded = parse_yaml(0, emitted).unwrap();
+        assert!(reloaded
+            .get_or_null("block")
+            .as_scalar()
+            .unwrap()
+            .is_binary());
+    }
+
+    #[test]
+    fn set_mappings() {
+        let node = parse_yaml(
+            0,
+            "flow: !!set {a, b}\nblock: !!set\n  ? c\nlist:\n  - !!set\n    ? d\n",
+        )
+        .unwrap();
+        let emitted = round_trips(&node, &EmitOptions::default());
+        assert_eq!(
+            emitted,
+            "flow: !!set\n  a: ~\n  b: ~\nblock: !!set\n  c: ~\nlist:\n  - !!set\n    d: ~\n"
+        );
+        let options = EmitOptions {
+            flow_width: Some(80),
+            ..EmitOptions::default()
+        };
+        assert_eq!(
+            round_trips(&node, &options),
+            "flow: !!set {a: ~, b: ~}\nblock: !!set {c: ~}\nlist:\n  - !!set {d: ~}\n"
+        );
+        let reloaded = parse_yaml(0, emitted).unwrap();
+        let list = reloaded.get_or_null("list").as_sequence().unwrap();
+        assert!(list.get_mapping(0).unwrap().is_set());
+        let set = reloaded.get_or_null("flow").as_mapping().unwrap();
+        assert!(set.is_set());
+        assert_eq!(
+            round_trips(&Node::from(set.clone()), &EmitOptions::default()),
+            "!!set\na: ~\nb: ~\n"
+        );
+    }
+
+    #[test]
+    fn complex_keys() {
+        let options = LoaderOptions {
+            allow_complex_keys: true,
+            ..LoaderOptions::default()
+        };
+        let node =
+            parse_yaml_with_options(0, "? - a\n  - 'b: c'\n: 1\n? {x: [y]}\n: {z: 2}\n", options)
+                .unwrap();
+        assert_eq!(
+            round_trips(&node, &EmitOptions::default()),
+            "[a, \"b: c\"]: 1\n{x: [y]}:\n  z: 2\n"
+        );
+        let options = EmitOptions {
+            flow_width: Some(80),
+            ..EmitOptions::default()
+        };
+        assert_eq!(
+            round_trips(&node, &options),
+            "[a, \"b: c\"]: 1\n{x: [y]}: {z: 2}\n"
+        );
+    }
+
+    #[test]
+    fn null_scalars() {
+        fn is_null(node: &Node) -> bool {
+            node.as_scalar()
+                .is_some_and(|s| s.may_coerce() && matches!(s.as_str(), "" | "~"))
+        }
+        let options = LoaderOptions {
+            allow_non_mapping_top_level: true,
+            ..LoaderOptions::default()
+        };
+        let node = parse_yaml(0, "empty:\ntilde: ~\nquoted: \"\"\nlist: [~, '']\n").unwrap();
+        for emit in [
+            EmitOptions::default(),
+            EmitOptions {
+                flow_width: Some(80),
+                ..EmitOptions::default()
+            },
+        ] {
+            let text = node.to_yaml_string_pretty(&emit);
+            let reparsed = parse_yaml(0, &text).unwrap();
+            let map = reparsed.as_mapping().unwrap();
+            assert!(is_null(map.get_node("empty").unwrap()), "{text}");
+            assert!(is_null(map.get_node("tilde").unwrap()), "{text}");
+            let quoted = map.get_node("quoted").unwrap();
+            assert!(!is_null(quoted), "{text}");
+            assert_eq!(quoted.as_scalar().unwrap().as_str(), "");
+            let list = map.get_sequence("list").unwrap();
+            assert!(is_null(&list[0]), "{text}");
+            assert!(!is_null(&list[1]), "{text}");
+        }
+        let empty = Node::from(MarkedScalarNode::new_empty(Span::new_blank()));
+        let text = empty.to_yaml_string_pretty(&EmitOptions::default());
+        assert_eq!(text, "~\n");
+        assert!(is_null(
+            &parse_yaml_with_options(0, &text, options).unwrap()
+        ));
+    }
+
+    #[test]
+    fn scalar_documents() {
+        let options = EmitOptions::default();
+        assert_eq!(round_trips(&Node::from("hello"), &options), "hello\n");
+        assert_eq!(round_trips(&Node::from("..."), &options), "\"...\"\n");
+        assert_eq!(round_trips(&Node::from("a\nb"), &options), "\"a\\nb\"\n");
+    }
+}
@@ -0,0 +1,138 @@
+//! Timestamps written as numbers
+//!
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::de::{Error, Unexpected};
+use serde::{Deserialize, Deserializer};
+
+/// A point in time written as a whole number of seconds since the Unix epoch
+///
+/// Negative numbers, and numbers too large to be represented as a date, are
+/// rejected with an error located at the offending scalar.
+///
+/// ```
+/// # use marked_yaml::*;
+/// # use serde::Deserialize;
+/// #[derive(Deserialize)]
+/// struct Token {
+///     expires: EpochSeconds,
+/// }
+/// let token: Token = from_yaml(0, "expires: 1700000000\n").unwrap();
+/// assert_eq!(token.expires.0.to_rfc3339(), "2023-11-14T22:13:20+00:00");
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EpochSeconds(pub DateTime<Utc>);
+
+/// A point in time written as a whole number of milliseconds since the Unix
+/// epoch
+///
+/// Negative numbers, and numbers too large to be represented as a date, are
+/// rejected with an error located at the offending scalar.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EpochMillis(pub DateTime<Utc>);
+
+struct Expected(&'static str);
+
+impl serde::de::Expected for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "a non-negative number of {} within range", self.0)
+    }
+}
+
+fn from_epoch<'de, D, F>(
+    deserializer: D,
+    unit: &'static str,
+    convert: F,
+) -> Result<DateTime<Utc>, D::Error>
+where
+    D: Deserializer<'de>,
+    F: FnOnce(i64) -> Option<DateTime<Utc>>,
+{
+    let value = i64::deserialize(deserializer)?;
+    if value < 0 {
+        return Err(D::Error::invalid_value(
+            Unexpected::Signed(value),
+            &Expected(unit),
+        ));
+    }
+    convert(value)
+        .ok_or_else(|| D::Error::invalid_value(Unexpected::Signed(value), &Expected(unit)))
+}
+
+impl<'de> Deserialize<'de> for EpochSeconds {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        from_epoch(deserializer, "seconds", |secs| {
+            DateTime::from_timestamp(secs, 0)
+        })
+        .map(Self)
+    }
+}
+
+impl<'de> Deserialize<'de> for EpochMillis {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        from_epoch(
+            deserializer,
+            "milliseconds",
+            DateTime::from_timestamp_millis,
+        )
+        .map(Self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::spanned_serde::FromYamlError;
+    use crate::{from_yaml, Marker, Spanned};
+
+    #[derive(Deserialize, Debug)]
+    struct TestDoc {
+        seconds: EpochSeconds,
+        millis: Spanned<EpochMillis>,
+    }
+
+    fn error_mark(yaml: &str) -> Option<Marker> {
+        match from_yaml::<TestDoc>(0, yaml) {
+            Err(FromYamlError::FromNode(e)) => e.start_mark(),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn epoch_timestamps() {
+        let doc: TestDoc = from_yaml(0, "seconds: 1700000000\nmillis: 1700000000123\n").unwrap();
+        assert_eq!(doc.seconds.0.to_rfc3339(), "2023-11-14T22:13:20+00:00");
+        assert_eq!(doc.millis.0.timestamp_millis(), 1_700_000_000_123);
+        assert_eq!(doc.millis.span().start(), Some(&Marker::new(0, 2, 9)));
+        let doc: TestDoc = from_yaml(0, "seconds: 0\nmillis: 0\n").unwrap();
+        assert_eq!(doc.seconds.0.timestamp(), 0);
+    }
+
+    #[test]
+    fn epoch_errors() {
+        assert_eq!(
+            error_mark("seconds: -1\nmillis: 0\n"),
+            Some(Marker::new(0, 1, 10))
+        );
+        assert_eq!(
+            error_mark("seconds: 9223372036854775807\nmillis: 0\n"),
+            Some(Marker::new(0, 1, 10))
+        );
+        assert_eq!(
+            error_mark("seconds: 0\nmillis: 99999999999999999999\n"),
+            Some(Marker::new(0, 2, 9))
+        );
+        assert_eq!(
+            error_mark("seconds: soon\nmillis: 0\n"),
+            Some(Marker::new(0, 1, 10))
+        );
+    }
+}
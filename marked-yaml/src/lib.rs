@@ -24,29 +24,68 @@
 //! Parsing a valid YAML file may fail because `marked_yaml` adds some
 //! additional constraints:
 //!
-//! * The top level of the YAML **MUST** be a mapping.
-//! * Mapping keys **MUST** be scalars (strings).
-//! * Aliases and anchors **MAY NOT** be used (though this limit may be lifted in the future).
+//! * The top level of the YAML **MUST** be a mapping (unless the
+//!   [`LoaderOptions`] say otherwise).
+//! * Mapping keys **MUST** be scalars (strings), unless the [`LoaderOptions`]
+//!   allow complex keys, which load as scalars holding the key in flow style.
+//! * Aliases and anchors **MAY NOT** be used, unless the [`LoaderOptions`]
+//!   allow them, in which case each alias loads as a copy of its anchor.
+//!   Amongst other things this means that by default untrusted documents cannot
+//!   use aliases to expand into something enormous (the "billion laughs" attack),
+//!   and when they are allowed [`LoaderOptions::max_alias_expansion`] limits
+//!   them.  Large documents can be refused with [`LoaderOptions::max_nodes`].
+//! * Tags **MAY NOT** be used, other than `!!set` on a mapping and `!!binary` on a scalar.
 //!
 //! In addition, you can convert between `marked_yaml::Node` and `yaml_rust::Yaml`
 //! though doing so will not give you any useful markers.
 
 #![deny(missing_docs)]
 
+pub mod emitter;
 pub mod loader;
 pub mod types;
 
 #[doc(inline)]
-pub use loader::{parse_yaml, parse_yaml_with_options, LoadError, LoaderOptions};
+pub use emitter::EmitOptions;
 #[doc(inline)]
-pub use types::{Marker, Node, Span};
+pub use loader::{
+    debug_events, parse_yaml, parse_yaml_bytes, parse_yaml_bytes_with_options, parse_yaml_document,
+    parse_yaml_multi, parse_yaml_multi_with_options, parse_yaml_named, parse_yaml_with_options,
+    parse_yaml_with_warnings, LoadError, LoaderOptions, MarkedEvent, ParsedDocument,
+};
+#[doc(inline)]
+pub use types::{LineIndex, Marker, Node, SourceRegistry, Span, Warning};
 
 #[cfg(feature = "serde")]
 #[doc(hidden)]
 pub mod spanned_serde;
 
+#[cfg(feature = "chrono")]
+pub mod epoch;
+
+#[cfg(feature = "chrono")]
+#[doc(inline)]
+pub use epoch::{EpochMillis, EpochSeconds};
+
+#[cfg(feature = "serde")]
+pub mod schema;
+
+#[cfg(feature = "serde")]
+pub mod spanned;
+
+#[cfg(feature = "serde")]
+#[doc(inline)]
+pub use schema::NodeSchema;
+#[cfg(all(feature = "serde", feature = "std"))]
+#[doc(inline)]
+pub use spanned_serde::from_file;
 #[cfg(feature = "serde")]
 #[doc(inline)]
 pub use spanned_serde::{
-    from_node, from_yaml, from_yaml_with_options, Error, FromNodeError, Spanned,
+    from_node, from_node_collect_errors, from_node_map, from_node_seq,
+    from_node_with_fallback_span, from_node_with_options, from_node_with_warnings, from_yaml,
+    from_yaml_with_options, to_spanned_tree, AmbiguousStrings, Error, FromNodeError,
+    FromNodeOptions, Spanned, SpannedSeed, SpannedValue, SPANNED_FIELDS, SPANNED_INNER,
+    SPANNED_SPAN_END_COLUMN, SPANNED_SPAN_END_LINE, SPANNED_SPAN_END_SOURCE,
+    SPANNED_SPAN_START_COLUMN, SPANNED_SPAN_START_LINE, SPANNED_SPAN_START_SOURCE, SPANNED_TYPE,
 };
@@ -1,15 +1,20 @@
 //! Loading YAML
 //!
 
+use crate::emitter::complex_key_text;
 use crate::types::*;
 
 use linked_hash_map::Entry;
-use yaml_rust::parser::{Event, MarkedEventReceiver, Parser};
+use yaml_rust::parser::{Event, MarkedEventReceiver, Parser, Tag};
 use yaml_rust::scanner::Marker as YamlMarker;
-use yaml_rust::scanner::ScanError;
+use yaml_rust::scanner::{ScanError, Scanner, TScalarStyle, Token, TokenType};
 
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::error::Error;
 use std::fmt::{self, Display};
+use std::io;
+use std::path::PathBuf;
 
 /// An error indicating that a duplicate key was detected in a mapping
 #[derive(Debug, PartialEq, Eq)]
@@ -22,7 +27,7 @@ pub struct DuplicateKeyInner {
 }
 
 /// Errors which can occur during loading of YAML
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug)]
 #[non_exhaustive]
 pub enum LoadError {
     /// Something other than a mapping detected at the top level
@@ -37,14 +42,112 @@ pub enum LoadError {
     ScanError(Marker, ScanError),
     /// A duplicate key was detected in a mapping
     DuplicateKey(Box<DuplicateKeyInner>),
+    /// The input bytes were not validly encoded text
+    ///
+    /// The marker indicates where in the successfully decoded text the
+    /// problem was found.
+    InvalidEncoding(Marker),
+    /// The YAML could not be read from the given file
+    Io(PathBuf, io::Error),
+    /// The YAML was loaded but could not be deserialized
+    #[cfg(feature = "serde")]
+    Deserialize(Box<crate::FromNodeError>),
+    /// The document contained more nodes than permitted
+    ///
+    /// The marker indicates the start of the first node over the limit,
+    /// see [`LoaderOptions::max_nodes`].
+    NodeLimitExceeded(Marker),
+    /// Aliases expanded into more copied nodes than permitted
+    ///
+    /// The span is that of the first alias over the limit, see
+    /// [`LoaderOptions::max_alias_expansion`].
+    ExpansionLimitExceeded(Span),
+    /// The YAML parser produced an event which made no sense at that point
+    ///
+    /// This should never happen, and indicates a bug in the loader or the
+    /// parser rather than a problem with the input.
+    UnexpectedEvent(Marker),
+    /// The YAML parser produced no document at all
+    ///
+    /// Like [`LoadError::UnexpectedEvent`] this should never happen, since
+    /// empty input is loaded as an empty document.
+    NoDocument,
+}
+
+impl PartialEq for LoadError {
+    fn eq(&self, other: &Self) -> bool {
+        use LoadError::*;
+        match (self, other) {
+            (TopLevelMustBeMapping(a), TopLevelMustBeMapping(b))
+            | (UnexpectedAnchor(a), UnexpectedAnchor(b))
+            | (MappingKeyMustBeScalar(a), MappingKeyMustBeScalar(b))
+            | (UnexpectedTag(a), UnexpectedTag(b))
+            | (InvalidEncoding(a), InvalidEncoding(b))
+            | (NodeLimitExceeded(a), NodeLimitExceeded(b))
+            | (UnexpectedEvent(a), UnexpectedEvent(b)) => a == b,
+            (NoDocument, NoDocument) => true,
+            (ScanError(a, ae), ScanError(b, be)) => a == b && ae == be,
+            (DuplicateKey(a), DuplicateKey(b)) => a == b,
+            (ExpansionLimitExceeded(a), ExpansionLimitExceeded(b)) => a == b,
+            // IO errors can't be compared directly, but what went wrong with
+            // which file is what matters.  Deserialisation errors can't be
+            // compared either, so compare where they are and what they say.
+            (Io(a, ae), Io(b, be)) => a == b && ae.kind() == be.kind(),
+            #[cfg(feature = "serde")]
+            (Deserialize(a), Deserialize(b)) => {
+                a.start_mark() == b.start_mark() && a.to_string() == b.to_string()
+            }
+            _ => false,
+        }
+    }
 }
 
+impl Eq for LoadError {}
+
 /// Options for loading YAML
-#[derive(Debug, Default)]
+#[derive(Debug)]
 pub struct LoaderOptions {
     /// If true, duplicate keys in mappings will cause an error. If false,
     /// the last key will be used.
     pub error_on_duplicate_keys: bool,
+    /// If true, the top level of a document may be a scalar or a sequence
+    /// rather than a mapping.  If false, anything other than a mapping at
+    /// the top level is an error.
+    pub allow_non_mapping_top_level: bool,
+    /// If set, loading stops with an error once more than this many nodes
+    /// have been loaded.  Every scalar, including mapping keys, counts as a
+    /// node, as does every mapping and sequence.  For multi-document streams
+    /// the limit applies to the stream as a whole.  This guards against
+    /// untrusted input exhausting memory with a very large tree.
+    pub max_nodes: Option<usize>,
+    /// If true, anchors (`&name`) and aliases (`*name`) are permitted, each
+    /// alias being loaded as a copy of the node which its anchor is defined
+    /// on, see [`Node::alias_of()`].  If false, anchors are an error.
+    pub allow_aliases: bool,
+    /// If true, mapping keys may be sequences or mappings (e.g. `? [a, b]`)
+    /// as well as scalars, loading as scalars which remember the collection,
+    /// see [`MarkedScalarNode::complex_key()`].  If false, keys which are
+    /// not scalars are an error.
+    pub allow_complex_keys: bool,
+    /// If set, loading stops with an error once aliases have copied more
+    /// than this many nodes in total, counting nodes as for
+    /// [`max_nodes`][LoaderOptions::max_nodes].  This guards against small
+    /// documents using aliases of aliases to expand into something enormous
+    /// (the "billion laughs" attack).  The default is 100,000 nodes.
+    pub max_alias_expansion: Option<usize>,
+}
+
+impl Default for LoaderOptions {
+    fn default() -> Self {
+        Self {
+            error_on_duplicate_keys: false,
+            allow_non_mapping_top_level: false,
+            max_nodes: None,
+            allow_aliases: false,
+            allow_complex_keys: false,
+            max_alias_expansion: Some(100_000),
+        }
+    }
 }
 
 impl Display for LoadError {
@@ -56,6 +159,17 @@ impl Display for LoadError {
             UnexpectedAnchor(m) => write!(f, "{}: Unexpected definition of anchor", m),
             MappingKeyMustBeScalar(m) => write!(f, "{}: Keys in mappings must be scalar", m),
             UnexpectedTag(m) => write!(f, "{}: Unexpected use of YAML tag", m),
+            InvalidEncoding(m) => write!(f, "{}: Invalid text encoding", m),
+            NodeLimitExceeded(m) => write!(f, "{}: Too many nodes in document", m),
+            ExpansionLimitExceeded(span) => match span.start() {
+                Some(m) => write!(f, "{}: Aliases expanded into too many nodes", m),
+                None => write!(f, "Aliases expanded into too many nodes"),
+            },
+            UnexpectedEvent(m) => write!(f, "{}: Unexpected event from YAML parser", m),
+            NoDocument => write!(f, "YAML parser produced no document"),
+            Io(path, e) => write!(f, "{}: {}", path.display(), e),
+            #[cfg(feature = "serde")]
+            Deserialize(e) => write!(f, "{}", e),
             DuplicateKey(inner) => {
                 let DuplicateKeyInner { prev_key, key } = inner.as_ref();
                 write!(
@@ -82,7 +196,46 @@ impl Display for LoadError {
     }
 }
 
-impl Error for LoadError {}
+impl Error for LoadError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            LoadError::Io(_, e) => Some(e),
+            #[cfg(feature = "serde")]
+            LoadError::Deserialize(e) => Some(e.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl LoadError {
+    /// Retrieve the marker at which this error was detected
+    ///
+    /// For duplicate keys this is the start of the second key.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let err = parse_yaml(0, "[]").err().unwrap();
+    /// assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 1)));
+    /// ```
+    pub fn start_mark(&self) -> Option<Marker> {
+        use LoadError::*;
+        match self {
+            TopLevelMustBeMapping(m)
+            | UnexpectedAnchor(m)
+            | MappingKeyMustBeScalar(m)
+            | UnexpectedTag(m)
+            | InvalidEncoding(m)
+            | NodeLimitExceeded(m)
+            | UnexpectedEvent(m)
+            | ScanError(m, _) => Some(*m),
+            DuplicateKey(inner) => inner.key.span().start().copied(),
+            ExpansionLimitExceeded(span) => span.start().copied(),
+            Io(_, _) | NoDocument => None,
+            #[cfg(feature = "serde")]
+            Deserialize(e) => e.start_mark(),
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Eq)]
 enum LoaderState {
@@ -103,41 +256,97 @@ impl LoaderState {
     }
 }
 
-struct MarkedLoader {
+// The parser produces events in a well-formed order, so the loader should
+// never be in a state which doesn't fit the next event.  Should that ever
+// happen, fail the load rather than panicking.
+fn unexpected_event(mark: Marker, state: &LoaderState) -> LoadError {
+    debug_assert!(false, "Unexpected YAML event in loader state {state:?}");
+    LoadError::UnexpectedEvent(mark)
+}
+
+// Each document loaded, with the span of the document as a whole
+type Documents = Vec<(Node, Span)>;
+
+// What we need to remember about a container which has started but not
+// yet ended
+#[derive(Default)]
+struct Container {
+    flow: bool,
+    set: bool,
+    anchor: usize,
+}
+
+struct MarkedLoader<'a> {
     source: usize,
+    index: LineIndex<'a>,
+    containers: Vec<Container>,
     state_stack: Vec<LoaderState>,
     options: LoaderOptions,
+    multi_document: bool,
+    document_start: Option<Marker>,
+    documents: Documents,
+    nodes: usize,
+    warnings: Option<Vec<Warning>>,
+    anchors: HashMap<usize, (Node, usize)>,
+    expanded: usize,
+    anchor_names: Option<Vec<String>>,
 }
 
-impl MarkedEventReceiver for MarkedLoader {
+impl MarkedEventReceiver for MarkedLoader<'_> {
     fn on_event(&mut self, ev: Event, mark: YamlMarker) {
         // Short-circuit if the state stack is in error
-        if self.state_stack[self.state_stack.len() - 1].is_error() {
+        if self.state_stack.last().is_some_and(LoaderState::is_error) {
             return;
         }
         let mark = self.marker(mark);
-        let curstate = self
-            .state_stack
-            .pop()
-            .expect("State stack became unbalanced");
+        if matches!(
+            ev,
+            Event::Scalar(..) | Event::MappingStart(..) | Event::SequenceStart(..)
+        ) {
+            self.nodes += 1;
+            if self.options.max_nodes.is_some_and(|max| self.nodes > max) {
+                self.state_stack
+                    .push(Error(LoadError::NodeLimitExceeded(mark)));
+                return;
+            }
+        }
+        let curstate = match self.state_stack.pop() {
+            Some(state) => state,
+            None => {
+                debug_assert!(false, "State stack became unbalanced");
+                self.state_stack
+                    .push(Error(LoadError::UnexpectedEvent(mark)));
+                return;
+            }
+        };
         let newstate = match ev {
-            Event::Alias(_) => unreachable!(),
+            Event::Alias(aid) => match self.alias(aid, mark) {
+                Ok(node) => self.insert_node(curstate, node, mark),
+                Err(e) => Error(e),
+            },
             Event::StreamStart => {
-                assert_eq!(curstate, Initial);
+                debug_assert_eq!(curstate, Initial);
                 StartStream
             }
             Event::DocumentStart => {
-                assert_eq!(curstate, StartStream);
+                debug_assert_eq!(curstate, StartStream);
+                self.document_start = Some(mark);
                 StartDocument
             }
             Event::MappingStart(aid, tag) => {
-                if tag.is_some() {
+                let set = tag.as_ref().is_some_and(|tag| is_core_tag(tag, "set"));
+                let container = self.enter_container(mark, '{');
+                container.set = set;
+                container.anchor = aid;
+                if tag.is_some() && !set {
                     Error(LoadError::UnexpectedTag(mark))
-                } else if aid == 0 {
+                } else if aid == 0 || self.options.allow_aliases {
                     match curstate {
                         StartDocument => MappingWaitingOnKey(mark, MappingHash::new()),
-                        MappingWaitingOnKey(_, _) => Error(LoadError::MappingKeyMustBeScalar(mark)),
-                        MappingWaitingOnValue(_, _, _) => {
+                        MappingWaitingOnKey(_, _) if !self.options.allow_complex_keys => {
+                            Error(LoadError::MappingKeyMustBeScalar(mark))
+                        }
+                        MappingWaitingOnKey(_, _) | MappingWaitingOnValue(_, _, _) => {
                             self.state_stack.push(curstate);
                             MappingWaitingOnKey(mark, MappingHash::new())
                         }
@@ -145,7 +354,7 @@ impl MarkedEventReceiver for MarkedLoader {
                             self.state_stack.push(curstate);
                             MappingWaitingOnKey(mark, MappingHash::new())
                         }
-                        _ => unreachable!(),
+                        state => Error(unexpected_event(mark, &state)),
                     }
                 } else {
                     Error(LoadError::UnexpectedAnchor(mark))
@@ -154,47 +363,29 @@ impl MarkedEventReceiver for MarkedLoader {
             Event::MappingEnd => match curstate {
                 MappingWaitingOnKey(startmark, map) => {
                     let span = Span::new_with_marks(startmark, mark);
-                    let node = Node::from(MarkedMappingNode::new(span, map));
-                    if let Some(topstate) = self.state_stack.pop() {
-                        match topstate {
-                            MappingWaitingOnValue(mark, mut map, key) => {
-                                match map.entry(key.clone()) {
-                                    Entry::Occupied(entry)
-                                        if self.options.error_on_duplicate_keys =>
-                                    {
-                                        Error(LoadError::DuplicateKey(Box::new(
-                                            DuplicateKeyInner {
-                                                prev_key: entry.key().clone(),
-                                                key,
-                                            },
-                                        )))
-                                    }
-                                    _ => {
-                                        map.insert(key, node);
-                                        MappingWaitingOnKey(mark, map)
-                                    }
-                                }
-                            }
-                            SequenceWaitingOnValue(mark, mut list) => {
-                                list.push(node);
-                                SequenceWaitingOnValue(mark, list)
-                            }
-                            _ => unreachable!(),
-                        }
-                    } else {
-                        Finished(node)
-                    }
+                    let mut mapping = MarkedMappingNode::new(span, map);
+                    let container = self.leave_container();
+                    mapping.set_flow(container.flow);
+                    mapping.set_is_set(container.set);
+                    let node = self.anchored(container.anchor, Node::from(mapping));
+                    self.complete(node, mark)
                 }
-                _ => unreachable!(),
+                state => Error(unexpected_event(mark, &state)),
             },
             Event::SequenceStart(aid, tag) => {
+                self.enter_container(mark, '[').anchor = aid;
                 if tag.is_some() {
                     Error(LoadError::UnexpectedTag(mark))
-                } else if aid == 0 {
+                } else if aid == 0 || self.options.allow_aliases {
                     match curstate {
+                        StartDocument if self.options.allow_non_mapping_top_level => {
+                            SequenceWaitingOnValue(mark, Vec::new())
+                        }
                         StartDocument => Error(LoadError::TopLevelMustBeMapping(mark)),
-                        MappingWaitingOnKey(_, _) => Error(LoadError::MappingKeyMustBeScalar(mark)),
-                        mv @ MappingWaitingOnValue(_, _, _) => {
+                        MappingWaitingOnKey(_, _) if !self.options.allow_complex_keys => {
+                            Error(LoadError::MappingKeyMustBeScalar(mark))
+                        }
+                        mv @ (MappingWaitingOnKey(_, _) | MappingWaitingOnValue(_, _, _)) => {
                             self.state_stack.push(mv);
                             SequenceWaitingOnValue(mark, Vec::new())
                         }
@@ -202,7 +393,7 @@ impl MarkedEventReceiver for MarkedLoader {
                             self.state_stack.push(sv);
                             SequenceWaitingOnValue(mark, Vec::new())
                         }
-                        _ => unreachable!(),
+                        state => Error(unexpected_event(mark, &state)),
                     }
                 } else {
                     Error(LoadError::UnexpectedAnchor(mark))
@@ -211,103 +402,104 @@ impl MarkedEventReceiver for MarkedLoader {
             Event::SequenceEnd => match curstate {
                 SequenceWaitingOnValue(startmark, list) => {
                     let span = Span::new_with_marks(startmark, mark);
-                    let node = Node::from(MarkedSequenceNode::new(span, list));
-                    if let Some(topstate) = self.state_stack.pop() {
-                        match topstate {
-                            MappingWaitingOnValue(mark, mut map, key) => {
-                                match map.entry(key.clone()) {
-                                    Entry::Occupied(entry)
-                                        if self.options.error_on_duplicate_keys =>
-                                    {
-                                        Error(LoadError::DuplicateKey(Box::new(
-                                            DuplicateKeyInner {
-                                                prev_key: entry.key().clone(),
-                                                key,
-                                            },
-                                        )))
-                                    }
-                                    _ => {
-                                        map.insert(key, node);
-                                        MappingWaitingOnKey(mark, map)
-                                    }
-                                }
-                            }
-                            SequenceWaitingOnValue(mark, mut list) => {
-                                list.push(node);
-                                SequenceWaitingOnValue(mark, list)
-                            }
-                            _ => unreachable!(),
-                        }
-                    } else {
-                        unreachable!()
-                    }
+                    let mut sequence = MarkedSequenceNode::new(span, list);
+                    let container = self.leave_container();
+                    sequence.set_flow(container.flow);
+                    let node = self.anchored(container.anchor, Node::from(sequence));
+                    self.complete(node, mark)
                 }
-                _ => unreachable!(),
+                state => Error(unexpected_event(mark, &state)),
             },
             Event::DocumentEnd => match curstate {
-                Finished(_) => curstate,
-                _ => unreachable!(),
+                Finished(node) => {
+                    let mut span = Span::new_blank();
+                    span.set_start(self.document_start.take());
+                    span.set_end(Some(mark));
+                    self.documents.push((node, span));
+                    StartStream
+                }
+                state => Error(unexpected_event(mark, &state)),
             },
             Event::StreamEnd => match curstate {
-                StartStream => Finished(Node::from(MarkedMappingNode::new_empty(
-                    Span::new_with_marks(mark, mark),
-                ))),
-                Finished(_) => curstate,
-                _ => unreachable!(),
+                StartStream => {
+                    if self.documents.is_empty() && !self.multi_document {
+                        // An empty single document is treated as an empty mapping
+                        let span = Span::new_with_marks(mark, mark);
+                        let node = Node::from(MarkedMappingNode::new_empty(span));
+                        self.documents.push((node, span));
+                    }
+                    StartStream
+                }
+                state => Error(unexpected_event(mark, &state)),
             },
-            Event::Scalar(val, _kind, aid, tag) => {
-                if aid == 0 {
-                    if tag.is_some() {
+            Event::Scalar(val, kind, aid, tag) => {
+                if aid == 0 || self.options.allow_aliases {
+                    let binary = tag.as_ref().is_some_and(|tag| is_core_tag(tag, "binary"));
+                    if tag.is_some() && !binary {
                         Error(LoadError::UnexpectedTag(mark))
                     } else {
                         let span = Span::new_start(mark);
-                        let node = MarkedScalarNode::new(span, val);
-                        match curstate {
-                            MappingWaitingOnKey(mark, map) => {
-                                MappingWaitingOnValue(mark, map, node)
-                            }
-                            MappingWaitingOnValue(mark, mut map, key) => {
-                                match map.entry(key.clone()) {
-                                    Entry::Occupied(entry)
-                                        if self.options.error_on_duplicate_keys =>
-                                    {
-                                        Error(LoadError::DuplicateKey(Box::new(
-                                            DuplicateKeyInner {
-                                                prev_key: entry.key().clone(),
-                                                key,
-                                            },
-                                        )))
-                                    }
-                                    _ => {
-                                        map.insert(key, Node::from(node));
-                                        MappingWaitingOnKey(mark, map)
-                                    }
-                                }
-                            }
-                            SequenceWaitingOnValue(mark, mut list) => {
-                                list.push(Node::from(node));
-                                SequenceWaitingOnValue(mark, list)
-                            }
-                            StartDocument => Error(LoadError::TopLevelMustBeMapping(mark)),
-                            _ => unreachable!(),
+                        let plain = matches!(kind, TScalarStyle::Plain);
+                        let mut node = MarkedScalarNode::new(span, val);
+                        node.set_coerce(plain && !binary);
+                        node.set_binary(binary);
+                        if plain
+                            && self.warnings.is_some()
+                            && is_yaml11_boolean(node.as_str())
+                            && node.as_bool().is_none()
+                        {
+                            self.warn(span, WarningKind::AmbiguousBoolean);
+                        }
+                        node.set_style(match kind {
+                            TScalarStyle::Plain => ScalarStyle::Plain,
+                            TScalarStyle::SingleQuoted => ScalarStyle::SingleQuoted,
+                            TScalarStyle::DoubleQuoted => ScalarStyle::DoubleQuoted,
+                            TScalarStyle::Literal => ScalarStyle::Literal,
+                            _ => ScalarStyle::Folded,
+                        });
+                        if matches!(node.style(), ScalarStyle::Literal | ScalarStyle::Folded) {
+                            let (chomping, indent) = self.block_header(mark);
+                            node.set_block_chomping(chomping);
+                            node.set_explicit_indent(indent);
                         }
+                        let node = self.anchored(aid, Node::from(node));
+                        self.insert_node(curstate, node, mark)
                     }
                 } else {
                     Error(LoadError::UnexpectedAnchor(mark))
                 }
             }
-            Event::Nothing => unreachable!(),
+            Event::Nothing => Error(unexpected_event(mark, &curstate)),
         };
         self.state_stack.push(newstate);
     }
 }
 
-impl MarkedLoader {
-    fn new(source: usize, options: LoaderOptions) -> Self {
+impl<'a> MarkedLoader<'a> {
+    fn new(source: usize, text: &'a str, options: LoaderOptions, multi_document: bool) -> Self {
+        // Editors on some platforms start files with a byte order mark, which
+        // isn't part of the content and so isn't counted as a column
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
         Self {
             source,
+            index: LineIndex::new(source, text),
+            containers: Vec::new(),
             state_stack: vec![Initial],
             options,
+            multi_document,
+            document_start: None,
+            documents: Vec::new(),
+            nodes: 0,
+            warnings: None,
+            anchors: HashMap::new(),
+            expanded: 0,
+            anchor_names: None,
+        }
+    }
+
+    fn warn(&mut self, span: Span, kind: WarningKind) {
+        if let Some(warnings) = &mut self.warnings {
+            warnings.push(Warning::new(span, kind));
         }
     }
 
@@ -315,14 +507,238 @@ impl MarkedLoader {
         Marker::new(self.source, mark.line(), mark.col() + 1)
     }
 
-    fn finish(mut self) -> Result<Node, LoadError> {
-        let top = self.state_stack.pop();
-        match top.expect("YAML parser state stack unexpectedly empty") {
-            Finished(n) => Ok(n),
-            Error(e) => Err(e),
-            _ => unreachable!(),
+    // The parser doesn't tell us whether containers are flow or block style,
+    // but flow containers start at their opening bracket whereas block ones
+    // start at their first entry.  Anything inside a flow container is also
+    // flow style, even if it's an implicit single pair mapping such as the
+    // one in `[a: b]`.
+    fn enter_container(&mut self, mark: Marker, opener: char) -> &mut Container {
+        let flow = self
+            .containers
+            .last()
+            .is_some_and(|container| container.flow)
+            || self
+                .index
+                .marker_to_offset(&mark)
+                .and_then(|offset| self.index.text()[offset..].chars().next())
+                == Some(opener);
+        self.containers.push(Container {
+            flow,
+            ..Container::default()
+        });
+        self.containers
+            .last_mut()
+            .expect("container was just pushed")
+    }
+
+    // The parser doesn't tell us about block scalar headers either, and
+    // block scalars start at their first line of content rather than at
+    // their `|` or `>`.  So the header is at the end of the last non-blank
+    // line before that, unless the scalar is empty and at the end of the
+    // input, in which case it starts at its indicator instead.
+    fn block_header(&self, mark: Marker) -> (Chomping, Option<usize>) {
+        let text = self.index.text();
+        let offset = self.index.marker_to_offset(&mark).unwrap_or(text.len());
+        let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let line = if text[line_start..offset].trim().is_empty() {
+            let before = text[..line_start].trim_end();
+            &before[before.rfind('\n').map_or(0, |i| i + 1)..]
+        } else {
+            let end = text[offset..].find('\n').map_or(text.len(), |i| offset + i);
+            &text[line_start..end]
+        };
+        line.char_indices()
+            .filter(|(_, c)| matches!(c, '|' | '>'))
+            .find_map(|(i, _)| parse_block_indicators(&line[i + 1..]))
+            .unwrap_or((Chomping::Clip, None))
+    }
+
+    fn leave_container(&mut self) -> Container {
+        self.containers.pop().unwrap_or_default()
+    }
+
+    // A container has ended, so put it into whatever contains it, unless
+    // it's the whole document
+    fn complete(&mut self, node: Node, mark: Marker) -> LoaderState {
+        match self.state_stack.pop() {
+            Some(state) => self.insert_node(state, node, mark),
+            None => Finished(node),
+        }
+    }
+
+    // Put a node which is complete into whatever contains it, giving the
+    // state to carry on in
+    fn insert_node(&mut self, state: LoaderState, node: Node, mark: Marker) -> LoaderState {
+        match state {
+            MappingWaitingOnKey(start, map) => match node {
+                Node::Scalar(key) => MappingWaitingOnValue(start, map, key),
+                node if self.options.allow_complex_keys => {
+                    let mut key = MarkedScalarNode::new(*node.span(), complex_key_text(&node));
+                    key.set_coerce(false);
+                    key.set_complex_key(Some(node));
+                    MappingWaitingOnValue(start, map, key)
+                }
+                _ => Error(LoadError::MappingKeyMustBeScalar(mark)),
+            },
+            MappingWaitingOnValue(start, mut map, key) => match map.entry(key.clone()) {
+                Entry::Occupied(entry) if self.options.error_on_duplicate_keys => {
+                    Error(LoadError::DuplicateKey(Box::new(DuplicateKeyInner {
+                        prev_key: entry.key().clone(),
+                        key,
+                    })))
+                }
+                _ => {
+                    if map.contains_key(&key) {
+                        self.warn(*key.span(), WarningKind::DuplicateKey);
+                    }
+                    map.insert(key, node);
+                    MappingWaitingOnKey(start, map)
+                }
+            },
+            SequenceWaitingOnValue(start, mut list) => {
+                list.push(node);
+                SequenceWaitingOnValue(start, list)
+            }
+            StartDocument if self.options.allow_non_mapping_top_level || node.is_mapping() => {
+                Finished(node)
+            }
+            StartDocument => Error(LoadError::TopLevelMustBeMapping(mark)),
+            state => Error(unexpected_event(mark, &state)),
+        }
+    }
+
+    // Remember a node which has an anchor defined on it, so that aliases to
+    // the anchor can be loaded as copies of it
+    fn anchored(&mut self, aid: usize, mut node: Node) -> Node {
+        if aid != 0 {
+            node.set_anchor(self.anchor_name(aid));
+            let (scalars, mappings, sequences) = node.count_by_kind();
+            let size = scalars + mappings + sequences;
+            self.anchors.insert(aid, (node.clone(), size));
+        }
+        node
+    }
+
+    // Aliases are loaded as copies of the node which their anchor is defined
+    // on.  The anchor must be complete, so a node can't contain an alias to
+    // itself.  The size of the copy is checked before making it.
+    fn alias(&mut self, aid: usize, mark: Marker) -> Result<Node, LoadError> {
+        let (anchored, size) = self
+            .anchors
+            .get(&aid)
+            .ok_or(LoadError::UnexpectedAnchor(mark))?;
+        self.expanded += size;
+        if self
+            .options
+            .max_alias_expansion
+            .is_some_and(|max| self.expanded > max)
+        {
+            return Err(LoadError::ExpansionLimitExceeded(Span::new_start(mark)));
+        }
+        let mut node = anchored.clone();
+        node.set_alias_of(anchored.anchor().map(str::to_string));
+        node.set_anchor(None);
+        *node.span_mut() = Span::new_start(mark);
+        Ok(node)
+    }
+
+    // The parser only tells us the number of each anchor, counting up from
+    // one through the whole stream, so find their names by scanning for them
+    fn anchor_name(&mut self, aid: usize) -> Option<String> {
+        let text = self.index.text();
+        let names = self.anchor_names.get_or_insert_with(|| {
+            Scanner::new(text.chars())
+                .filter_map(|Token(_, token)| match token {
+                    TokenType::Anchor(name) => Some(name),
+                    _ => None,
+                })
+                .collect()
+        });
+        names.get(aid - 1).cloned()
+    }
+
+    fn finish(mut self) -> Result<Documents, LoadError> {
+        let end = self.index.offset_to_marker(self.index.text().len());
+        match self.state_stack.pop() {
+            Some(StartStream) => Ok(self.documents),
+            Some(Error(e)) => Err(e),
+            Some(state) => Err(unexpected_event(end, &state)),
+            None => {
+                debug_assert!(false, "YAML parser state stack unexpectedly empty");
+                Err(LoadError::UnexpectedEvent(end))
+            }
+        }
+    }
+
+    fn load(self) -> Result<Documents, LoadError> {
+        self.load_with_warnings().map(|(docs, _)| docs)
+    }
+
+    fn load_with_warnings(mut self) -> Result<(Documents, Vec<Warning>), LoadError> {
+        let text = self.index.text();
+        let mut parser = Parser::new(text.chars());
+        let multi_document = self.multi_document;
+        parser.load(&mut self, multi_document).map_err(|se| {
+            let mark = locate_indentation_tab(text, self.marker(*se.marker()));
+            LoadError::ScanError(mark, se)
+        })?;
+        let warnings = self.warnings.take().unwrap_or_default();
+        self.finish().map(|docs| (docs, warnings))
+    }
+}
+
+// Only a couple of tags are permitted, which are recorded on the node rather
+// than changing how it loads: `!!set` on mappings whose values are all null,
+// and `!!binary` on scalars which hold base64 data.
+fn is_core_tag(tag: &Tag, name: &str) -> bool {
+    matches!(tag.handle.as_str(), "!!" | "tag:yaml.org,2002:") && tag.suffix == name
+}
+
+// Parse what follows a block scalar's `|` or `>`, which must be at most one
+// chomping and one indentation indicator, optionally followed by a comment.
+fn parse_block_indicators(rest: &str) -> Option<(Chomping, Option<usize>)> {
+    let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+    let (indicators, comment) = rest.split_at(len);
+    let comment = comment.trim_start();
+    if len > 2 || !(comment.is_empty() || comment.starts_with('#')) {
+        return None;
+    }
+    let mut chomping = Chomping::Clip;
+    let mut indent = None;
+    for c in indicators.chars() {
+        match c {
+            '-' => chomping = Chomping::Strip,
+            '+' => chomping = Chomping::Keep,
+            '1'..='9' => indent = c.to_digit(10).map(|d| d as usize),
+            _ => return None,
         }
     }
+    Some((chomping, indent))
+}
+
+// yaml-rust reports tabs used as indentation at the point where it gave up,
+// which is after the tabs.  If an error is preceded on its line only by
+// whitespace which includes a tab, point at the first tab instead so that
+// renderers can show the user exactly what is wrong.
+fn locate_indentation_tab(yaml: &str, mark: Marker) -> Marker {
+    let tab = yaml
+        .lines()
+        .nth(mark.line().saturating_sub(1))
+        .filter(|line| {
+            line.chars()
+                .take(mark.column().saturating_sub(1))
+                .all(char::is_whitespace)
+        })
+        .and_then(|line| {
+            line.chars()
+                .take(mark.column())
+                .take_while(|c| c.is_whitespace())
+                .position(|c| c == '\t')
+        });
+    match tab {
+        Some(col) => Marker::new(mark.source(), mark.line(), col + 1),
+        None => mark,
+    }
 }
 
 /// Parse YAML from a string and return a Node representing
@@ -338,6 +754,11 @@ impl MarkedLoader {
 /// for callers to use.  Regardless, it's always possible to treat the
 /// returned node as a mapping node without risk of panic.
 ///
+/// A byte order mark at the very start of the text is ignored, and is not
+/// counted as a column in markers.  Only the first document in the text is
+/// loaded, so a trailing `...` or `---` marker, as some editors and tools
+/// write, is accepted.  Use [`parse_yaml_multi`] to load every document.
+///
 /// ```
 /// # use marked_yaml::*;
 /// let node = parse_yaml(0, include_str!("../examples/everything.yaml"))
@@ -349,17 +770,25 @@ pub fn parse_yaml<S>(source: usize, yaml: S) -> Result<Node, LoadError>
 where
     S: AsRef<str>,
 {
-    let options = LoaderOptions {
-        error_on_duplicate_keys: false,
-    };
-    parse_yaml_with_options(source, yaml, options)
+    parse_yaml_with_options(source, yaml, LoaderOptions::default())
 }
 
 /// Parse YAML from a string and return a Node representing
 /// the content.
 /// Takes an additional LoaderOptions struct to control the behavior of the loader.
 ///
-/// See `parse_yaml` for more information.
+/// See `parse_yaml` for more information.  Note that if the options permit a
+/// non-mapping top level then the returned node may be of any kind.
+///
+/// ```
+/// # use marked_yaml::*;
+/// let options = LoaderOptions {
+///     allow_non_mapping_top_level: true,
+///     ..LoaderOptions::default()
+/// };
+/// let node = parse_yaml_with_options(0, "42", options).unwrap();
+/// assert_eq!(node.as_scalar().unwrap().as_u32(), Some(42));
+/// ```
 pub fn parse_yaml_with_options<S>(
     source: usize,
     yaml: S,
@@ -368,19 +797,508 @@ pub fn parse_yaml_with_options<S>(
 where
     S: AsRef<str>,
 {
-    let mut loader = MarkedLoader::new(source, options);
+    MarkedLoader::new(source, yaml.as_ref(), options, false)
+        .load()
+        .and_then(first_document)
+}
+
+// Single document parsing stops after the first document, of which there
+// is always one since empty input loads as an empty mapping
+fn first_document(docs: Documents) -> Result<Node, LoadError> {
+    docs.into_iter()
+        .next()
+        .map(|(node, _)| node)
+        .ok_or(LoadError::NoDocument)
+}
+
+/// Parse a YAML string, also returning warnings about suspicious content
+///
+/// This behaves like [`parse_yaml`], but also reports things which are
+/// legal YAML yet may not mean what their author intended.  Currently these
+/// are:
+///
+/// * [`WarningKind::DuplicateKey`] for a key which appears more than once in
+///   a mapping, located at the later key, whose value replaces the earlier.
+/// * [`WarningKind::AmbiguousBoolean`] for a plain scalar such as `no` or
+///   `On` which YAML 1.1 reads as a boolean but which is a string here.
+///
+/// Warnings are only gathered by this function, so [`parse_yaml`] does no
+/// extra work.
+///
+/// ```
+/// # use marked_yaml::*;
+/// # use marked_yaml::types::WarningKind;
+/// let (node, warnings) = parse_yaml_with_warnings(0, "country: NO\n").unwrap();
+/// assert_eq!(node.as_mapping().unwrap().get_scalar("country").unwrap().as_str(), "NO");
+/// assert_eq!(warnings[0].kind(), WarningKind::AmbiguousBoolean);
+/// assert_eq!(warnings[0].span().start(), Some(&Marker::new(0, 1, 10)));
+/// ```
+pub fn parse_yaml_with_warnings<S>(
+    source: usize,
+    yaml: S,
+) -> Result<(Node, Vec<Warning>), LoadError>
+where
+    S: AsRef<str>,
+{
+    let mut loader = MarkedLoader::new(source, yaml.as_ref(), LoaderOptions::default(), false);
+    loader.warnings = Some(Vec::new());
+    let (docs, warnings) = loader.load_with_warnings()?;
+    Ok((first_document(docs)?, warnings))
+}
+
+/// A parsed document kept together with the text it was parsed from
+///
+/// Spans only give lines and columns, so recovering the text of a node
+/// needs the source text and a [`LineIndex`] over it.  This bundles both
+/// with the node so that callers don't have to keep track of them
+/// separately.  See [`parse_yaml_document()`].
+#[derive(Clone, Debug)]
+pub struct ParsedDocument {
+    node: Node,
+    source: usize,
+    text: String,
+    line_starts: Vec<usize>,
+}
+
+impl ParsedDocument {
+    /// The root node of the document
+    pub fn node(&self) -> &Node {
+        &self.node
+    }
+
+    /// Take the root node, discarding the text
+    pub fn into_node(self) -> Node {
+        self.node
+    }
+
+    /// The text the document was parsed from
+    ///
+    /// A byte order mark at the start of the text is not included, since
+    /// it is not counted in markers either.
+    pub fn source_text(&self) -> &str {
+        &self.text
+    }
+
+    /// An index over the document's text, for converting between markers
+    /// and offsets
+    pub fn line_index(&self) -> LineIndex<'_> {
+        LineIndex::with_line_starts(self.source, &self.text, &self.line_starts)
+    }
+
+    /// The text from which the given node was parsed
+    ///
+    /// Flow collections include their brackets and quoted scalars include
+    /// their quotes.  Block collections run up to the start of whatever
+    /// follows them, less any trailing whitespace, so may include trailing
+    /// comments.  This is `None` for nodes which did not come from this
+    /// document, and for block scalars and plain scalars spanning several
+    /// lines, whose extent can't be told from their spans.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let doc = parse_yaml_document(0, "ports: [80, 443]\nname: 'web'\n").unwrap();
+    /// let map = doc.node().as_mapping().unwrap();
+    /// assert_eq!(doc.node_text(map.get_node("ports").unwrap()), Some("[80, 443]"));
+    /// assert_eq!(doc.node_text(map.get_node("name").unwrap()), Some("'web'"));
+    /// ```
+    pub fn node_text(&self, node: &Node) -> Option<&str> {
+        let index = self.line_index();
+        let start = match node {
+            // Block mappings start at the `:` after their first key
+            Node::Mapping(map) if !map.is_flow() => map.keys().next()?.span().start()?,
+            _ => node.span().start()?,
+        };
+        let start = index.marker_to_offset(start)?;
+        let rest = &self.text[start..];
+        let flow = match node {
+            Node::Scalar(scalar) => return rest.get(..scalar_text_len(scalar, rest)?),
+            Node::Mapping(map) => map.is_flow(),
+            Node::Sequence(seq) => seq.is_flow(),
+        };
+        let end = index.marker_to_offset(node.span().end()?)?;
+        let len = end.checked_sub(start)?;
+        if flow {
+            // Flow collections end at their closing bracket
+            rest.get(..=len)
+        } else {
+            rest.get(..len).map(str::trim_end)
+        }
+    }
+}
+
+// The length of the text of a scalar which starts `rest`, if it can be told
+fn scalar_text_len(scalar: &MarkedScalarNode, rest: &str) -> Option<usize> {
+    match scalar.style() {
+        ScalarStyle::Plain => rest
+            .starts_with(scalar.as_str())
+            .then_some(scalar.as_str().len()),
+        ScalarStyle::SingleQuoted => {
+            let mut chars = rest.char_indices().skip(1).peekable();
+            while let Some((i, c)) = chars.next() {
+                if c == '\'' {
+                    if chars.peek().map(|&(_, c)| c) != Some('\'') {
+                        return Some(i + 1);
+                    }
+                    chars.next();
+                }
+            }
+            None
+        }
+        ScalarStyle::DoubleQuoted => {
+            let mut chars = rest.char_indices().skip(1);
+            while let Some((i, c)) = chars.next() {
+                match c {
+                    '"' => return Some(i + 1),
+                    '\\' => {
+                        chars.next();
+                    }
+                    _ => {}
+                }
+            }
+            None
+        }
+        ScalarStyle::Literal | ScalarStyle::Folded => None,
+    }
+}
+
+/// Parse YAML from a string, keeping the text alongside the node
+///
+/// This behaves like [`parse_yaml`], but takes ownership of the text and
+/// returns it in a [`ParsedDocument`] with the node, so that the text of
+/// any node can be retrieved later.
+///
+/// ```
+/// # use marked_yaml::*;
+/// let doc = parse_yaml_document(0, String::from("server:\n  port: 8080\n")).unwrap();
+/// let server = doc.node().as_mapping().unwrap().get_node("server").unwrap();
+/// assert_eq!(doc.node_text(server), Some("port: 8080"));
+/// ```
+pub fn parse_yaml_document<S>(source: usize, yaml: S) -> Result<ParsedDocument, LoadError>
+where
+    S: Into<String>,
+{
+    let mut text = yaml.into();
+    if text.starts_with('\u{feff}') {
+        text.drain(..'\u{feff}'.len_utf8());
+    }
+    let node = parse_yaml(source, &text)?;
+    let line_starts = LineIndex::new(source, &text).into_line_starts();
+    Ok(ParsedDocument {
+        node,
+        source,
+        text,
+        line_starts,
+    })
+}
+
+/// Parse YAML from raw bytes, detecting and decoding the text encoding
+///
+/// The encoding is determined from the first bytes of the input as
+/// described by the YAML specification, so UTF-8, UTF-16, and UTF-32 are
+/// all accepted, with or without a byte order mark.  Without any of these
+/// indications the input is assumed to be UTF-8.  If the input is not
+/// validly encoded then [`LoadError::InvalidEncoding`] is returned.
+///
+/// Markers in the returned node refer to lines and columns of the decoded
+/// text, and a byte order mark is not counted as a column.
+///
+/// See `parse_yaml` for more information.
+///
+/// ```
+/// # use marked_yaml::*;
+/// let bytes: Vec<u8> = "\u{feff}key: value"
+///     .encode_utf16()
+///     .flat_map(u16::to_le_bytes)
+///     .collect();
+/// let node = parse_yaml_bytes(0, bytes).unwrap();
+/// let value = node.as_mapping().unwrap().get_node("key").unwrap();
+/// assert_eq!(value.span().start(), Some(&Marker::new(0, 1, 6)));
+/// ```
+pub fn parse_yaml_bytes<B>(source: usize, bytes: B) -> Result<Node, LoadError>
+where
+    B: AsRef<[u8]>,
+{
+    parse_yaml_bytes_with_options(source, bytes, LoaderOptions::default())
+}
+
+/// Parse YAML from raw bytes, detecting and decoding the text encoding
+/// Takes an additional LoaderOptions struct to control the behavior of the loader.
+///
+/// See `parse_yaml_bytes` for more information.
+pub fn parse_yaml_bytes_with_options<B>(
+    source: usize,
+    bytes: B,
+    options: LoaderOptions,
+) -> Result<Node, LoadError>
+where
+    B: AsRef<[u8]>,
+{
+    let text = decode_yaml_bytes(source, bytes.as_ref())?;
+    parse_yaml_with_options(source, text, options)
+}
+
+// Detect the encoding as per section 5.2 of the YAML 1.2 specification,
+// which looks at the byte order mark if there is one, and otherwise at
+// where the null bytes are, since the first character must be ASCII.
+fn decode_yaml_bytes(source: usize, bytes: &[u8]) -> Result<Cow<'_, str>, LoadError> {
+    let utf16 = |rest: &[u8], unit: fn([u8; 2]) -> u16| {
+        let units = rest.chunks_exact(2);
+        let truncated = !units.remainder().is_empty();
+        let chars = char::decode_utf16(units.map(|u| unit([u[0], u[1]]))).map(Result::ok);
+        decode_chars(source, chars.chain(truncated.then_some(None)))
+    };
+    let utf32 = |rest: &[u8], unit: fn([u8; 4]) -> u32| {
+        let units = rest.chunks_exact(4);
+        let truncated = !units.remainder().is_empty();
+        let chars = units.map(|u| char::from_u32(unit([u[0], u[1], u[2], u[3]])));
+        decode_chars(source, chars.chain(truncated.then_some(None)))
+    };
+    match bytes {
+        [0, 0, 0xfe, 0xff, rest @ ..] => utf32(rest, u32::from_be_bytes),
+        [0, 0, 0, _, ..] => utf32(bytes, u32::from_be_bytes),
+        [0xff, 0xfe, 0, 0, rest @ ..] => utf32(rest, u32::from_le_bytes),
+        [_, 0, 0, 0, ..] => utf32(bytes, u32::from_le_bytes),
+        [0xfe, 0xff, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        [0, _, ..] => utf16(bytes, u16::from_be_bytes),
+        [0xff, 0xfe, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [_, 0, ..] => utf16(bytes, u16::from_le_bytes),
+        [0xef, 0xbb, 0xbf, rest @ ..] => decode_utf8(source, rest),
+        _ => decode_utf8(source, bytes),
+    }
+}
+
+fn decode_utf8(source: usize, bytes: &[u8]) -> Result<Cow<'_, str>, LoadError> {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => Ok(Cow::Borrowed(text)),
+        Err(e) => {
+            // Everything before the error is valid, so this can't fail
+            let valid = std::str::from_utf8(&bytes[..e.valid_up_to()]).unwrap_or_default();
+            Err(invalid_encoding(source, valid))
+        }
+    }
+}
+
+fn decode_chars<I>(source: usize, chars: I) -> Result<Cow<'static, str>, LoadError>
+where
+    I: Iterator<Item = Option<char>>,
+{
+    let mut text = String::new();
+    for ch in chars {
+        match ch {
+            Some(ch) => text.push(ch),
+            None => return Err(invalid_encoding(source, &text)),
+        }
+    }
+    Ok(Cow::Owned(text))
+}
+
+// The error is located just after the text which decoded successfully
+fn invalid_encoding(source: usize, decoded: &str) -> LoadError {
+    LoadError::InvalidEncoding(LineIndex::new(source, decoded).offset_to_marker(decoded.len()))
+}
+
+/// Parse YAML from a string which has a name rather than a source identifier
+///
+/// This is a convenience for one-off parses, such as of snippets which
+/// aren't tied to a file.  A fresh [`SourceRegistry`] is created, the name
+/// is registered in it, and the YAML is parsed using the allocated source
+/// identifier.  The registry can then be used to render markers from the
+/// returned node.
+///
+/// See `parse_yaml` for more information.
+///
+/// ```
+/// # use marked_yaml::*;
+/// let (node, registry) = parse_yaml_named("snippet", "hello: world").unwrap();
+/// let mark = node.as_mapping().unwrap().get_node("hello").unwrap().span().start().unwrap();
+/// assert_eq!(format!("{}", registry.render(*mark)), "snippet:1:8");
+/// ```
+pub fn parse_yaml_named<S>(
+    name: impl Into<String>,
+    yaml: S,
+) -> Result<(Node, SourceRegistry), LoadError>
+where
+    S: AsRef<str>,
+{
+    let mut registry = SourceRegistry::new();
+    let source = registry.add(name);
+    let node = parse_yaml(source, yaml)?;
+    Ok((node, registry))
+}
+
+/// Parse a stream of YAML documents from a string
+///
+/// Each document in the stream is returned along with the span which it
+/// covers.  If the document was introduced with a `---` marker then the
+/// span starts at that marker, otherwise it starts at the first token of
+/// the document.  The span ends where the end of the document was detected,
+/// which is either the `...` marker, the `---` of the next document, or the
+/// end of the stream.
+///
+/// Every document is subject to the same constraints as in `parse_yaml`,
+/// in particular each must have a mapping at its top level.  An empty
+/// stream has no documents at all.
+///
+/// ```
+/// # use marked_yaml::*;
+/// let docs = parse_yaml_multi(0, "---\nfirst: 1\n---\nsecond: 2\n").unwrap();
+/// assert_eq!(docs.len(), 2);
+/// let (node, span) = &docs[1];
+/// assert!(node.as_mapping().unwrap().get_scalar("second").is_some());
+/// assert_eq!(span.start(), Some(&Marker::new(0, 3, 1)));
+/// ```
+pub fn parse_yaml_multi<S>(source: usize, yaml: S) -> Result<Vec<(Node, Span)>, LoadError>
+where
+    S: AsRef<str>,
+{
+    parse_yaml_multi_with_options(source, yaml, LoaderOptions::default())
+}
+
+/// Parse a stream of YAML documents from a string
+/// Takes an additional LoaderOptions struct to control the behavior of the loader.
+///
+/// See `parse_yaml_multi` for more information.
+pub fn parse_yaml_multi_with_options<S>(
+    source: usize,
+    yaml: S,
+    options: LoaderOptions,
+) -> Result<Vec<(Node, Span)>, LoadError>
+where
+    S: AsRef<str>,
+{
+    MarkedLoader::new(source, yaml.as_ref(), options, true).load()
+}
+
+/// A raw YAML parse event, as returned by [`debug_events`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MarkedEvent {
+    /// The start of the stream
+    StreamStart,
+    /// The end of the stream
+    StreamEnd,
+    /// The start of a document
+    DocumentStart,
+    /// The end of a document
+    DocumentEnd,
+    /// An alias, with the parser's identifier for the anchor it refers to
+    Alias(usize),
+    /// A scalar, with its value
+    Scalar(String),
+    /// The start of a sequence
+    SequenceStart,
+    /// The end of a sequence
+    SequenceEnd,
+    /// The start of a mapping
+    MappingStart,
+    /// The end of a mapping
+    MappingEnd,
+}
+
+struct EventCollector {
+    source: usize,
+    events: Vec<(MarkedEvent, Span)>,
+}
+
+impl MarkedEventReceiver for EventCollector {
+    fn on_event(&mut self, ev: Event, mark: YamlMarker) {
+        let event = match ev {
+            Event::Nothing => return,
+            Event::StreamStart => MarkedEvent::StreamStart,
+            Event::StreamEnd => MarkedEvent::StreamEnd,
+            Event::DocumentStart => MarkedEvent::DocumentStart,
+            Event::DocumentEnd => MarkedEvent::DocumentEnd,
+            Event::Alias(id) => MarkedEvent::Alias(id),
+            Event::Scalar(value, _, _, _) => MarkedEvent::Scalar(value),
+            Event::SequenceStart(_, _) => MarkedEvent::SequenceStart,
+            Event::SequenceEnd => MarkedEvent::SequenceEnd,
+            Event::MappingStart(_, _) => MarkedEvent::MappingStart,
+            Event::MappingEnd => MarkedEvent::MappingEnd,
+        };
+        let marker = Marker::new(self.source, mark.line(), mark.col() + 1);
+        self.events.push((event, Span::new_start(marker)));
+    }
+}
+
+/// Parse a stream of YAML into its raw events, for debugging
+///
+/// This exposes the events which the YAML parser produces, each with the
+/// place it was produced, before any of the loader's constraints are
+/// applied.  It's mostly useful for working out why a node has ended up
+/// with the span it has, and for reporting precise bugs about that.
+///
+/// ```
+/// # use marked_yaml::*;
+/// let events = debug_events(0, "a: [b]\n").unwrap();
+/// assert_eq!(
+///     events[4],
+///     (MarkedEvent::SequenceStart, Span::new_start(Marker::new(0, 1, 4)))
+/// );
+/// ```
+pub fn debug_events<S>(source: usize, yaml: S) -> Result<Vec<(MarkedEvent, Span)>, LoadError>
+where
+    S: AsRef<str>,
+{
+    let mut collector = EventCollector {
+        source,
+        events: Vec::new(),
+    };
     let mut parser = Parser::new(yaml.as_ref().chars());
-    parser.load(&mut loader, false).map_err(|se| {
-        let mark = loader.marker(*se.marker());
+    parser.load(&mut collector, true).map_err(|se| {
+        let mark = Marker::new(source, se.marker().line(), se.marker().col() + 1);
         LoadError::ScanError(mark, se)
     })?;
-    loader.finish()
+    Ok(collector.events)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
 
+    #[test]
+    fn raw_events() {
+        use MarkedEvent::*;
+        let events = debug_events(0, "a: [b]\n").unwrap();
+        let kinds: Vec<_> = events.iter().map(|(event, _)| event.clone()).collect();
+        assert_eq!(
+            kinds,
+            [
+                StreamStart,
+                DocumentStart,
+                MappingStart,
+                Scalar("a".into()),
+                SequenceStart,
+                Scalar("b".into()),
+                SequenceEnd,
+                MappingEnd,
+                DocumentEnd,
+                StreamEnd,
+            ]
+        );
+        let starts: Vec<_> = events[2..7]
+            .iter()
+            .map(|(_, span)| *span.start().unwrap())
+            .collect();
+        assert_eq!(
+            starts,
+            [
+                // Block mappings are reported at the `:` after their first key
+                Marker::new(0, 1, 2),
+                Marker::new(0, 1, 1),
+                Marker::new(0, 1, 4),
+                Marker::new(0, 1, 5),
+                Marker::new(0, 1, 6),
+            ]
+        );
+        // Events are reported even where the loader would refuse them
+        let events = debug_events(0, "- &x a\n- *x\n").unwrap();
+        assert!(events.iter().any(|(event, _)| matches!(event, Alias(_))));
+        assert!(matches!(
+            debug_events(0, "a: [b"),
+            Err(LoadError::ScanError(_, _))
+        ));
+    }
+
     #[test]
     fn smoke_basics() {
         let node = parse_yaml(0, "{}").unwrap();
@@ -428,6 +1346,66 @@ mod test {
         );
     }
 
+    #[test]
+    fn named_parse() {
+        let (node, registry) = parse_yaml_named("inline snippet", "key: value\n").unwrap();
+        let mark = node.span().start().unwrap();
+        assert_eq!(registry.name(mark.source()), Some("inline snippet"));
+        assert_eq!(registry.name(mark.source() + 1), None);
+        let err = parse_yaml_named("broken", "- a\n").err().unwrap();
+        assert!(matches!(err, LoadError::TopLevelMustBeMapping(_)));
+    }
+
+    #[test]
+    fn flow_and_block_styles() {
+        const FLOW: &str = "top: {map: {a: 1}, seq: [1, [2], b: 3]}\n";
+        const BLOCK: &str = "top:\n  map:\n    a: 1\n  seq:\n  - 1\n  - - 2\n  - b: 3\n";
+        let flow = parse_yaml(0, FLOW).unwrap();
+        let block = parse_yaml(0, BLOCK).unwrap();
+        assert_eq!(flow, block);
+
+        let top = flow.as_mapping().unwrap();
+        assert!(!top.is_flow());
+        let inner = top.get_mapping("top").unwrap();
+        assert!(inner.is_flow());
+        assert!(inner.get_mapping("map").unwrap().is_flow());
+        let seq = inner.get_sequence("seq").unwrap();
+        assert!(seq.is_flow());
+        assert!(seq.get_sequence(1).unwrap().is_flow());
+        assert!(seq.get_mapping(2).unwrap().is_flow());
+
+        let top = block.as_mapping().unwrap();
+        assert!(!top.is_flow());
+        let inner = top.get_mapping("top").unwrap();
+        assert!(!inner.is_flow());
+        assert!(!inner.get_mapping("map").unwrap().is_flow());
+        let seq = inner.get_sequence("seq").unwrap();
+        assert!(!seq.is_flow());
+        assert!(!seq.get_sequence(1).unwrap().is_flow());
+        assert!(!seq.get_mapping(2).unwrap().is_flow());
+    }
+
+    #[test]
+    fn toplevel_non_mapping_permitted() {
+        let options = || LoaderOptions {
+            allow_non_mapping_top_level: true,
+            ..LoaderOptions::default()
+        };
+        let node = parse_yaml_with_options(0, "foo", options()).unwrap();
+        assert_eq!(node.as_scalar().unwrap().as_str(), "foo");
+        assert_eq!(node.span().start(), Some(&Marker::new(0, 1, 1)));
+        let node = parse_yaml_with_options(0, "[a, [b], {c: d}]", options()).unwrap();
+        let seq = node.as_sequence().unwrap();
+        assert_eq!(seq.len(), 3);
+        assert_eq!(
+            seq.span(),
+            &Span::new_with_marks(Marker::new(0, 1, 1), Marker::new(0, 1, 16))
+        );
+        let docs = parse_yaml_multi_with_options(0, "--- 1\n--- [2]\n", options()).unwrap();
+        assert!(docs[0].0.as_scalar().is_some());
+        assert!(docs[1].0.as_sequence().is_some());
+    }
+
     #[test]
     fn duplicate_key() {
         let err = parse_yaml_with_options(
@@ -435,6 +1413,7 @@ mod test {
             "{foo: bar, foo: baz}",
             LoaderOptions {
                 error_on_duplicate_keys: true,
+                ..LoaderOptions::default()
             },
         );
 
@@ -464,6 +1443,199 @@ mod test {
         assert!(format!("{}", err.err().unwrap()).starts_with("1:6: "));
     }
 
+    #[test]
+    fn aliases() {
+        let options = || LoaderOptions {
+            allow_aliases: true,
+            ..LoaderOptions::default()
+        };
+        const DOC: &str = "base: &base {a: &a 1}\nboth: [*base, *a]\nb: &a 2\nc: *a\n";
+        let node = parse_yaml_with_options(0, DOC, options()).unwrap();
+        let map = node.as_mapping().unwrap();
+        let base = map.get_mapping("base").unwrap();
+        assert_eq!(base.anchor(), Some("base"));
+        assert_eq!(base.alias_of(), None);
+        let both = map.get_sequence("both").unwrap();
+        // An alias is a copy of its anchor, spanning just the alias itself
+        let copy = both[0].as_mapping().unwrap();
+        assert_eq!(copy, base);
+        assert_eq!(copy.alias_of(), Some("base"));
+        assert_eq!(copy.anchor(), None);
+        assert_eq!(copy.span(), &Span::new_start(Marker::new(0, 2, 8)));
+        assert_eq!(copy.get_scalar("a").unwrap().anchor(), Some("a"));
+        assert_eq!(both[1].alias_of(), Some("a"));
+        assert_eq!(both[1].as_scalar().unwrap().as_str(), "1");
+        // Redefining an anchor affects aliases after it
+        assert_eq!(map.get_scalar("c").unwrap().as_str(), "2");
+        assert_eq!(map.get_node("c").unwrap().alias_of(), Some("a"));
+        let anchors: Vec<_> = node
+            .anchors()
+            .into_iter()
+            .map(|(name, span)| (name, span.start().copied().unwrap()))
+            .collect();
+        assert_eq!(
+            anchors,
+            [
+                ("base", Marker::new(0, 1, 13)),
+                ("a", Marker::new(0, 1, 20)),
+                ("a", Marker::new(0, 3, 7)),
+            ]
+        );
+        // Keys can be aliased too, but must still be scalars
+        let node = parse_yaml_with_options(0, "&k a: *k\nb: {*k : c}\n", options()).unwrap();
+        assert_eq!(
+            node.pointer("/a").unwrap().as_scalar().unwrap().as_str(),
+            "a"
+        );
+        assert_eq!(
+            node.pointer("/b/a").unwrap().as_scalar().unwrap().as_str(),
+            "c"
+        );
+        assert_eq!(
+            parse_yaml_with_options(0, "a: &m {}\n*m : b\n", options()),
+            Err(LoadError::MappingKeyMustBeScalar(Marker::new(0, 2, 1)))
+        );
+        // Aliases within the node they refer to would never end
+        assert_eq!(
+            parse_yaml_with_options(0, "a: &r [*r]\n", options()),
+            Err(LoadError::UnexpectedAnchor(Marker::new(0, 1, 8)))
+        );
+    }
+
+    #[test]
+    fn complex_keys() {
+        const DOC: &str = "? [a, b]\n: 1\n{c: [d]}: 2\n[]: 3\n";
+        assert_eq!(
+            parse_yaml(0, DOC),
+            Err(LoadError::MappingKeyMustBeScalar(Marker::new(0, 1, 3)))
+        );
+        let options = || LoaderOptions {
+            allow_complex_keys: true,
+            error_on_duplicate_keys: true,
+            ..LoaderOptions::default()
+        };
+        let node = parse_yaml_with_options(0, DOC, options()).unwrap();
+        let map = node.as_mapping().unwrap();
+        let keys: Vec<_> = map
+            .keys()
+            .map(|key| (key.as_str(), key.span().start().copied().unwrap()))
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                ("[a, b]", Marker::new(0, 1, 3)),
+                ("{c: [d]}", Marker::new(0, 3, 1)),
+                ("[]", Marker::new(0, 4, 1)),
+            ]
+        );
+        let (key, value) = map.iter().next().unwrap();
+        assert_eq!(key.span().end(), Some(&Marker::new(0, 1, 8)));
+        let seq = key.complex_key().unwrap().as_sequence().unwrap();
+        assert_eq!(seq.get_scalar(1).unwrap().as_str(), "b");
+        assert_eq!(value.as_scalar().unwrap().as_str(), "1");
+        assert_eq!(map.get_scalar("{c: [d]}").unwrap().as_str(), "2");
+        assert_eq!(node.count_by_kind(), (7, 2, 3));
+        // Keys are the same if they're written the same in flow style
+        let err = parse_yaml_with_options(0, "[a, 'b']: 1\n? - a\n  - b\n: 2\n", options());
+        assert!(matches!(err, Err(LoadError::DuplicateKey(_))));
+    }
+
+    #[test]
+    fn alias_bomb() {
+        // By default anchors are refused outright, so a "billion laughs"
+        // document is rejected at its first anchor
+        let mut bomb = String::from("a: &a [lol, lol, lol, lol, lol, lol, lol, lol, lol]\n");
+        for (prev, name) in "abcdefgh".chars().zip("bcdefghi".chars()) {
+            let aliases = vec![format!("*{}", prev); 9].join(", ");
+            bomb.push_str(&format!("{}: &{} [{}]\n", name, name, aliases));
+        }
+        assert_eq!(
+            parse_yaml(0, &bomb),
+            Err(LoadError::UnexpectedAnchor(Marker::new(0, 1, 7)))
+        );
+        // When aliases are allowed it gets as far as the expansion limit,
+        // where `f` starts making copies of `e`
+        let aliases = |max_alias_expansion| LoaderOptions {
+            allow_aliases: true,
+            max_alias_expansion,
+            ..LoaderOptions::default()
+        };
+        let err = parse_yaml_with_options(0, &bomb, aliases(Some(100_000))).unwrap_err();
+        assert_eq!(
+            err,
+            LoadError::ExpansionLimitExceeded(Span::new_start(Marker::new(0, 6, 8)))
+        );
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 6, 8)));
+        assert_eq!(err.to_string(), "6:8: Aliases expanded into too many nodes");
+        // `b` copies `a`, with its nine items, nine times
+        const SMALL: &str =
+            "a: &a [1, 2, 3, 4, 5, 6, 7, 8, 9]\nb: [*a, *a, *a, *a, *a, *a, *a, *a, *a]\n";
+        assert!(parse_yaml_with_options(0, SMALL, aliases(Some(90))).is_ok());
+        assert_eq!(
+            parse_yaml_with_options(0, SMALL, aliases(Some(89))),
+            Err(LoadError::ExpansionLimitExceeded(Span::new_start(
+                Marker::new(0, 2, 37)
+            )))
+        );
+        assert!(parse_yaml_with_options(0, SMALL, aliases(None)).is_ok());
+    }
+
+    #[test]
+    fn warnings() {
+        use crate::types::WarningKind;
+        const DOC: &str = "country: NO\nname: a\nlist: [yes, 'no', true]\nname: b\non: push\n";
+        let (node, warnings) = parse_yaml_with_warnings(0, DOC).unwrap();
+        assert_eq!(node, parse_yaml(0, DOC).unwrap());
+        let found: Vec<_> = warnings
+            .iter()
+            .map(|w| (w.kind(), w.span().start().copied().unwrap()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (WarningKind::AmbiguousBoolean, Marker::new(0, 1, 10)),
+                (WarningKind::AmbiguousBoolean, Marker::new(0, 3, 8)),
+                (WarningKind::DuplicateKey, Marker::new(0, 4, 1)),
+                (WarningKind::AmbiguousBoolean, Marker::new(0, 5, 1)),
+            ]
+        );
+        assert_eq!(
+            warnings[2].to_string(),
+            "4:1: Duplicate key in mapping, the earlier value is ignored"
+        );
+        let (_, warnings) = parse_yaml_with_warnings(0, "name: a\nflag: true\n").unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn node_limits() {
+        let limited = |max| LoaderOptions {
+            max_nodes: Some(max),
+            ..LoaderOptions::default()
+        };
+        // The mapping, two keys, a scalar, a sequence, and its two items
+        const DOC: &str = "a: 1\nb: [2, 3]\n";
+        assert!(parse_yaml_with_options(0, DOC, limited(7)).is_ok());
+        let err = parse_yaml_with_options(0, DOC, limited(6)).err().unwrap();
+        assert_eq!(err, LoadError::NodeLimitExceeded(Marker::new(0, 2, 8)));
+        assert_eq!(err.to_string(), "2:8: Too many nodes in document");
+        let err = parse_yaml_with_options(0, DOC, limited(0)).err().unwrap();
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 2)));
+        assert!(parse_yaml_with_options(0, DOC, LoaderOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn merge_keys_are_literal() {
+        // Merge keys are never resolved, so `<<` is just another key
+        let node = parse_yaml(0, "base:\n  <<: {a: 1}\n  b: 2\n").unwrap();
+        let base = node.as_mapping().unwrap().get_mapping("base").unwrap();
+        let merge = base.get_mapping("<<").unwrap();
+        assert_eq!(merge.get_scalar("a").unwrap().as_str(), "1");
+        assert!(base.get_node("a").is_none());
+        let (key, _) = base.iter().next().unwrap();
+        assert_eq!(key.span().start(), Some(&Marker::new(0, 2, 3)));
+    }
+
     #[test]
     fn unexpected_anchor2() {
         assert_eq!(
@@ -519,4 +1691,395 @@ mod test {
         assert!(err.is_err());
         assert!(format!("{}", err.err().unwrap()).starts_with("2:1: "));
     }
+
+    #[test]
+    fn multi_document_stream() {
+        let docs =
+            parse_yaml_multi(0, "---\nfirst: 1\n---\nsecond: 2\n...\n---\nthird: 3\n").unwrap();
+        assert_eq!(docs.len(), 3);
+        for ((node, _), key) in docs.iter().zip(["first", "second", "third"]) {
+            assert!(node.as_mapping().unwrap().get_scalar(key).is_some());
+        }
+        assert_eq!(
+            docs[0].1,
+            Span::new_with_marks(Marker::new(0, 1, 1), Marker::new(0, 3, 1))
+        );
+        assert_eq!(
+            docs[1].1,
+            Span::new_with_marks(Marker::new(0, 3, 1), Marker::new(0, 5, 1))
+        );
+        assert_eq!(docs[2].1.start(), Some(&Marker::new(0, 6, 1)));
+        assert!(docs[2].1.end().is_some());
+    }
+
+    #[test]
+    fn multi_document_empty_and_errors() {
+        assert_eq!(parse_yaml_multi(0, ""), Ok(vec![]));
+        assert_eq!(
+            parse_yaml_multi(0, "---\nfoo: bar\n---\n[]\n"),
+            Err(LoadError::TopLevelMustBeMapping(Marker::new(0, 4, 1)))
+        );
+    }
+
+    #[test]
+    fn tab_indentation() {
+        let err = parse_yaml(0, "foo:\n  bar:\n\tbaz: 1\n").err().unwrap();
+        assert!(matches!(err, LoadError::ScanError(_, _)));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 3, 1)));
+        assert!(format!("{}", err).starts_with("3:1: "));
+        // Errors elsewhere on a tab indented line are left where they are
+        assert_eq!(
+            locate_indentation_tab("a:\n\tb: [\n", Marker::new(0, 2, 6)),
+            Marker::new(0, 2, 6)
+        );
+        assert_eq!(
+            locate_indentation_tab("a:\n \t b\n", Marker::new(0, 2, 4)),
+            Marker::new(0, 2, 2)
+        );
+        assert_eq!(
+            locate_indentation_tab("a:\n  b\n", Marker::new(0, 2, 3)),
+            Marker::new(0, 2, 3)
+        );
+    }
+
+    // Every prefix of a document, and a selection of corruptions of it,
+    // must produce a result or an error rather than a panic.
+    fn adversarial_documents() -> Vec<String> {
+        const DOC: &str = include_str!("../examples/everything.yaml");
+        const NASTY: &[&str] = &[
+            "",
+            "-",
+            ":",
+            "?",
+            "[",
+            "]",
+            "{",
+            "}",
+            "{:",
+            "[:]",
+            "- -",
+            "? ? ?",
+            "&a",
+            "*a",
+            "&a x: *a",
+            "!!str",
+            "!t x",
+            "---",
+            "...",
+            "--- ...",
+            "\t",
+            "a:\n\t- b",
+            "\"",
+            "'",
+            "|",
+            ">-",
+            "|9",
+            "a: |\n b\n  c",
+            "%YAML 1.2",
+            "%TAG ! x",
+            "\u{feff}",
+            "\u{0}",
+            "a: b: c",
+            "[a: b, c: d]",
+            "{a, b}",
+            "? a\n? a",
+            "a: 1\na: 2",
+        ];
+        let mut docs: Vec<String> = NASTY.iter().map(|s| s.to_string()).collect();
+        docs.extend(
+            DOC.char_indices()
+                .map(|(i, _)| DOC[..i].to_string())
+                .step_by(3),
+        );
+        // A small deterministic generator so failures are reproducible
+        let mut state: u32 = 0x5eed;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as usize
+        };
+        let chars: Vec<char> = DOC.chars().collect();
+        for _ in 0..200 {
+            let mut mutated = chars.clone();
+            for _ in 0..4 {
+                let at = next() % mutated.len();
+                let with = [
+                    '[', ']', '{', '}', ':', '-', '\n', ' ', '\t', '&', '*', '"', '#',
+                ];
+                mutated[at] = with[next() % with.len()];
+            }
+            docs.push(mutated.into_iter().collect());
+        }
+        docs
+    }
+
+    #[test]
+    fn invariant_errors() {
+        assert_eq!(first_document(Vec::new()), Err(LoadError::NoDocument));
+        assert_eq!(LoadError::NoDocument.start_mark(), None);
+        assert_eq!(
+            LoadError::NoDocument.to_string(),
+            "YAML parser produced no document"
+        );
+        let err = LoadError::UnexpectedEvent(Marker::new(0, 2, 3));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 3)));
+        assert_eq!(err.to_string(), "2:3: Unexpected event from YAML parser");
+        // Aliases are rejected along with anchors, whether or not the
+        // anchor they refer to exists
+        assert!(parse_yaml(0, "a: *missing\n").is_err());
+        assert!(matches!(
+            parse_yaml(0, "a: &x 1\nb: *x\n"),
+            Err(LoadError::UnexpectedAnchor(_))
+        ));
+    }
+
+    #[test]
+    fn adversarial_input_never_panics() {
+        let permissive = || LoaderOptions {
+            error_on_duplicate_keys: true,
+            allow_non_mapping_top_level: true,
+            ..LoaderOptions::default()
+        };
+        for doc in adversarial_documents() {
+            let _ = parse_yaml(0, &doc);
+            let _ = parse_yaml_multi(0, &doc);
+            let _ = parse_yaml_with_options(0, &doc, permissive());
+            let _ = parse_yaml_multi_with_options(0, &doc, permissive());
+            let _ = parse_yaml_bytes(0, doc.as_bytes());
+        }
+        for bytes in [
+            &b"\xff\xfe\x00"[..],
+            b"\x00",
+            b"\x00\x00\x00",
+            b"\xfe\xff\xd8\x00",
+        ] {
+            let _ = parse_yaml_bytes(0, bytes);
+        }
+    }
+
+    #[test]
+    fn set_tag() {
+        let node = parse_yaml(0, "set: !!set {a, b}\nblock: !!set\n  ? c\n").unwrap();
+        let map = node.as_mapping().unwrap();
+        assert_eq!(map.get_mapping("set").unwrap().len(), 2);
+        assert_eq!(map.get_mapping("block").unwrap().len(), 1);
+        assert!(map.get_mapping("set").unwrap().is_set());
+        assert!(map.get_mapping("block").unwrap().is_set());
+        assert!(!map.is_set());
+        assert_eq!(
+            parse_yaml(0, "other: !!omap {a: b}\n"),
+            Err(LoadError::UnexpectedTag(Marker::new(0, 1, 15)))
+        );
+        assert_eq!(
+            parse_yaml(0, "seq: !!set [a]\n"),
+            Err(LoadError::UnexpectedTag(Marker::new(0, 1, 12)))
+        );
+    }
+
+    #[test]
+    fn io_errors_compare_by_kind() {
+        let path = PathBuf::from("config.yaml");
+        let missing = LoadError::Io(
+            path.clone(),
+            io::Error::new(io::ErrorKind::NotFound, "gone"),
+        );
+        assert_eq!(
+            missing,
+            LoadError::Io(path.clone(), io::ErrorKind::NotFound.into())
+        );
+        assert_ne!(
+            missing,
+            LoadError::Io(path, io::ErrorKind::PermissionDenied.into())
+        );
+        assert_ne!(
+            missing,
+            LoadError::Io("other.yaml".into(), io::ErrorKind::NotFound.into())
+        );
+    }
+
+    #[test]
+    fn binary_tag() {
+        let node = parse_yaml(0, "data: !!binary |\n  aGVs\n  bG8=\nplain: aGVsbG8=\n").unwrap();
+        let map = node.as_mapping().unwrap();
+        let data = map.get_scalar("data").unwrap();
+        assert!(data.is_binary());
+        assert!(!data.may_coerce());
+        assert_eq!(data.as_str(), "aGVs\nbG8=\n");
+        assert!(!map.get_scalar("plain").unwrap().is_binary());
+        assert_eq!(
+            parse_yaml(0, "seq: !!binary [a]\n"),
+            Err(LoadError::UnexpectedTag(Marker::new(0, 1, 15)))
+        );
+    }
+
+    #[test]
+    fn encoded_bytes() {
+        const TEXT: &str = "name: café\r\nlist: [ß, 🦀]\r\n";
+        let expected = parse_yaml(0, TEXT).unwrap();
+        let name = Marker::new(0, 1, 7);
+        let crab = Marker::new(0, 2, 11);
+
+        let utf8_bom: Vec<u8> = b"\xef\xbb\xbf"
+            .iter()
+            .chain(TEXT.as_bytes())
+            .copied()
+            .collect();
+        let utf16_le: Vec<u8> = TEXT.encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let utf16_le_bom: Vec<u8> = "\u{feff}"
+            .encode_utf16()
+            .chain(TEXT.encode_utf16())
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let utf16_be_bom: Vec<u8> = "\u{feff}"
+            .encode_utf16()
+            .chain(TEXT.encode_utf16())
+            .flat_map(u16::to_be_bytes)
+            .collect();
+        let utf32_le: Vec<u8> = TEXT
+            .chars()
+            .flat_map(|c| (c as u32).to_le_bytes())
+            .collect();
+
+        for bytes in [
+            TEXT.as_bytes(),
+            &utf8_bom,
+            &utf16_le,
+            &utf16_le_bom,
+            &utf16_be_bom,
+            &utf32_le,
+        ] {
+            let node = parse_yaml_bytes(0, bytes).unwrap();
+            assert_eq!(node, expected);
+            let map = node.as_mapping().unwrap();
+            assert_eq!(map.get_node("name").unwrap().span().start(), Some(&name));
+            let list = map.get_sequence("list").unwrap();
+            assert_eq!(list.get_node(1).unwrap().span().start(), Some(&crab));
+        }
+    }
+
+    #[test]
+    fn byte_order_marks_and_trailing_markers() {
+        let expected = parse_yaml(0, "key: value\n").unwrap();
+        for text in [
+            "\u{feff}key: value\n",
+            "key: value\n...\n",
+            "key: value\n---\n",
+            "\u{feff}---\nkey: value\n...\n",
+        ] {
+            let node = parse_yaml(0, text).unwrap();
+            assert_eq!(node, expected, "{text:?}");
+        }
+        let node = parse_yaml(0, "\u{feff}key: [value]\n").unwrap();
+        let map = node.as_mapping().unwrap();
+        assert_eq!(
+            map.keys().next().unwrap().span().start(),
+            Some(&Marker::new(0, 1, 1))
+        );
+        let seq = map.get_sequence("key").unwrap();
+        assert!(seq.is_flow());
+        assert_eq!(seq.span().start(), Some(&Marker::new(0, 1, 6)));
+        let docs = parse_yaml_multi(0, "\u{feff}a: 1\n...\n").unwrap();
+        assert_eq!(docs.len(), 1);
+    }
+
+    #[test]
+    fn block_scalar_headers() {
+        let node = parse_yaml(
+            0,
+            "strip: |-\n  a\nkeep: |+2\n   b\n\nfolded: >\n  c\n  d\nboth: >2- # note\n   e\nplain: f\n",
+        )
+        .unwrap();
+        let map = node.as_mapping().unwrap();
+        let strip = map.get_scalar("strip").unwrap();
+        assert_eq!(strip.style(), ScalarStyle::Literal);
+        assert_eq!(strip.block_chomping(), Some(Chomping::Strip));
+        assert_eq!(strip.explicit_indent(), None);
+        assert_eq!(strip.as_str(), "a");
+        let keep = map.get_scalar("keep").unwrap();
+        assert_eq!(keep.style(), ScalarStyle::Literal);
+        assert_eq!(keep.block_chomping(), Some(Chomping::Keep));
+        assert_eq!(keep.explicit_indent(), Some(2));
+        assert_eq!(keep.as_str(), " b\n\n");
+        let folded = map.get_scalar("folded").unwrap();
+        assert_eq!(folded.style(), ScalarStyle::Folded);
+        assert_eq!(folded.block_chomping(), Some(Chomping::Clip));
+        assert_eq!(folded.explicit_indent(), None);
+        assert_eq!(folded.as_str(), "c d\n");
+        let both = map.get_scalar("both").unwrap();
+        assert_eq!(both.style(), ScalarStyle::Folded);
+        assert_eq!(both.block_chomping(), Some(Chomping::Strip));
+        assert_eq!(both.explicit_indent(), Some(2));
+        let plain = map.get_scalar("plain").unwrap();
+        assert_eq!(plain.block_chomping(), None);
+        assert_eq!(plain.explicit_indent(), None);
+
+        // Leading blank lines, empty scalars, and comments mentioning indicators
+        let node = parse_yaml(
+            0,
+            "gap: |+ # a | b\n\n  x\nempty: >-\nnext: |\n  y\nlast: |2-\n",
+        )
+        .unwrap();
+        let map = node.as_mapping().unwrap();
+        let header = |key| {
+            let scalar = map.get_scalar(key).unwrap();
+            (scalar.block_chomping(), scalar.explicit_indent())
+        };
+        assert_eq!(header("gap"), (Some(Chomping::Keep), None));
+        assert_eq!(header("empty"), (Some(Chomping::Strip), None));
+        assert_eq!(header("next"), (Some(Chomping::Clip), None));
+        assert_eq!(header("last"), (Some(Chomping::Strip), Some(2)));
+    }
+
+    #[test]
+    fn parsed_documents() {
+        let text = "\u{feff}name: web\nports: [80, 443]\nquoted: 'it''s'\nserver:\n  host: example.com\n  port: 8080\nlast: \"a\\\"b\"\nblock: |\n  text\n";
+        let doc = parse_yaml_document(0, text).unwrap();
+        assert_eq!(doc.source_text(), &text[3..]);
+        let map = doc.node().as_mapping().unwrap();
+        let text_of = |key| doc.node_text(map.get_node(key).unwrap());
+        assert_eq!(text_of("name"), Some("web"));
+        assert_eq!(text_of("ports"), Some("[80, 443]"));
+        assert_eq!(text_of("quoted"), Some("'it''s'"));
+        assert_eq!(text_of("server"), Some("host: example.com\n  port: 8080"));
+        assert_eq!(text_of("last"), Some("\"a\\\"b\""));
+        assert_eq!(text_of("block"), None);
+        let key = map.keys().nth(1).unwrap();
+        assert_eq!(doc.node_text(&Node::from(key.clone())), Some("ports"));
+        let ports = map.get_sequence("ports").unwrap();
+        assert_eq!(doc.node_text(&ports[1]), Some("443"));
+        assert_eq!(
+            doc.line_index()
+                .offset_to_marker(doc.source_text().find("443").unwrap()),
+            *ports[1].span().start().unwrap()
+        );
+        let elsewhere = parse_yaml(1, "name: web\n").unwrap();
+        assert_eq!(doc.node_text(&elsewhere), None);
+        assert_eq!(doc.into_node(), parse_yaml(0, text).unwrap());
+    }
+
+    #[test]
+    fn badly_encoded_bytes() {
+        assert_eq!(
+            parse_yaml_bytes(0, b"ok: 1\nbad: \xff\n"),
+            Err(LoadError::InvalidEncoding(Marker::new(0, 2, 6)))
+        );
+        let mut truncated: Vec<u8> = "a: b\nc: d"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        truncated.pop();
+        let err = parse_yaml_bytes(0, truncated).err().unwrap();
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 4)));
+        assert!(format!("{}", err).starts_with("2:4: "));
+        // A lone surrogate isn't valid UTF-16
+        let surrogate: Vec<u8> = [0x61u16, 0x3a, 0x20, 0xd800]
+            .iter()
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+        assert_eq!(
+            parse_yaml_bytes(0, surrogate),
+            Err(LoadError::InvalidEncoding(Marker::new(0, 1, 4)))
+        );
+    }
 }
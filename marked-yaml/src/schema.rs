@@ -0,0 +1,280 @@
+//! Lightweight validation of node trees
+//!
+
+use crate::spanned_serde::Error;
+use crate::types::{MarkedMappingNode, Node};
+
+/// A description of the shape a node is expected to have
+///
+/// This is a small, dependency free, middle ground between checking a
+/// node tree by hand and using something like JSON Schema.  A schema can
+/// say what kind of node is expected, which keys a mapping may and must
+/// have, and which values a scalar may take.  See [`Node::validate`] for
+/// checking a node against a schema.
+///
+/// ```
+/// # use marked_yaml::*;
+/// let schema = NodeSchema::mapping(&["name", "mode", "tags"])
+///     .required("name")
+///     .field("name", NodeSchema::scalar())
+///     .field("mode", NodeSchema::one_of(&["fast", "slow"]))
+///     .field("tags", NodeSchema::sequence(NodeSchema::scalar()));
+/// let node = parse_yaml(0, "name: example\nmode: fast\ntags: [a, b]\n").unwrap();
+/// assert!(node.validate(&schema).is_empty());
+/// ```
+#[derive(Clone, Debug)]
+pub struct NodeSchema {
+    kind: SchemaKind,
+}
+
+#[derive(Clone, Debug)]
+enum SchemaKind {
+    Any,
+    Scalar(Option<&'static [&'static str]>),
+    Sequence(Box<NodeSchema>),
+    Mapping {
+        keys: &'static [&'static str],
+        required: Vec<&'static str>,
+        fields: Vec<(&'static str, NodeSchema)>,
+    },
+}
+
+impl NodeSchema {
+    /// A schema which accepts any node at all
+    pub fn any() -> Self {
+        Self {
+            kind: SchemaKind::Any,
+        }
+    }
+
+    /// A schema which accepts any scalar
+    pub fn scalar() -> Self {
+        Self {
+            kind: SchemaKind::Scalar(None),
+        }
+    }
+
+    /// A schema which accepts a scalar with one of the given values
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let schema = NodeSchema::mapping(&["mode"]).field("mode", NodeSchema::one_of(&["on", "off"]));
+    /// let node = parse_yaml(0, "mode: maybe").unwrap();
+    /// let errors = node.validate(&schema);
+    /// assert_eq!(errors[0].to_string(), "Unexpected value `maybe`, expected `on` or `off`");
+    /// ```
+    pub fn one_of(values: &'static [&'static str]) -> Self {
+        Self {
+            kind: SchemaKind::Scalar(Some(values)),
+        }
+    }
+
+    /// A schema which accepts a sequence whose items match the given schema
+    pub fn sequence(items: NodeSchema) -> Self {
+        Self {
+            kind: SchemaKind::Sequence(Box::new(items)),
+        }
+    }
+
+    /// A schema which accepts a mapping with only the given keys
+    ///
+    /// By default none of the keys are required and their values may be
+    /// anything.  Use [`required()`][NodeSchema::required] and
+    /// [`field()`][NodeSchema::field] to refine this.
+    pub fn mapping(keys: &'static [&'static str]) -> Self {
+        Self {
+            kind: SchemaKind::Mapping {
+                keys,
+                required: Vec::new(),
+                fields: Vec::new(),
+            },
+        }
+    }
+
+    /// Require that a mapping has the given key
+    ///
+    /// This has no effect on schemas other than mapping schemas.
+    pub fn required(mut self, key: &'static str) -> Self {
+        if let SchemaKind::Mapping { required, .. } = &mut self.kind {
+            required.push(key);
+        }
+        self
+    }
+
+    /// Set the schema for the value of the given key of a mapping
+    ///
+    /// This has no effect on schemas other than mapping schemas.
+    pub fn field(mut self, key: &'static str, schema: NodeSchema) -> Self {
+        if let SchemaKind::Mapping { fields, .. } = &mut self.kind {
+            fields.retain(|(k, _)| *k != key);
+            fields.push((key, schema));
+        }
+        self
+    }
+
+    fn validate_into(&self, node: &Node, errors: &mut Vec<Error>) {
+        match (&self.kind, node) {
+            (SchemaKind::Any, _) => {}
+            (SchemaKind::Scalar(allowed), Node::Scalar(scalar)) => {
+                if let Some(allowed) = allowed {
+                    if !allowed.contains(&scalar.as_str()) {
+                        errors.push(Error::UnexpectedValue(
+                            scalar.as_str().to_string(),
+                            allowed,
+                            *scalar.span(),
+                        ));
+                    }
+                }
+            }
+            (SchemaKind::Sequence(items), Node::Sequence(seq)) => {
+                for item in seq.iter() {
+                    items.validate_into(item, errors);
+                }
+            }
+            (
+                SchemaKind::Mapping {
+                    keys,
+                    required,
+                    fields,
+                },
+                Node::Mapping(map),
+            ) => validate_mapping(map, keys, required, fields, errors),
+            (kind, node) => errors.push(Error::TypeMismatch(
+                kind_name(kind),
+                node_kind_name(node),
+                *node.span(),
+            )),
+        }
+    }
+}
+
+fn validate_mapping(
+    map: &MarkedMappingNode,
+    keys: &'static [&'static str],
+    required: &[&'static str],
+    fields: &[(&'static str, NodeSchema)],
+    errors: &mut Vec<Error>,
+) {
+    for (key, value) in map.iter() {
+        if !keys.contains(&key.as_str()) {
+            errors.push(Error::UnknownFieldError(
+                key.as_str().to_string(),
+                keys,
+                *key.span(),
+            ));
+        } else if let Some((_, schema)) = fields.iter().find(|(k, _)| *k == key.as_str()) {
+            schema.validate_into(value, errors);
+        }
+    }
+    for key in required {
+        if map.get_node(key).is_none() {
+            errors.push(Error::MissingField(key.to_string(), *map.span()));
+        }
+    }
+}
+
+fn kind_name(kind: &SchemaKind) -> &'static str {
+    match kind {
+        SchemaKind::Any => "node",
+        SchemaKind::Scalar(_) => "scalar",
+        SchemaKind::Sequence(_) => "sequence",
+        SchemaKind::Mapping { .. } => "mapping",
+    }
+}
+
+fn node_kind_name(node: &Node) -> &'static str {
+    match node {
+        Node::Scalar(_) => "scalar",
+        Node::Sequence(_) => "sequence",
+        Node::Mapping(_) => "mapping",
+    }
+}
+
+impl Node {
+    /// Check this node against a schema
+    ///
+    /// Every violation of the schema is reported, each located at the node
+    /// which caused it.  Missing required keys are located at the mapping
+    /// which should have contained them.  If the node matches the schema
+    /// then the returned vector is empty.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let schema = NodeSchema::mapping(&["name", "port"]).required("name");
+    /// let node = parse_yaml(0, "port: 80\nhost: example.com\n").unwrap();
+    /// let errors = node.validate(&schema);
+    /// assert_eq!(errors.len(), 2);
+    /// assert_eq!(errors[0].start_mark(), Some(Marker::new(0, 2, 1)));
+    /// assert_eq!(errors[1].to_string(), "Missing field `name`");
+    /// ```
+    pub fn validate(&self, schema: &NodeSchema) -> Vec<Error> {
+        let mut errors = Vec::new();
+        schema.validate_into(self, &mut errors);
+        errors
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parse_yaml, Marker};
+
+    fn server_schema() -> NodeSchema {
+        NodeSchema::mapping(&["name", "ports", "mode", "extra"])
+            .required("name")
+            .required("ports")
+            .field("name", NodeSchema::scalar())
+            .field("ports", NodeSchema::sequence(NodeSchema::scalar()))
+            .field("mode", NodeSchema::one_of(&["fast", "slow"]))
+    }
+
+    #[test]
+    fn valid_document() {
+        let node = parse_yaml(
+            0,
+            "name: web\nports: [80, 443]\nmode: slow\nextra: {anything: [goes]}\n",
+        )
+        .unwrap();
+        assert!(node.validate(&server_schema()).is_empty());
+        assert!(node.validate(&NodeSchema::any()).is_empty());
+    }
+
+    #[test]
+    fn missing_required() {
+        let node = parse_yaml(0, "mode: fast\n").unwrap();
+        let errors = node.validate(&server_schema());
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(&errors[0], Error::MissingField(key, _) if key == "name"));
+        assert!(matches!(&errors[1], Error::MissingField(key, _) if key == "ports"));
+        assert_eq!(errors[0].start_mark(), Some(Marker::new(0, 1, 5)));
+    }
+
+    #[test]
+    fn wrong_types() {
+        let node = parse_yaml(0, "name: [web]\nports: [80, {bad: port}]\nmode: medium\n").unwrap();
+        let errors = node.validate(&server_schema());
+        assert_eq!(errors.len(), 3);
+        assert!(matches!(
+            errors[0],
+            Error::TypeMismatch("scalar", "sequence", _)
+        ));
+        assert_eq!(errors[0].start_mark(), Some(Marker::new(0, 1, 7)));
+        assert_eq!(errors[0].to_string(), "Expected a scalar, found a sequence");
+        assert!(matches!(
+            errors[1],
+            Error::TypeMismatch("scalar", "mapping", _)
+        ));
+        assert_eq!(errors[1].start_mark(), Some(Marker::new(0, 2, 13)));
+        assert!(matches!(errors[2], Error::UnexpectedValue(_, _, _)));
+        assert_eq!(errors[2].start_mark(), Some(Marker::new(0, 3, 7)));
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let node = parse_yaml(0, "name: web\nports: []\nprot: 80\n").unwrap();
+        let errors = node.validate(&server_schema());
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(&errors[0], Error::UnknownFieldError(k, _, _) if k == "prot"));
+        assert_eq!(errors[0].start_mark(), Some(Marker::new(0, 3, 1)));
+    }
+}
@@ -0,0 +1,42 @@
+//! Helpers for capturing spans via serde's field attributes
+//!
+
+use serde::{Deserialize, Deserializer};
+
+use crate::Spanned;
+
+/// Deserialize a [`Spanned`] value, for use with `deserialize_with`
+///
+/// This does exactly what deserializing a [`Spanned`] normally does, but as
+/// a plain function it can be named in a `#[serde(deserialize_with)]`
+/// attribute, or called from your own `deserialize_with` function to get at
+/// the span before adjusting the value.
+///
+/// ```
+/// # use marked_yaml::*;
+/// # use serde::{Deserialize, Deserializer};
+/// fn lowercase<'de, D>(deserializer: D) -> Result<Spanned<String>, D::Error>
+/// where
+///     D: Deserializer<'de>,
+/// {
+///     let name: Spanned<String> = marked_yaml::spanned::deserialize_with(deserializer)?;
+///     Ok(Spanned::new(*name.span(), name.to_lowercase()))
+/// }
+///
+/// #[derive(Deserialize)]
+/// struct User {
+///     #[serde(deserialize_with = "lowercase")]
+///     name: Spanned<String>,
+/// }
+///
+/// let user: User = from_yaml(0, "name: ALICE\n").unwrap();
+/// assert_eq!(*user.name, "alice");
+/// assert_eq!(user.name.span().start(), Some(&Marker::new(0, 1, 7)));
+/// ```
+pub fn deserialize_with<'de, D, T>(deserializer: D) -> Result<Spanned<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Spanned::deserialize(deserializer)
+}
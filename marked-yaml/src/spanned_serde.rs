@@ -2,24 +2,32 @@
 
 use std::{
     borrow::Borrow,
+    cell::{Cell, RefCell},
+    cmp::Ordering,
     fmt,
     hash::Hash,
     iter::Peekable,
     marker::PhantomData,
     num::{ParseFloatError, ParseIntError},
     ops::Deref,
+    rc::Rc,
+    sync::Arc,
 };
 
+use linked_hash_map::LinkedHashMap;
 use serde::{
     de::{
-        value::BorrowedStrDeserializer, DeserializeOwned, EnumAccess, IntoDeserializer, MapAccess,
-        SeqAccess, Unexpected, VariantAccess, Visitor,
+        value::BorrowedStrDeserializer, DeserializeOwned, DeserializeSeed, EnumAccess,
+        IntoDeserializer, MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor,
     },
     forward_to_deserialize_any, Deserialize, Deserializer, Serialize,
 };
 
 use crate::{
-    types::{MarkedMappingNode, MarkedScalarNode, MarkedSequenceNode},
+    types::{
+        is_yaml11_boolean, yaml11_integer, MarkedMappingNode, MarkedScalarNode, MarkedSequenceNode,
+        ResolvedScalar, Warning, WarningKind,
+    },
     LoaderOptions, Marker, Node, Span,
 };
 
@@ -28,6 +36,26 @@ use crate::{
 /// You must use a compatible deserializer if you want to deserialize these values,
 /// however when serializing you will lose the span information so do not expect
 /// to round-trip these values.
+///
+/// Optional values can be spanned in two different ways.  An
+/// `Option<Spanned<T>>` is `None` when the value is absent or null, so
+/// there is only a span when there is a value.  A `Spanned<Option<T>>`
+/// must be present, and always has a span, but its value is `None` when it
+/// was written as an explicit null.
+///
+/// ```
+/// # use marked_yaml::*;
+/// # use serde::Deserialize;
+/// #[derive(Deserialize)]
+/// struct Config {
+///     maybe: Option<Spanned<u32>>,
+///     located: Spanned<Option<u32>>,
+/// }
+/// let config: Config = from_yaml(0, "maybe: ~\nlocated: ~\n").unwrap();
+/// assert!(config.maybe.is_none());
+/// assert!(config.located.is_none());
+/// assert_eq!(config.located.span().start(), Some(&Marker::new(0, 2, 10)));
+/// ```
 #[derive(Debug)]
 pub struct Spanned<T> {
     span: Span,
@@ -46,6 +74,30 @@ impl<T> Spanned<T> {
     }
 }
 
+impl<T> Spanned<Spanned<T>> {
+    /// Collapse a doubly spanned value into a singly spanned one
+    ///
+    /// The outer span is kept, since that describes the whole of the
+    /// value, unless it is blank in which case the inner span is kept.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let inner = Spanned::new(Span::new_start(Marker::new(0, 2, 3)), 42);
+    /// let outer = Spanned::new(Span::new_start(Marker::new(0, 1, 1)), inner);
+    /// let flat = outer.flatten();
+    /// assert_eq!(*flat, 42);
+    /// assert_eq!(flat.span().start(), Some(&Marker::new(0, 1, 1)));
+    /// ```
+    pub fn flatten(self) -> Spanned<T> {
+        let span = if self.span.is_blank() {
+            self.inner.span
+        } else {
+            self.span
+        };
+        Spanned::new(span, self.inner.inner)
+    }
+}
+
 impl<T> Deref for Spanned<T> {
     type Target = T;
 
@@ -54,6 +106,29 @@ impl<T> Deref for Spanned<T> {
     }
 }
 
+/// Spanned values display as their inner values
+///
+/// The alternate form (`{:#}`) also shows where the value started, if that
+/// is known.
+///
+/// ```
+/// # use marked_yaml::*;
+/// let value = Spanned::new(Span::new_start(Marker::new(0, 3, 5)), "value");
+/// assert_eq!(format!("{}", value), "value");
+/// assert_eq!(format!("{:#}", value), "value at 3:5");
+/// ```
+impl<T> fmt::Display for Spanned<T>
+where
+    T: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span.start() {
+            Some(start) if f.alternate() => write!(f, "{} at {}", self.inner, start),
+            _ => self.inner.fmt(f),
+        }
+    }
+}
+
 impl<T> PartialEq for Spanned<T>
 where
     T: PartialEq,
@@ -89,6 +164,39 @@ where
     }
 }
 
+impl<T> PartialOrd for Spanned<T>
+where
+    T: PartialOrd,
+{
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.inner.partial_cmp(&other.inner)
+    }
+}
+
+/// Spanned values are ordered by their inner values, ignoring the spans
+///
+/// This permits deserializing into ordered collections such as a
+/// `BTreeMap<Spanned<String>, _>`.  Note that such collections are sorted
+/// by key and so will not preserve the order of the source document, though
+/// each key and value still knows where it came from.
+///
+/// ```
+/// # use std::collections::BTreeMap;
+/// # use marked_yaml::{from_yaml, Spanned};
+/// let map: BTreeMap<Spanned<String>, Spanned<i64>> = from_yaml(0, "b: 1\na: 2\n").unwrap();
+/// let (first, _) = map.iter().next().unwrap();
+/// assert_eq!(first, "a");
+/// assert_eq!(first.span().start().unwrap().line(), 2);
+/// ```
+impl<T> Ord for Spanned<T>
+where
+    T: Ord,
+{
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.inner.cmp(&other.inner)
+    }
+}
+
 impl Borrow<str> for Spanned<String> {
     fn borrow(&self) -> &str {
         self.inner.borrow()
@@ -105,16 +213,47 @@ impl Borrow<str> for Spanned<&'_ str> {
 
 // Convention for these markers comes from the toml crates
 
-const SPANNED_TYPE: &str = "$___::marked_data::serde::Spanned<T>";
-const SPANNED_SPAN_START_SOURCE: &str = "$___::marked_data::serde::Spanned<T>::span_start_source";
-const SPANNED_SPAN_START_LINE: &str = "$___::marked_data::serde::Spanned<T>::span_start_line";
-const SPANNED_SPAN_START_COLUMN: &str = "$___::marked_data::serde::Spanned<T>::span_start_column";
-const SPANNED_SPAN_END_SOURCE: &str = "$___::marked_data::serde::Spanned<T>::span_end_source";
-const SPANNED_SPAN_END_LINE: &str = "$___::marked_data::serde::Spanned<T>::span_end_line";
-const SPANNED_SPAN_END_COLUMN: &str = "$___::marked_data::serde::Spanned<T>::span_end_column";
-const SPANNED_INNER: &str = "$___::marked_data::serde::Spanned<T>::inner";
-
-const SPANNED_FIELDS: [&str; 7] = [
+/// The struct name which [`Spanned`] asks deserializers for
+///
+/// These markers are public so that deserializers other than the ones in
+/// this crate can cooperate with [`Spanned`] in the same way this crate
+/// does.  The contract is as follows.  A [`Spanned`] value (or a
+/// [`SpannedSeed`]) calls `deserialize_struct` with this name and with
+/// [`SPANNED_FIELDS`] as the fields.  A deserializer which recognises it
+/// should call `visit_map` with a map whose keys are the field names,
+/// supplied as borrowed strings, in this order:
+///
+/// 1. [`SPANNED_SPAN_START_SOURCE`], [`SPANNED_SPAN_START_LINE`], and
+///    [`SPANNED_SPAN_START_COLUMN`], each with a `usize` value, or none of
+///    them if there is no start marker.
+/// 2. [`SPANNED_SPAN_END_SOURCE`], [`SPANNED_SPAN_END_LINE`], and
+///    [`SPANNED_SPAN_END_COLUMN`], likewise for the end marker.
+/// 3. [`SPANNED_INNER`], whose value is the value being wrapped.
+///
+/// Deserializers which don't recognise the name will typically try to
+/// deserialize the inner value as a structure, which will fail.  These
+/// names are part of the crate's public API and will not change without a
+/// major version bump.
+pub const SPANNED_TYPE: &str = "$___::marked_data::serde::Spanned<T>";
+/// The key for the source of the start marker, see [`SPANNED_TYPE`]
+pub const SPANNED_SPAN_START_SOURCE: &str =
+    "$___::marked_data::serde::Spanned<T>::span_start_source";
+/// The key for the line of the start marker, see [`SPANNED_TYPE`]
+pub const SPANNED_SPAN_START_LINE: &str = "$___::marked_data::serde::Spanned<T>::span_start_line";
+/// The key for the column of the start marker, see [`SPANNED_TYPE`]
+pub const SPANNED_SPAN_START_COLUMN: &str =
+    "$___::marked_data::serde::Spanned<T>::span_start_column";
+/// The key for the source of the end marker, see [`SPANNED_TYPE`]
+pub const SPANNED_SPAN_END_SOURCE: &str = "$___::marked_data::serde::Spanned<T>::span_end_source";
+/// The key for the line of the end marker, see [`SPANNED_TYPE`]
+pub const SPANNED_SPAN_END_LINE: &str = "$___::marked_data::serde::Spanned<T>::span_end_line";
+/// The key for the column of the end marker, see [`SPANNED_TYPE`]
+pub const SPANNED_SPAN_END_COLUMN: &str = "$___::marked_data::serde::Spanned<T>::span_end_column";
+/// The key for the wrapped value, see [`SPANNED_TYPE`]
+pub const SPANNED_INNER: &str = "$___::marked_data::serde::Spanned<T>::inner";
+
+/// The fields which [`Spanned`] asks deserializers for, see [`SPANNED_TYPE`]
+pub const SPANNED_FIELDS: [&str; 7] = [
     SPANNED_SPAN_START_SOURCE,
     SPANNED_SPAN_START_LINE,
     SPANNED_SPAN_START_COLUMN,
@@ -132,81 +271,121 @@ where
     where
         D: serde::Deserializer<'de>,
     {
-        struct MarkedNodeVisitor<T>(PhantomData<T>);
+        SpannedSeed::new(PhantomData::<T>).deserialize(deserializer)
+    }
+}
 
-        impl<'de, T> Visitor<'de> for MarkedNodeVisitor<T>
-        where
-            T: Deserialize<'de>,
-        {
-            type Value = Spanned<T>;
+/// Adapter which captures spans for values deserialized with a seed
+///
+/// If you are driving deserialisation with a [`DeserializeSeed`] then you
+/// cannot simply ask for a [`Spanned`] value.  Instead, wrap your seed in
+/// this and it will produce a [`Spanned`] value, capturing the span in the
+/// same way that deserializing a [`Spanned`] does.
+///
+/// ```
+/// # use marked_yaml::*;
+/// # use marked_yaml::spanned_serde::NodeDeserializer;
+/// # use serde::de::DeserializeSeed;
+/// # use std::marker::PhantomData;
+/// let node = parse_yaml(0, "answer: 42\n").unwrap();
+/// let seed = SpannedSeed::new(PhantomData::<std::collections::HashMap<String, u8>>);
+/// let value = seed.deserialize(NodeDeserializer::new(&node)).unwrap();
+/// assert_eq!(value["answer"], 42);
+/// assert_eq!(value.span().start(), Some(&Marker::new(0, 1, 7)));
+/// ```
+pub struct SpannedSeed<S> {
+    seed: S,
+}
 
-            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
-                formatter.write_str("a MarkedNode of some kind")
-            }
+impl<S> SpannedSeed<S> {
+    /// Wrap a seed so that the value it produces is spanned
+    pub fn new(seed: S) -> Self {
+        Self { seed }
+    }
+}
 
-            fn visit_map<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
-            where
-                V: MapAccess<'de>,
-            {
-                let mut key: Option<&str> = visitor.next_key()?;
-
-                let span_start = if key == Some(SPANNED_SPAN_START_SOURCE) {
-                    let source: usize = visitor.next_value()?;
-                    if visitor.next_key()? != Some(SPANNED_SPAN_START_LINE) {
-                        return Err(serde::de::Error::custom(
-                            "marked node span start line missing",
-                        ));
-                    }
-                    let line: usize = visitor.next_value()?;
-                    if visitor.next_key()? != Some(SPANNED_SPAN_START_COLUMN) {
-                        return Err(serde::de::Error::custom(
-                            "marked node span start column missing",
-                        ));
-                    }
-                    let column: usize = visitor.next_value()?;
-                    key = visitor.next_key()?;
-                    Some(Marker::new(source, line, column))
-                } else {
-                    None
-                };
+impl<'de, S> DeserializeSeed<'de> for SpannedSeed<S>
+where
+    S: DeserializeSeed<'de>,
+{
+    type Value = Spanned<S::Value>;
 
-                let span_end = if key == Some(SPANNED_SPAN_END_SOURCE) {
-                    let source: usize = visitor.next_value()?;
-                    if visitor.next_key()? != Some(SPANNED_SPAN_END_LINE) {
-                        return Err(serde::de::Error::custom(
-                            "marked node span end line missing",
-                        ));
-                    }
-                    let line: usize = visitor.next_value()?;
-                    if visitor.next_key()? != Some(SPANNED_SPAN_END_COLUMN) {
-                        return Err(serde::de::Error::custom(
-                            "marked node span end column missing",
-                        ));
-                    }
-                    let column: usize = visitor.next_value()?;
-                    key = visitor.next_key()?;
-                    Some(Marker::new(source, line, column))
-                } else {
-                    None
-                };
+    fn deserialize<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        deserializer.deserialize_struct(SPANNED_TYPE, &SPANNED_FIELDS, self)
+    }
+}
 
-                if key != Some(SPANNED_INNER) {
-                    return Err(serde::de::Error::custom(
-                        "marked node inner value not found",
-                    ));
-                }
-                let inner: T = visitor.next_value()?;
+impl<'de, S> Visitor<'de> for SpannedSeed<S>
+where
+    S: DeserializeSeed<'de>,
+{
+    type Value = Spanned<S::Value>;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a MarkedNode of some kind")
+    }
 
-                let mut span = Span::new_blank();
-                span.set_start(span_start);
-                span.set_end(span_end);
+    fn visit_map<V>(self, mut visitor: V) -> Result<Self::Value, V::Error>
+    where
+        V: MapAccess<'de>,
+    {
+        let mut key: Option<&str> = visitor.next_key()?;
+
+        let span_start = if key == Some(SPANNED_SPAN_START_SOURCE) {
+            let source: usize = visitor.next_value()?;
+            if visitor.next_key()? != Some(SPANNED_SPAN_START_LINE) {
+                return Err(serde::de::Error::custom(
+                    "marked node span start line missing",
+                ));
+            }
+            let line: usize = visitor.next_value()?;
+            if visitor.next_key()? != Some(SPANNED_SPAN_START_COLUMN) {
+                return Err(serde::de::Error::custom(
+                    "marked node span start column missing",
+                ));
+            }
+            let column: usize = visitor.next_value()?;
+            key = visitor.next_key()?;
+            Some(Marker::new(source, line, column))
+        } else {
+            None
+        };
 
-                Ok(Spanned::new(span, inner))
+        let span_end = if key == Some(SPANNED_SPAN_END_SOURCE) {
+            let source: usize = visitor.next_value()?;
+            if visitor.next_key()? != Some(SPANNED_SPAN_END_LINE) {
+                return Err(serde::de::Error::custom(
+                    "marked node span end line missing",
+                ));
+            }
+            let line: usize = visitor.next_value()?;
+            if visitor.next_key()? != Some(SPANNED_SPAN_END_COLUMN) {
+                return Err(serde::de::Error::custom(
+                    "marked node span end column missing",
+                ));
             }
+            let column: usize = visitor.next_value()?;
+            key = visitor.next_key()?;
+            Some(Marker::new(source, line, column))
+        } else {
+            None
+        };
+
+        if key != Some(SPANNED_INNER) {
+            return Err(serde::de::Error::custom(
+                "marked node inner value not found",
+            ));
         }
-        let visitor = MarkedNodeVisitor(PhantomData);
+        let inner = visitor.next_value_seed(self.seed)?;
+
+        let mut span = Span::new_blank();
+        span.set_start(span_start);
+        span.set_end(span_end);
 
-        deserializer.deserialize_struct(SPANNED_TYPE, &SPANNED_FIELDS, visitor)
+        Ok(Spanned::new(span, inner))
     }
 }
 
@@ -226,7 +405,7 @@ where
 
 /// Errors which can come from deserialisation
 #[non_exhaustive]
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Error {
     /// The value was not a valid boolean
     NotBoolean(Span),
@@ -236,8 +415,29 @@ pub enum Error {
     FloatParseFailure(ParseFloatError, Span),
     /// An unknown field was encountered
     UnknownFieldError(String, &'static [&'static str], Span),
+    /// A node was of a different kind than expected
+    ///
+    /// The first value is the kind which was expected, the second is the kind
+    /// which was found, each of which is one of `scalar`, `mapping`, or
+    /// `sequence`.
+    TypeMismatch(&'static str, &'static str, Span),
+    /// A required field was missing from a mapping
+    MissingField(String, Span),
+    /// A scalar was not one of the permitted values
+    UnexpectedValue(String, &'static [&'static str], Span),
+    /// An unknown enum variant was encountered
+    UnknownVariant(String, &'static [&'static str], Span),
+    /// Values which conflict with one another were found
+    ///
+    /// The first span is where the error is considered to have occurred,
+    /// the others are the further places involved in the conflict.  See
+    /// [`Error::conflict()`] and [`Error::spans()`].
+    Conflict(String, Span, Vec<Span>),
     /// Some other error occurred
-    Other(Box<dyn std::error::Error>, Span),
+    ///
+    /// The error is reference counted so that errors can be cloned, and is
+    /// `Send` and `Sync` so that errors can be moved between threads.
+    Other(Arc<dyn std::error::Error + Send + Sync>, Span),
 }
 
 impl Error {
@@ -247,11 +447,41 @@ impl Error {
             Error::IntegerParseFailure(_, s) => s,
             Error::FloatParseFailure(_, s) => s,
             Error::UnknownFieldError(_, _, s) => s,
+            Error::TypeMismatch(_, _, s) => s,
+            Error::MissingField(_, s) => s,
+            Error::UnexpectedValue(_, _, s) => s,
+            Error::UnknownVariant(_, _, s) => s,
+            Error::Conflict(_, s, _) => s,
             Error::Other(_, s) => s,
         };
         *spanloc = span;
     }
 
+    fn span(&self) -> &Span {
+        match self {
+            Error::NotBoolean(s) => s,
+            Error::IntegerParseFailure(_, s) => s,
+            Error::FloatParseFailure(_, s) => s,
+            Error::UnknownFieldError(_, _, s) => s,
+            Error::TypeMismatch(_, _, s) => s,
+            Error::MissingField(_, s) => s,
+            Error::UnexpectedValue(_, _, s) => s,
+            Error::UnknownVariant(_, _, s) => s,
+            Error::Conflict(_, s, _) => s,
+            Error::Other(_, s) => s,
+        }
+    }
+
+    // Errors which are raised by visitors (e.g. untagged enums failing to
+    // match any variant) come back to us without a span, so give them the
+    // span of the node which was being deserialized when they occurred.
+    fn with_default_span(mut self, span: Span) -> Self {
+        if self.span().is_blank() {
+            self.set_span(span);
+        }
+        self
+    }
+
     /// Retrieve the start marker if there is one
     ///
     /// Most spans which are generated by the loader only have start
@@ -282,15 +512,102 @@ impl Error {
     /// assert_eq!(mark.column(), 6);
     /// ```
     pub fn start_mark(&self) -> Option<Marker> {
-        let spanloc = match self {
-            Error::NotBoolean(s) => s,
-            Error::IntegerParseFailure(_, s) => s,
-            Error::FloatParseFailure(_, s) => s,
-            Error::UnknownFieldError(_, _, s) => s,
-            Error::Other(_, s) => s,
-        };
-        spanloc.start().copied()
+        self.span().start().copied()
+    }
+
+    /// Create an error for values which conflict with one another
+    ///
+    /// This is for checks which involve more than one place in a document,
+    /// such as two fields which may not both be given.  The first span is
+    /// the primary location of the error, and any others are kept so that
+    /// they can be shown too.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "file: a.txt\nurl: https://example.com/\n").unwrap();
+    /// let map = node.as_mapping().unwrap();
+    /// let keys = map.keys().map(|key| *key.span());
+    /// let err = Error::conflict("`file` and `url` may not both be given", keys);
+    /// assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 1)));
+    /// assert_eq!(err.spans()[1].start(), Some(&Marker::new(0, 2, 1)));
+    /// ```
+    pub fn conflict<S>(message: impl Into<String>, spans: S) -> Self
+    where
+        S: IntoIterator<Item = Span>,
+    {
+        let mut spans = spans.into_iter();
+        let primary = spans.next().unwrap_or_else(Span::new_blank);
+        Error::Conflict(message.into(), primary, spans.collect())
+    }
+
+    /// All of the spans involved in this error
+    ///
+    /// For most errors this is just the one span, but for
+    /// [`Error::Conflict`] the primary span is followed by the others
+    /// involved, so that diagnostics can point at all of them.
+    pub fn spans(&self) -> Vec<Span> {
+        let mut spans = vec![*self.span()];
+        if let Error::Conflict(_, _, others) = self {
+            spans.extend(others.iter().copied());
+        }
+        spans
     }
+
+    /// Suggest what was probably meant by an unknown field or variant
+    ///
+    /// For [`Error::UnknownFieldError`] and [`Error::UnknownVariant`] this
+    /// is the expected name closest to the one which was found, provided
+    /// it is close enough to plausibly be a typo.  The suggestion is also
+    /// included when the error is displayed.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// # use serde::Deserialize;
+    /// #[derive(Deserialize)]
+    /// #[serde(deny_unknown_fields)]
+    /// struct Config {
+    ///     timeout: Option<u32>,
+    /// }
+    /// let node = parse_yaml(0, "timeut: 5\n").unwrap();
+    /// let err = from_node::<Config>(&node).err().unwrap();
+    /// assert_eq!(err.suggestion(), Some("timeout"));
+    /// ```
+    pub fn suggestion(&self) -> Option<&'static str> {
+        match self {
+            Error::UnknownFieldError(found, expected, _)
+            | Error::UnknownVariant(found, expected, _) => closest_match(found, expected),
+            _ => None,
+        }
+    }
+}
+
+// Find the candidate nearest to `found`, if any is near enough that `found`
+// is likely a misspelling of it.  Roughly one edit is permitted for every
+// three characters.
+fn closest_match(found: &str, candidates: &[&'static str]) -> Option<&'static str> {
+    let limit = found.chars().count().max(3) / 3;
+    candidates
+        .iter()
+        .map(|&candidate| (edit_distance(found, candidate), candidate))
+        .filter(|&(distance, _)| distance <= limit)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, candidate)| candidate)
+}
+
+// The Levenshtein distance between two strings, keeping only a single row
+// of the table.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let mut row: Vec<usize> = (0..=b.chars().count()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.chars().enumerate() {
+            let substitution = diagonal + usize::from(ca != cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[row.len() - 1]
 }
 
 impl fmt::Display for Error {
@@ -301,39 +618,96 @@ impl fmt::Display for Error {
             Error::FloatParseFailure(e, _) => e.fmt(f),
             Error::UnknownFieldError(field, expected, _) => match expected.len() {
                 0 => write!(f, "Unknown field `{field}`, there are no fields"),
-                1 => write!(f, "Unknown field `{field}`, expected `{}`", expected[0]),
-                2 => write!(
-                    f,
-                    "Unknown field `{field}`, expected `{}` or `{}`",
-                    expected[0], expected[1]
-                ),
                 _ => {
-                    write!(f, "Unknown field `{field}`, expected one of ")?;
-                    let last = expected[expected.len() - 1];
-                    for v in expected[..=expected.len() - 2].iter() {
-                        write!(f, "`{v}`, ")?;
-                    }
-                    write!(f, "or `{last}`")
+                    write!(f, "Unknown field `{field}`, ")?;
+                    write_expected(f, expected)?;
+                    write_suggestion(f, self.suggestion())
+                }
+            },
+            Error::TypeMismatch(expected, found, _) => {
+                write!(f, "Expected a {expected}, found a {found}")
+            }
+            Error::MissingField(field, _) => write!(f, "Missing field `{field}`"),
+            Error::UnexpectedValue(value, expected, _) => {
+                write!(f, "Unexpected value `{value}`, ")?;
+                write_expected(f, expected)
+            }
+            Error::UnknownVariant(variant, expected, _) => match expected.len() {
+                0 => write!(f, "Unknown variant `{variant}`, there are no variants"),
+                _ => {
+                    write!(f, "Unknown variant `{variant}`, ")?;
+                    write_expected(f, expected)?;
+                    write_suggestion(f, self.suggestion())
                 }
             },
+            Error::Conflict(message, _, _) => f.write_str(message),
             Error::Other(e, _) => e.fmt(f),
         }
     }
 }
 
+// Turn a message into something suitable for `Error::Other`
+fn message(msg: impl fmt::Display) -> Arc<dyn std::error::Error + Send + Sync> {
+    Box::<dyn std::error::Error + Send + Sync>::from(msg.to_string()).into()
+}
+
+fn write_expected(f: &mut fmt::Formatter<'_>, expected: &[&str]) -> fmt::Result {
+    match expected.len() {
+        0 => f.write_str("expected nothing"),
+        1 => write!(f, "expected `{}`", expected[0]),
+        2 => write!(f, "expected `{}` or `{}`", expected[0], expected[1]),
+        _ => {
+            f.write_str("expected one of ")?;
+            let last = expected[expected.len() - 1];
+            for v in expected[..=expected.len() - 2].iter() {
+                write!(f, "`{v}`, ")?;
+            }
+            write!(f, "or `{last}`")
+        }
+    }
+}
+
+fn write_suggestion(f: &mut fmt::Formatter<'_>, suggestion: Option<&str>) -> fmt::Result {
+    match suggestion {
+        Some(suggestion) => write!(f, " (did you mean `{suggestion}`?)"),
+        None => Ok(()),
+    }
+}
+
 impl std::error::Error for Error {}
 
+// Command line tools often return `io::Result` from their main logic, so
+// let them `?` deserialisation errors.  The location is kept in the message
+// since the span can't be recovered from the `io::Error` afterwards.
+fn located_io_error(mark: Option<Marker>, value: &dyn fmt::Display) -> std::io::Error {
+    let message = match mark {
+        Some(mark) => format!("{mark}: {value}"),
+        None => value.to_string(),
+    };
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+impl From<Error> for std::io::Error {
+    fn from(value: Error) -> Self {
+        located_io_error(value.start_mark(), &value)
+    }
+}
+
 impl serde::de::Error for Error {
     fn custom<T>(msg: T) -> Self
     where
         T: fmt::Display,
     {
-        Error::Other(msg.to_string().into(), Span::new_blank())
+        Error::Other(message(msg), Span::new_blank())
     }
 
     fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
         Self::UnknownFieldError(field.to_string(), expected, Span::new_blank())
     }
+
+    fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
+        Self::UnknownVariant(variant.to_string(), expected, Span::new_blank())
+    }
 }
 
 impl From<ParseIntError> for Error {
@@ -367,23 +741,126 @@ where
 
 // -------------------------------------------------------------------------------
 
+// State shared by every deserializer involved in a single deserialisation,
+// namely the options in force and somewhere to gather warnings and, if
+// we're collecting them, errors.
+#[derive(Default)]
+struct DeserializerContext {
+    options: FromNodeOptions,
+    warnings: Rc<RefCell<Vec<Warning>>>,
+    collect_errors: bool,
+    errors: Rc<RefCell<Vec<Error>>>,
+    // Whether this context was given to the root node, and whether the root
+    // node was then read with `deserialize_any`, as untagged enums and other
+    // types which buffer their content do
+    root: bool,
+    root_buffered: Rc<Cell<bool>>,
+    // Where to locate errors which no node claims, if the caller said
+    fallback_span: Option<Span>,
+}
+
+// Contexts are only cloned to hand them to child nodes, which are never the
+// root, so the clone doesn't inherit `root`.
+impl Clone for DeserializerContext {
+    fn clone(&self) -> Self {
+        Self {
+            options: self.options,
+            warnings: Rc::clone(&self.warnings),
+            collect_errors: self.collect_errors,
+            errors: Rc::clone(&self.errors),
+            root: false,
+            root_buffered: Rc::clone(&self.root_buffered),
+            fallback_span: self.fallback_span,
+        }
+    }
+}
+
+impl DeserializerContext {
+    fn new(options: FromNodeOptions) -> Self {
+        Self {
+            options,
+            ..Self::default()
+        }
+    }
+
+    fn warn(&self, warning: Warning) {
+        self.warnings.borrow_mut().push(warning);
+    }
+
+    // Called by `deserialize_any`, so that errors raised by the type being
+    // deserialized after it has buffered the root node can be located there.
+    fn note_buffered(&self) {
+        if self.root {
+            self.root_buffered.set(true);
+        }
+    }
+
+    // Errors which still have no span once deserialization is over were
+    // raised by the type being deserialized rather than by us.  They go to
+    // the caller's fallback span if there is one, otherwise to the root
+    // node if it was buffered, since then the error is about it as a whole.
+    fn unclaimed_span(&self, root: &Node) -> Span {
+        match self.fallback_span {
+            Some(span) => span,
+            None if self.root_buffered.get() => *root.span(),
+            None => Span::new_blank(),
+        }
+    }
+
+    // When collecting errors, record the error and carry on with the
+    // fallback value, otherwise simply fail.
+    fn recover<T>(&self, error: Error, fallback: T) -> Result<T, Error> {
+        if self.collect_errors {
+            self.errors.borrow_mut().push(error);
+            Ok(fallback)
+        } else {
+            Err(error)
+        }
+    }
+}
+
+// Like `IntoDeserializer` but carries the deserialisation context down
+// through the node tree.
+trait IntoOptionedDeserializer<'de> {
+    type Deserializer: Deserializer<'de, Error = Error>;
+
+    fn into_optioned_deserializer(self, ctx: DeserializerContext) -> Self::Deserializer;
+}
+
 impl<'de> IntoDeserializer<'de, Error> for &'de Node {
     type Deserializer = NodeDeserializer<'de>;
 
     fn into_deserializer(self) -> Self::Deserializer {
-        NodeDeserializer { node: self }
+        NodeDeserializer::new(self)
+    }
+}
+
+impl<'de> IntoOptionedDeserializer<'de> for &'de Node {
+    type Deserializer = NodeDeserializer<'de>;
+
+    fn into_optioned_deserializer(self, ctx: DeserializerContext) -> Self::Deserializer {
+        NodeDeserializer { node: self, ctx }
     }
 }
 
 /// Deserializer for nodes
 pub struct NodeDeserializer<'node> {
     node: &'node Node,
+    ctx: DeserializerContext,
 }
 
 impl<'node> NodeDeserializer<'node> {
     /// Create a new deserializer over a borrowed node
     pub fn new(node: &'node Node) -> Self {
-        Self { node }
+        Self::new_with_options(node, FromNodeOptions::default())
+    }
+
+    /// Create a new deserializer over a borrowed node with the given options
+    ///
+    /// Any warnings raised while deserialising are discarded, use
+    /// [`from_node_with_warnings`] if you want to see them.
+    pub fn new_with_options(node: &'node Node, options: FromNodeOptions) -> Self {
+        node.into_optioned_deserializer(DeserializerContext::new(options))
     }
 }
 
@@ -395,7 +872,7 @@ impl<'node> NodeDeserializer<'node> {
 /// one is available, and then via the error: the marker
 /// indicating where the error occurred if it's available.
 /// Finally you may extract the error itself.
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct FromNodeError {
     error: Error,
     path: Option<String>,
@@ -431,6 +908,138 @@ impl fmt::Display for FromNodeError {
     }
 }
 
+impl std::error::Error for FromNodeError {}
+
+impl From<FromNodeError> for std::io::Error {
+    fn from(value: FromNodeError) -> Self {
+        located_io_error(value.start_mark(), &value)
+    }
+}
+
+/// Options for deserialising from [`Node`]s
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FromNodeOptions {
+    /// If true, errors will only carry the start marker of the span where
+    /// they occurred, even if the end is known.  This is useful for
+    /// renderers which only point at a single location.
+    pub prefer_start_only_spans: bool,
+    /// If true, leading and trailing whitespace in scalars is ignored when
+    /// deserialising numbers.  Errors still carry the span of the whole
+    /// scalar.
+    pub trim_numbers: bool,
+    /// If true, scalars are only deserialised as numbers if the YAML core
+    /// schema would resolve them as such.  Rust's number parsing also
+    /// accepts spellings such as `inf`, `infinity` and `NaN` which YAML
+    /// treats as plain strings, and in strict mode these are rejected as
+    /// strings rather than read as floats.
+    ///
+    /// The legacy integer forms described under
+    /// [`yaml11_numbers`][Self::yaml11_numbers], such as `0755` and `1:30`,
+    /// are rejected in the same way, whether or not that option is set,
+    /// because YAML 1.1 and YAML 1.2 read them differently.
+    ///
+    /// Booleans are always restricted to the core schema (see
+    /// [`MarkedScalarNode::as_bool`]), so values such as `NO` or `yes` are
+    /// strings regardless of this option.
+    pub strict: bool,
+    /// How to treat plain scalars which are deserialised as strings but
+    /// which YAML might resolve to some other type, such as `NO`, `on`,
+    /// `~`, or `1.0`.  This is commonly known as "the Norway problem".
+    ///
+    /// Quoted scalars are never ambiguous.
+    pub ambiguous_strings: AmbiguousStrings,
+    /// If true, `0` and `1` are accepted as `false` and `true` when
+    /// deserialising booleans, as well as the usual words.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "flag: 1\n").unwrap();
+    /// let options = FromNodeOptions {
+    ///     numeric_bools: true,
+    ///     ..FromNodeOptions::default()
+    /// };
+    /// let flags: std::collections::HashMap<String, bool> =
+    ///     from_node_with_options(&node, options).unwrap();
+    /// assert!(flags["flag"]);
+    /// ```
+    pub numeric_bools: bool,
+    /// If true, enum variants are matched without regard to ASCII case, so
+    /// `FAST` or `fast` will be accepted for a variant named `Fast`.  An
+    /// exact match is always preferred.  This is off by default since it
+    /// is ambiguous for enums with variants differing only in case.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// # use serde::Deserialize;
+    /// #[derive(Deserialize, Debug, PartialEq)]
+    /// enum Mode {
+    ///     Fast,
+    ///     Slow,
+    /// }
+    /// let node = parse_yaml(0, "mode: FAST\n").unwrap();
+    /// let options = FromNodeOptions {
+    ///     case_insensitive_variants: true,
+    ///     ..FromNodeOptions::default()
+    /// };
+    /// let modes: std::collections::HashMap<String, Mode> =
+    ///     from_node_with_options(&node, options).unwrap();
+    /// assert_eq!(modes["mode"], Mode::Fast);
+    /// ```
+    pub case_insensitive_variants: bool,
+    /// If true, plain scalars are read as integers in the two legacy forms
+    /// which YAML 1.1 permits, as well as in the usual decimal form:
+    ///
+    /// * Octal, with a leading zero, such as `0755` for 493.
+    /// * Sexagesimal (base 60), with colon separated digits, such as `1:30`
+    ///   for 90.  Each part after the first must be less than 60.
+    ///
+    /// Underscores may be used as separators in either form, and a leading
+    /// sign is permitted.  This applies when deserializing numbers and to
+    /// the values seen by [`Deserializer::deserialize_any()`].
+    ///
+    /// By default the YAML 1.2 rules apply, under which `0755` is the
+    /// decimal number 755 and `1:30` is a string.  The numeric accessors on
+    /// [`MarkedScalarNode`], such as [`as_u32()`][MarkedScalarNode::as_u32],
+    /// follow the YAML 1.2 rules, and each has a counterpart, such as
+    /// [`as_yaml11_u32()`][MarkedScalarNode::as_yaml11_u32], which follows
+    /// these rules instead.
+    ///
+    /// In [`strict`][Self::strict] mode the legacy forms are rejected.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "mode: 0755\n").unwrap();
+    /// let modes: std::collections::HashMap<String, u32> = from_node(&node).unwrap();
+    /// assert_eq!(modes["mode"], 755);
+    /// let options = FromNodeOptions {
+    ///     yaml11_numbers: true,
+    ///     ..FromNodeOptions::default()
+    /// };
+    /// let modes: std::collections::HashMap<String, u32> =
+    ///     from_node_with_options(&node, options).unwrap();
+    /// assert_eq!(modes["mode"], 0o755);
+    /// ```
+    pub yaml11_numbers: bool,
+}
+
+/// How to handle plain scalars which look like non-strings
+///
+/// See [`FromNodeOptions::ambiguous_strings`].  Scalars are considered
+/// ambiguous if they are not quoted and YAML 1.1 would resolve them to a
+/// boolean (e.g. `yes`, `No`, `OFF`), a null (e.g. `~`, `null`, or nothing
+/// at all), or a number.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum AmbiguousStrings {
+    /// Silently deserialise the scalar as the string it was written as
+    #[default]
+    Coerce,
+    /// Deserialise the scalar as a string, but raise a [`Warning`] pointing
+    /// at it.  Use [`from_node_with_warnings`] to retrieve the warnings.
+    Warn,
+    /// Refuse to deserialise the scalar as a string
+    Reject,
+}
+
 // -------------------------------------------------------------------------------
 
 /// Errors which can occur when deserialising from YAML
@@ -528,6 +1137,46 @@ where
     Ok(from_node(&node)?)
 }
 
+/// Deserialize the YAML in a file into the requisite type
+///
+/// This reads the file, registers its path in a new [`SourceRegistry`],
+/// parses it, and deserializes the result, so that any markers can be
+/// rendered with the name of the file they came from.  The registry is
+/// returned alongside the value.  Failing to read the file, parse it, or
+/// deserialize it are all reported as a [`LoadError`][crate::LoadError].
+/// The file is always registered as source `0`, so markers in errors can
+/// be matched up with the path which was passed in.
+///
+/// The file may be in any of the encodings which
+/// [`parse_yaml_bytes()`][crate::parse_yaml_bytes] understands.
+///
+/// ```no_run
+/// # use serde::Deserialize;
+/// #[derive(Deserialize)]
+/// struct Config {
+///     name: String,
+/// }
+/// match marked_yaml::from_file::<Config, _>("config.yaml") {
+///     Ok((config, _)) => println!("Hello {}", config.name),
+///     Err(e) => eprintln!("{}", e),
+/// }
+/// ```
+#[cfg(feature = "std")]
+#[allow(clippy::result_large_err)]
+pub fn from_file<T, P>(path: P) -> Result<(T, crate::SourceRegistry), crate::LoadError>
+where
+    T: DeserializeOwned,
+    P: AsRef<std::path::Path>,
+{
+    let path = path.as_ref();
+    let bytes = std::fs::read(path).map_err(|e| crate::LoadError::Io(path.to_path_buf(), e))?;
+    let mut registry = crate::SourceRegistry::new();
+    let source = registry.add(path.display().to_string());
+    let node = crate::parse_yaml_bytes(source, bytes)?;
+    let value = from_node(&node).map_err(|e| crate::LoadError::Deserialize(Box::new(e)))?;
+    Ok((value, registry))
+}
+
 // -------------------------------------------------------------------------------
 
 /// Deserialize some [`Node`] into the requisite type
@@ -537,6 +1186,12 @@ where
 /// the type tree is [`Spanned`] then the spans are provided
 /// from the requisite marked node.
 ///
+/// Scalars are read as numbers whenever the type being deserialized asks
+/// for a number, whether or not they were quoted, so `port: "8080"` can be
+/// read into a `u16` just as `port: 8080` can.  Where the type doesn't say
+/// what it wants, as with `serde_json::Value`, the YAML rules apply and
+/// quoted scalars are always strings.
+///
 /// ```
 /// # use serde::Deserialize;
 /// # use marked_yaml::Spanned;
@@ -556,25 +1211,490 @@ pub fn from_node<'de, T>(node: &'de Node) -> Result<T, FromNodeError>
 where
     T: Deserialize<'de>,
 {
-    #[cfg(not(feature = "serde-path"))]
-    fn inner_from_node<'de, T>(node: &'de Node) -> Result<T, FromNodeError>
-    where
-        T: Deserialize<'de>,
+    from_node_with_options(node, FromNodeOptions::default())
+}
+
+/// Deserialize some [`Node`] into the requisite type
+///
+/// This permits deserialisation of [`Node`]s into any structure
+/// which [`serde`] can deserialize.  In addition, if any part of
+/// the type tree is [`Spanned`] then the spans are provided
+/// from the requisite marked node.
+///
+/// Takes an additional [`FromNodeOptions`] struct to control the
+/// behaviour of the deserializer.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use marked_yaml::{FromNodeOptions, Spanned};
+/// const YAML: &str = "hello: world\n";
+/// let node = marked_yaml::parse_yaml(0, YAML).unwrap();
+/// #[derive(Deserialize)]
+/// struct Greeting {
+///     hello: Spanned<String>,
+/// }
+/// let greets: Greeting =
+///     marked_yaml::from_node_with_options(&node, FromNodeOptions::default()).unwrap();
+/// let start = greets.hello.span().start().unwrap();
+/// assert_eq!(start.line(), 1);
+/// assert_eq!(start.column(), 8);
+/// ```
+#[allow(clippy::result_large_err)]
+pub fn from_node_with_options<'de, T>(
+    node: &'de Node,
+    options: FromNodeOptions,
+) -> Result<T, FromNodeError>
+where
+    T: Deserialize<'de>,
+{
+    from_node_with_context(node, DeserializerContext::new(options))
+}
+
+/// Deserialize some [`Node`] into the requisite type, with a fallback span
+///
+/// This behaves like [`from_node`] except that any error which would
+/// otherwise come back without a span (for example one raised by a type's
+/// own `Deserialize` implementation while deserializing a node built by
+/// hand) is given the `fallback` span instead.  Errors which already know
+/// where they occurred are left alone.  Errors raised by the root type itself
+/// which no node claims, even for a parsed document, also get the fallback.
+///
+/// ```
+/// # use marked_yaml::{Marker, Node, Span};
+/// let node = Node::from("not a number");
+/// let root = Span::new_start(Marker::new(0, 1, 1));
+/// let err = marked_yaml::from_node_with_fallback_span::<u32>(&node, root)
+///     .err()
+///     .unwrap();
+/// assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 1)));
+/// ```
+#[allow(clippy::result_large_err)]
+pub fn from_node_with_fallback_span<'de, T>(
+    node: &'de Node,
+    fallback: Span,
+) -> Result<T, FromNodeError>
+where
+    T: Deserialize<'de>,
+{
+    let ctx = DeserializerContext {
+        fallback_span: Some(fallback),
+        ..DeserializerContext::default()
+    };
+    from_node_with_context(node, ctx)
+}
+
+/// Deserialize the items of a sequence one at a time
+///
+/// Rather than deserializing a whole sequence into a `Vec<T>` at once, this
+/// yields the result of deserializing each item in turn, so that large
+/// sequences can be processed without holding every item in memory, and
+/// so that a bad item doesn't prevent the rest from being used.  Errors
+/// are located within the item which caused them.
+///
+/// If the node is not a sequence then a single [`Error::TypeMismatch`] is
+/// yielded.
+///
+/// ```
+/// # use marked_yaml::*;
+/// let node = parse_yaml(0, "ports: [80, eighty, 443]\n").unwrap();
+/// let ports = node.as_mapping().unwrap().get_node("ports").unwrap();
+/// let results: Vec<Result<u16, Error>> = from_node_seq(ports).collect();
+/// assert_eq!(results[0].as_ref().unwrap(), &80);
+/// assert_eq!(results[1].as_ref().err().unwrap().start_mark(), Some(Marker::new(0, 1, 13)));
+/// assert_eq!(results[2].as_ref().unwrap(), &443);
+/// ```
+pub fn from_node_seq<'de, T>(node: &'de Node) -> impl Iterator<Item = Result<T, Error>> + 'de
+where
+    T: Deserialize<'de> + 'de,
+{
+    let (items, mismatch) = match node {
+        Node::Sequence(seq) => (seq.iter(), None),
+        Node::Scalar(_) => (<&[Node]>::default().iter(), Some("scalar")),
+        Node::Mapping(_) => (<&[Node]>::default().iter(), Some("mapping")),
+    };
+    mismatch
+        .map(|found| Err(Error::TypeMismatch("sequence", found, *node.span())))
+        .into_iter()
+        .chain(items.map(|item| {
+            from_node(item).map_err(|e| e.into_inner().with_default_span(*item.span()))
+        }))
+}
+
+/// Deserialize the entries of a mapping one at a time
+///
+/// This is the mapping counterpart to [`from_node_seq()`], yielding the
+/// result of deserializing each key and its value in turn.  Errors are
+/// located at the key or within the value which caused them.
+///
+/// If the node is not a mapping then a single [`Error::TypeMismatch`] is
+/// yielded.
+///
+/// ```
+/// # use marked_yaml::*;
+/// let node = parse_yaml(0, "web: 80\ndb: lots\ncache: 6379\n").unwrap();
+/// let results: Vec<Result<(String, u16), Error>> = from_node_map(&node).collect();
+/// assert_eq!(results[0].as_ref().unwrap(), &("web".to_string(), 80));
+/// assert_eq!(results[1].as_ref().err().unwrap().start_mark(), Some(Marker::new(0, 2, 5)));
+/// assert_eq!(results[2].as_ref().unwrap().1, 6379);
+/// ```
+pub fn from_node_map<'de, K, V>(
+    node: &'de Node,
+) -> impl Iterator<Item = Result<(K, V), Error>> + 'de
+where
+    K: Deserialize<'de> + 'de,
+    V: Deserialize<'de> + 'de,
+{
+    let (entries, mismatch) = match node {
+        Node::Mapping(map) => (Some(map.iter()), None),
+        Node::Scalar(_) => (None, Some("scalar")),
+        Node::Sequence(_) => (None, Some("sequence")),
+    };
+    mismatch
+        .map(|found| Err(Error::TypeMismatch("mapping", found, *node.span())))
+        .into_iter()
+        .chain(entries.into_iter().flatten().map(|(key, value)| {
+            let key = K::deserialize(key.into_deserializer())
+                .map_err(|e| e.with_default_span(*key.span()))?;
+            let value =
+                from_node(value).map_err(|e| e.into_inner().with_default_span(*value.span()))?;
+            Ok((key, value))
+        }))
+}
+
+/// A dynamically typed value with a span at every level
+///
+/// This is what [`to_spanned_tree()`] produces, for when you want spans on
+/// everything without writing [`Spanned`] on every field of a type.
+/// Scalars are resolved in the same way as [`Deserializer::deserialize_any`]
+/// does for nodes: plain `null`, `~` and empty scalars are nulls, booleans
+/// and numbers are typed, and everything else (including every quoted
+/// scalar) is a string.
+#[derive(Debug, PartialEq)]
+pub enum SpannedValue {
+    /// A null, located at the given span
+    Null(Span),
+    /// A boolean
+    Bool(Spanned<bool>),
+    /// A negative integer, or one which fits an `i64`
+    Int(Spanned<i64>),
+    /// A positive integer too large for an `i64`
+    UInt(Spanned<u64>),
+    /// A floating point number
+    Float(Spanned<f64>),
+    /// A string
+    String(Spanned<String>),
+    /// A sequence of values
+    Sequence(Spanned<Vec<SpannedValue>>),
+    /// A mapping from spanned keys to values
+    Mapping(Spanned<LinkedHashMap<Spanned<String>, SpannedValue>>),
+}
+
+impl SpannedValue {
+    /// The span of this value
+    pub fn span(&self) -> &Span {
+        match self {
+            SpannedValue::Null(span) => span,
+            SpannedValue::Bool(b) => b.span(),
+            SpannedValue::Int(i) => i.span(),
+            SpannedValue::UInt(u) => u.span(),
+            SpannedValue::Float(f) => f.span(),
+            SpannedValue::String(s) => s.span(),
+            SpannedValue::Sequence(seq) => seq.span(),
+            SpannedValue::Mapping(map) => map.span(),
+        }
+    }
+
+    /// Whether this value is a null
+    pub fn is_null(&self) -> bool {
+        matches!(self, SpannedValue::Null(_))
+    }
+
+    /// Retrieve this value as a boolean, if it is one
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            SpannedValue::Bool(b) => Some(**b),
+            _ => None,
+        }
+    }
+
+    /// Retrieve this value as an `i64`, if it is an integer which fits
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            SpannedValue::Int(i) => Some(**i),
+            SpannedValue::UInt(u) => i64::try_from(**u).ok(),
+            _ => None,
+        }
+    }
+
+    /// Retrieve this value as a `u64`, if it is an integer which fits
+    pub fn as_u64(&self) -> Option<u64> {
+        match self {
+            SpannedValue::Int(i) => u64::try_from(**i).ok(),
+            SpannedValue::UInt(u) => Some(**u),
+            _ => None,
+        }
+    }
+
+    /// Retrieve this value as an `f64`, if it is any kind of number
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            SpannedValue::Int(i) => Some(**i as f64),
+            SpannedValue::UInt(u) => Some(**u as f64),
+            SpannedValue::Float(f) => Some(**f),
+            _ => None,
+        }
+    }
+
+    /// Retrieve this value as a string, if it is one
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            SpannedValue::String(s) => Some(s.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Retrieve the items of this value, if it is a sequence
+    pub fn as_sequence(&self) -> Option<&[SpannedValue]> {
+        match self {
+            SpannedValue::Sequence(seq) => Some(seq.as_slice()),
+            _ => None,
+        }
+    }
+
+    /// Retrieve the entries of this value, if it is a mapping
+    pub fn as_mapping(&self) -> Option<&LinkedHashMap<Spanned<String>, SpannedValue>> {
+        match self {
+            SpannedValue::Mapping(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    /// Look up a key, if this value is a mapping
+    pub fn get(&self, key: &str) -> Option<&SpannedValue> {
+        self.as_mapping().and_then(|map| map.get(key))
+    }
+
+    /// Look up an index, if this value is a sequence
+    pub fn get_index(&self, index: usize) -> Option<&SpannedValue> {
+        self.as_sequence().and_then(|seq| seq.get(index))
+    }
+}
+
+/// Convert a node into a [`SpannedValue`] tree
+///
+/// Every value in the result, including mapping keys, carries the span of
+/// the node it came from, so this is a convenient way to get at spans
+/// without defining a type full of [`Spanned`] fields.
+///
+/// ```
+/// # use marked_yaml::*;
+/// let node = parse_yaml(0, "server:\n  ports: [80, 443]\n").unwrap();
+/// let tree = to_spanned_tree(&node);
+/// let port = tree.get("server").and_then(|s| s.get("ports")).and_then(|p| p.get_index(1));
+/// assert_eq!(port.and_then(SpannedValue::as_u64), Some(443));
+/// assert_eq!(port.unwrap().span().start(), Some(&Marker::new(0, 2, 15)));
+/// ```
+pub fn to_spanned_tree(node: &Node) -> SpannedValue {
+    match node {
+        Node::Scalar(scalar) => {
+            let span = *scalar.span();
+            match scalar.resolve() {
+                ResolvedScalar::Null => SpannedValue::Null(span),
+                ResolvedScalar::Bool(b) => SpannedValue::Bool(Spanned::new(span, b)),
+                ResolvedScalar::Int(i) => SpannedValue::Int(Spanned::new(span, i)),
+                ResolvedScalar::UInt(u) => SpannedValue::UInt(Spanned::new(span, u)),
+                ResolvedScalar::Float(f) => SpannedValue::Float(Spanned::new(span, f)),
+                ResolvedScalar::Str(s) => SpannedValue::String(Spanned::new(span, s.to_string())),
+            }
+        }
+        Node::Sequence(seq) => SpannedValue::Sequence(Spanned::new(
+            *seq.span(),
+            seq.iter().map(to_spanned_tree).collect(),
+        )),
+        Node::Mapping(map) => SpannedValue::Mapping(Spanned::new(
+            *map.span(),
+            map.iter()
+                .map(|(k, v)| {
+                    (
+                        Spanned::new(*k.span(), k.as_str().to_string()),
+                        to_spanned_tree(v),
+                    )
+                })
+                .collect(),
+        )),
+    }
+}
+
+impl MarkedMappingNode {
+    /// Look up a key and deserialize its value into the requisite type
+    ///
+    /// This is a shortcut for [`get_node()`][MarkedMappingNode::get_node]
+    /// followed by [`from_node()`], for code which reads mappings by hand.
+    /// If the key is missing then [`Error::MissingField`] is returned,
+    /// located at this mapping.  Otherwise any error is that from
+    /// deserializing the value.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "port: 8080\n").unwrap();
+    /// let map = node.as_mapping().unwrap();
+    /// let port: Spanned<u16> = map.get_typed("port").unwrap();
+    /// assert_eq!(*port, 8080);
+    /// let key = String::from("host");
+    /// assert!(matches!(map.get_typed::<u16>(&key), Err(Error::MissingField(k, _)) if k == key));
+    /// ```
+    pub fn get_typed<'de, T>(&'de self, key: &str) -> Result<T, Error>
+    where
+        T: Deserialize<'de>,
+    {
+        match self.get_node(key) {
+            Some(node) => from_node(node).map_err(FromNodeError::into_inner),
+            None => Err(Error::MissingField(key.to_string(), *self.span())),
+        }
+    }
+}
+
+/// Deserialize some [`Node`] into the requisite type, gathering warnings
+///
+/// This behaves like [`from_node_with_options`] but also returns any
+/// [`Warning`]s raised along the way.  Warnings are only raised for things
+/// which the options ask to be warned about, for example setting
+/// [`FromNodeOptions::ambiguous_strings`] to [`AmbiguousStrings::Warn`].
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use marked_yaml::{AmbiguousStrings, FromNodeOptions, Marker};
+/// const YAML: &str = "country: NO\n";
+/// let node = marked_yaml::parse_yaml(0, YAML).unwrap();
+/// #[derive(Deserialize)]
+/// struct Place {
+///     country: String,
+/// }
+/// let options = FromNodeOptions {
+///     ambiguous_strings: AmbiguousStrings::Warn,
+///     ..FromNodeOptions::default()
+/// };
+/// let (place, warnings): (Place, _) =
+///     marked_yaml::from_node_with_warnings(&node, options).unwrap();
+/// assert_eq!(place.country, "NO");
+/// assert_eq!(warnings.len(), 1);
+/// assert_eq!(warnings[0].span().start(), Some(&Marker::new(0, 1, 10)));
+/// ```
+#[allow(clippy::result_large_err)]
+pub fn from_node_with_warnings<'de, T>(
+    node: &'de Node,
+    options: FromNodeOptions,
+) -> Result<(T, Vec<Warning>), FromNodeError>
+where
+    T: Deserialize<'de>,
+{
+    let ctx = DeserializerContext::new(options);
+    let value = from_node_with_context(node, ctx.clone())?;
+    let warnings = ctx.warnings.take();
+    Ok((value, warnings))
+}
+
+/// Deserialize some [`Node`] into the requisite type, collecting errors
+///
+/// This behaves like [`from_node_with_options`] except that rather than
+/// stopping at the first error, it records errors in scalar values and
+/// carries on, so that you can report as many problems as possible at once.
+/// If anything went wrong then all the errors are returned, in the order
+/// they were encountered, each with the span of the node responsible.
+///
+/// Not every error can be recovered from.  Scalars which fail to parse as
+/// numbers or booleans (or which are rejected by the options) are recorded
+/// and deserialisation continues as though they were zero (or false, for
+/// booleans).  A node of the wrong kind, such as a mapping where a sequence
+/// or a string was expected, is recorded as an [`Error::TypeMismatch`] and
+/// deserialisation continues as though it were empty (or zero, or false).
+/// Other structural problems, such as unknown or missing fields or a
+/// scalar where a struct was expected, as well as any errors raised by the
+/// types being deserialized, stop deserialisation, in which case that error
+/// is the last one returned.
+///
+/// ```
+/// # use serde::Deserialize;
+/// # use marked_yaml::{FromNodeOptions, Marker};
+/// const YAML: &str = "port: eighty\nverbose: maybe\n";
+/// let node = marked_yaml::parse_yaml(0, YAML).unwrap();
+/// #[derive(Deserialize)]
+/// struct Config {
+///     port: u16,
+///     verbose: bool,
+/// }
+/// let errors = marked_yaml::from_node_collect_errors::<Config>(&node, FromNodeOptions::default())
+///     .err()
+///     .unwrap();
+/// assert_eq!(errors.len(), 2);
+/// assert_eq!(errors[0].start_mark(), Some(Marker::new(0, 1, 7)));
+/// assert_eq!(errors[1].start_mark(), Some(Marker::new(0, 2, 10)));
+/// ```
+pub fn from_node_collect_errors<'de, T>(
+    node: &'de Node,
+    options: FromNodeOptions,
+) -> Result<T, Vec<Error>>
+where
+    T: Deserialize<'de>,
+{
+    let ctx = DeserializerContext {
+        collect_errors: true,
+        ..DeserializerContext::new(options)
+    };
+    let result = from_node_with_context(node, ctx.clone());
+    let mut errors = ctx.errors.take();
+    if options.prefer_start_only_spans {
+        for e in errors.iter_mut() {
+            let span = e.span().start_only();
+            e.set_span(span);
+        }
+    }
+    match result {
+        Ok(value) if errors.is_empty() => Ok(value),
+        Ok(_) => Err(errors),
+        Err(e) => {
+            errors.push(e.into_inner());
+            Err(errors)
+        }
+    }
+}
+
+#[allow(clippy::result_large_err)]
+fn from_node_with_context<'de, T>(
+    node: &'de Node,
+    ctx: DeserializerContext,
+) -> Result<T, FromNodeError>
+where
+    T: Deserialize<'de>,
+{
+    #[cfg(not(feature = "serde-path"))]
+    fn inner_from_node<'de, T>(
+        node: &'de Node,
+        ctx: DeserializerContext,
+    ) -> Result<T, FromNodeError>
+    where
+        T: Deserialize<'de>,
     {
-        T::deserialize(NodeDeserializer::new(node)).map_err(|e| FromNodeError {
-            error: e,
+        let unclaimed = ctx.clone();
+        T::deserialize(node.into_optioned_deserializer(ctx)).map_err(|e| FromNodeError {
+            error: e.with_default_span(unclaimed.unclaimed_span(node)),
             path: None,
         })
     }
 
     #[cfg(feature = "serde-path")]
-    fn inner_from_node<'de, T>(node: &'de Node) -> Result<T, FromNodeError>
+    fn inner_from_node<'de, T>(
+        node: &'de Node,
+        ctx: DeserializerContext,
+    ) -> Result<T, FromNodeError>
     where
         T: Deserialize<'de>,
     {
         use serde_path_to_error::Segment;
 
-        let p2e: Result<T, _> = serde_path_to_error::deserialize(NodeDeserializer::new(node));
+        let unclaimed = ctx.clone();
+        let p2e: Result<T, _> =
+            serde_path_to_error::deserialize(node.into_optioned_deserializer(ctx));
 
         p2e.map_err(|e| {
             if e.inner().start_mark().is_none() {
@@ -619,7 +1739,12 @@ where
                         Segment::Unknown => break,
                     }
                 }
-                let mut best_span = *best_node.span();
+                // The root node only claims errors about buffered content
+                let mut best_span = if std::ptr::eq(best_node, node) {
+                    Span::new_blank()
+                } else {
+                    *best_node.span()
+                };
                 if let Error::UnknownFieldError(field, _, _) = &e {
                     // We actually would prefer to point at the key not the value,
                     if let Some(map) = prev_best_node.as_mapping() {
@@ -633,7 +1758,7 @@ where
                 }
                 e.set_span(best_span);
                 FromNodeError {
-                    error: e,
+                    error: e.with_default_span(unclaimed.unclaimed_span(node)),
                     path: Some(path),
                 }
             } else {
@@ -646,7 +1771,15 @@ where
         })
     }
 
-    inner_from_node(node)
+    let prefer_start_only_spans = ctx.options.prefer_start_only_spans;
+    let ctx = DeserializerContext { root: true, ..ctx };
+    inner_from_node(node, ctx).map_err(|mut e| {
+        if prefer_start_only_spans {
+            let span = e.error.span().start_only();
+            e.error.set_span(span);
+        }
+        e
+    })
 }
 
 #[cfg(feature = "serde-path")]
@@ -718,13 +1851,13 @@ macro_rules! forward_to_nodes {
             {
                 match self.node {
                     Node::Scalar(s) => s
-                        .into_deserializer()
+                        .into_optioned_deserializer(self.ctx)
                         .$meth($($arg,)* visitor),
                     Node::Mapping(m) => m
-                        .into_deserializer()
+                        .into_optioned_deserializer(self.ctx)
                         .$meth($($arg,)* visitor),
                     Node::Sequence(s) => s
-                        .into_deserializer()
+                        .into_optioned_deserializer(self.ctx)
                         .$meth($($arg,)* visitor),
                 }
             }
@@ -773,6 +1906,7 @@ impl MarkedValue for Node {
 struct SpannedDeserializer<'de, T> {
     node: &'de T,
     state: SpannedDeserializerState,
+    ctx: DeserializerContext,
 }
 
 enum SpannedDeserializerState {
@@ -790,7 +1924,7 @@ impl<'de, T> SpannedDeserializer<'de, T>
 where
     T: MarkedValue,
 {
-    fn new(node: &'de T) -> Self {
+    fn new(node: &'de T, ctx: DeserializerContext) -> Self {
         let state = if node.mark_span().start().is_some() {
             SpannedDeserializerState::SendStartSource
         } else if node.mark_span().end().is_some() {
@@ -798,14 +1932,35 @@ where
         } else {
             SpannedDeserializerState::SendValue
         };
-        Self { node, state }
+        Self { node, state, ctx }
+    }
+}
+
+impl<'de, T> SpannedDeserializer<'de, T>
+where
+    T: MarkedValue,
+{
+    // The states which send markers are only entered when the markers are
+    // present, but there's no need to panic if that is ever not true
+    fn start(&self) -> Result<&'de Marker, Error> {
+        self.node
+            .mark_span()
+            .start()
+            .ok_or_else(|| serde::de::Error::custom("marked node span missing start"))
+    }
+
+    fn end(&self) -> Result<&'de Marker, Error> {
+        self.node
+            .mark_span()
+            .end()
+            .ok_or_else(|| serde::de::Error::custom("marked node span missing end"))
     }
 }
 
 impl<'de, T> MapAccess<'de> for SpannedDeserializer<'de, T>
 where
     T: MarkedValue,
-    &'de T: IntoDeserializer<'de, Error>,
+    &'de T: IntoOptionedDeserializer<'de>,
 {
     type Error = Error;
 
@@ -833,32 +1988,17 @@ where
     {
         match self.state {
             SpannedDeserializerState::SendStartSource => {
-                let v = self
-                    .node
-                    .mark_span()
-                    .start()
-                    .expect("Span missing start")
-                    .source();
+                let v = self.start()?.source();
                 self.state = SpannedDeserializerState::SendStartLine;
                 seed.deserialize(v.into_deserializer())
             }
             SpannedDeserializerState::SendStartLine => {
-                let v = self
-                    .node
-                    .mark_span()
-                    .start()
-                    .expect("Span missing start")
-                    .line();
+                let v = self.start()?.line();
                 self.state = SpannedDeserializerState::SendStartColumn;
                 seed.deserialize(v.into_deserializer())
             }
             SpannedDeserializerState::SendStartColumn => {
-                let v = self
-                    .node
-                    .mark_span()
-                    .start()
-                    .expect("Span missing start")
-                    .column();
+                let v = self.start()?.column();
                 self.state = if self.node.mark_span().end().is_some() {
                     SpannedDeserializerState::SendEndSource
                 } else {
@@ -867,40 +2007,28 @@ where
                 seed.deserialize(v.into_deserializer())
             }
             SpannedDeserializerState::SendEndSource => {
-                let v = self
-                    .node
-                    .mark_span()
-                    .end()
-                    .expect("Span missing end")
-                    .source();
+                let v = self.end()?.source();
                 self.state = SpannedDeserializerState::SendEndLine;
                 seed.deserialize(v.into_deserializer())
             }
             SpannedDeserializerState::SendEndLine => {
-                let v = self
-                    .node
-                    .mark_span()
-                    .end()
-                    .expect("Span missing end")
-                    .line();
+                let v = self.end()?.line();
                 self.state = SpannedDeserializerState::SendEndColumn;
                 seed.deserialize(v.into_deserializer())
             }
             SpannedDeserializerState::SendEndColumn => {
-                let v = self
-                    .node
-                    .mark_span()
-                    .end()
-                    .expect("Span missing end")
-                    .column();
+                let v = self.end()?.column();
                 self.state = SpannedDeserializerState::SendValue;
                 seed.deserialize(v.into_deserializer())
             }
             SpannedDeserializerState::SendValue => {
                 self.state = SpannedDeserializerState::Done;
-                seed.deserialize(self.node.into_deserializer())
+                seed.deserialize(self.node.into_optioned_deserializer(self.ctx.clone()))
+                    .map_err(|e| e.with_default_span(*self.node.mark_span()))
             }
-            SpannedDeserializerState::Done => panic!("next_value_seed called before next_key_seed"),
+            SpannedDeserializerState::Done => Err(serde::de::Error::custom(
+                "next_value_seed called before next_key_seed",
+            )),
         }
     }
 }
@@ -953,6 +2081,42 @@ impl<'de> VariantAccess<'de> for UnitVariantAccess {
 
 struct MarkedScalarNodeEnumAccess<'de> {
     node: &'de MarkedScalarNode,
+    variant: Option<&'static str>,
+    ctx: DeserializerContext,
+}
+
+// When variants are matched case insensitively, find the variant which the
+// scalar names.  Exact matches, and scalars which match nothing at all, are
+// left for the visitor to deal with as usual.
+fn find_variant(
+    node: &MarkedScalarNode,
+    variants: &'static [&'static str],
+    ctx: &DeserializerContext,
+) -> Option<&'static str> {
+    let name = node.as_str();
+    if !ctx.options.case_insensitive_variants || variants.contains(&name) {
+        return None;
+    }
+    variants
+        .iter()
+        .find(|variant| variant.eq_ignore_ascii_case(name))
+        .copied()
+}
+
+fn deserialize_variant<'de, V>(
+    node: &'de MarkedScalarNode,
+    variant: Option<&'static str>,
+    ctx: DeserializerContext,
+    seed: V,
+) -> Result<V::Value, Error>
+where
+    V: DeserializeSeed<'de>,
+{
+    match variant {
+        Some(variant) => seed.deserialize(BorrowedStrDeserializer::new(variant)),
+        None => seed.deserialize(node.into_optioned_deserializer(ctx)),
+    }
+    .map_err(|e| e.with_default_span(*node.span()))
 }
 
 impl<'de> EnumAccess<'de> for MarkedScalarNodeEnumAccess<'de> {
@@ -964,8 +2128,7 @@ impl<'de> EnumAccess<'de> for MarkedScalarNodeEnumAccess<'de> {
     where
         V: serde::de::DeserializeSeed<'de>,
     {
-        seed.deserialize(self.node.into_deserializer())
-            .map(|v| (v, UnitVariantAccess))
+        deserialize_variant(self.node, self.variant, self.ctx, seed).map(|v| (v, UnitVariantAccess))
     }
 }
 
@@ -974,13 +2137,190 @@ impl<'de> EnumAccess<'de> for MarkedScalarNodeEnumAccess<'de> {
 impl<'de> IntoDeserializer<'de, Error> for &'de MarkedScalarNode {
     type Deserializer = MarkedScalarNodeDeserializer<'de>;
     fn into_deserializer(self) -> MarkedScalarNodeDeserializer<'de> {
-        MarkedScalarNodeDeserializer { node: self }
+        self.into_optioned_deserializer(DeserializerContext::default())
+    }
+}
+
+impl<'de> IntoOptionedDeserializer<'de> for &'de MarkedScalarNode {
+    type Deserializer = MarkedScalarNodeDeserializer<'de>;
+    fn into_optioned_deserializer(self, ctx: DeserializerContext) -> Self::Deserializer {
+        MarkedScalarNodeDeserializer { node: self, ctx }
     }
 }
 
+// When collecting errors, a node of the wrong kind is recorded as a type
+// mismatch and read as an empty value of the expected kind, so that
+// deserialisation carries on with the next field.  Otherwise the node is
+// handed to the visitor as it is, and the visitor rejects it.
+macro_rules! recover_mismatch {
+    ($found:literal, $($meth:ident($expected:literal) => $visit:ident($($value:expr)?),)*) => {
+        $(
+            fn $meth<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                if !self.ctx.collect_errors {
+                    return self.deserialize_any(visitor);
+                }
+                let span = *self.node.span();
+                self.ctx.recover(Error::TypeMismatch($expected, $found, span), ())?;
+                visitor.$visit($($value)?)
+            }
+        )*
+    };
+}
+
+// Decode the base64 text of a `!!binary` scalar.  This is usually written
+// as a block scalar over several lines, so whitespace is ignored.
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut count = 0;
+    let mut padding = 0;
+    for c in text.bytes().filter(|c| !c.is_ascii_whitespace()) {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => {
+                padding += 1;
+                continue;
+            }
+            _ => return None,
+        };
+        if padding > 0 {
+            return None;
+        }
+        bits = (bits << 6) | u32::from(value);
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            bytes.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+    // A lone trailing character can't make a whole byte
+    (count < 6 && padding <= 2).then_some(bytes)
+}
+
+// Ignored values are never looked at, so there's no need to walk them,
+// whatever kind of node they are.
+macro_rules! ignore_without_walking {
+    () => {
+        fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        where
+            V: Visitor<'de>,
+        {
+            visitor.visit_unit()
+        }
+    };
+}
+
+// An empty mapping, for when a mapping was expected but not found
+fn no_entries<'de>() -> serde::de::value::MapDeserializer<'de, std::iter::Empty<((), ())>, Error> {
+    serde::de::value::MapDeserializer::new(std::iter::empty())
+}
+
 /// Deserializer for scalar nodes
 pub struct MarkedScalarNodeDeserializer<'node> {
     node: &'node MarkedScalarNode,
+    ctx: DeserializerContext,
+}
+
+impl<'node> MarkedScalarNodeDeserializer<'node> {
+    // Legacy integers are given in decimal so that they can be parsed as
+    // any kind of number in the same way as everything else.
+    fn yaml11_integer(&self) -> Option<String> {
+        if self.ctx.options.yaml11_numbers && self.node.may_coerce() {
+            yaml11_integer(self.number_str()).map(|value| value.to_string())
+        } else {
+            None
+        }
+    }
+
+    fn number_str(&self) -> &'node str {
+        if self.ctx.options.trim_numbers {
+            self.node.as_str().trim()
+        } else {
+            self.node.as_str()
+        }
+    }
+}
+
+impl<'node> MarkedScalarNodeDeserializer<'node> {
+    fn check_ambiguous_string(&self) -> Result<(), Error> {
+        if !self.node.may_coerce() || !is_ambiguous_string(self.node.as_str()) {
+            return Ok(());
+        }
+        match self.ctx.options.ambiguous_strings {
+            AmbiguousStrings::Coerce => Ok(()),
+            AmbiguousStrings::Warn => {
+                self.ctx.warn(Warning::new(
+                    *self.node.span(),
+                    WarningKind::AmbiguousString,
+                ));
+                Ok(())
+            }
+            AmbiguousStrings::Reject => self.ctx.recover(
+                Error::Other(
+                    message(format_args!(
+                        "Plain scalar `{}` might not be a string, quote it if a string is intended",
+                        self.node.as_str()
+                    )),
+                    *self.node.span(),
+                ),
+                (),
+            ),
+        }
+    }
+}
+
+// Whether YAML 1.1 would resolve this plain scalar to something other than
+// a string.  This is deliberately broader than what we resolve ourselves
+// since the point is to catch values which other tools may read differently.
+fn is_ambiguous_string(s: &str) -> bool {
+    is_yaml11_boolean(s)
+        || matches!(
+            s,
+            "" | "~"
+                | "null"
+                | "Null"
+                | "NULL"
+                | ".inf"
+                | ".Inf"
+                | ".INF"
+                | "+.inf"
+                | "+.Inf"
+                | "+.INF"
+                | "-.inf"
+                | "-.Inf"
+                | "-.INF"
+                | ".nan"
+                | ".NaN"
+                | ".NAN"
+        )
+        || (is_core_schema_number(s) && s.parse::<f64>().is_ok())
+        || s.strip_prefix("0x")
+            .or_else(|| s.strip_prefix("0o"))
+            .is_some_and(|digits| {
+                !digits.is_empty() && digits.chars().all(|c| c.is_ascii_hexdigit())
+            })
+}
+
+// Rust's number parsing accepts a handful of spellings which the YAML core
+// schema does not, such as `inf` and `NaN`.  All of those start with a letter
+// whereas core schema numbers (after any sign) start with a digit or a dot.
+fn is_core_schema_number(s: &str) -> bool {
+    s.trim_start_matches(['+', '-'])
+        .starts_with(|c: char| c.is_ascii_digit() || c == '.')
+}
+
+// Strict mode also refuses the legacy YAML 1.1 integer forms, since the two
+// schemas disagree on what `0755` means and YAML 1.2 has no `1:30` at all.
+fn is_strict_number(s: &str) -> bool {
+    is_core_schema_number(s) && yaml11_integer(s).is_none()
 }
 
 macro_rules! scalar_fromstr {
@@ -1004,12 +2344,39 @@ macro_rules! scalar_fromstr {
         where
             V: Visitor<'de>,
         {
-            let value: $ty = self.node.as_str().parse().addspans(*self.node.span())?;
+            let text = self.number_str();
+            let legacy = self.yaml11_integer();
+            let s = legacy.as_deref().unwrap_or(text);
+            let parsed: Result<$ty, Error> =
+                if self.ctx.options.strict && !is_strict_number(text) {
+                    Err(serde::de::Error::invalid_type(Unexpected::Str(text), &visitor))
+                } else {
+                    s.parse().map_err(Error::from)
+                };
+            let value = match parsed.addspans(*self.node.span()) {
+                Ok(value) => value,
+                Err(e) => self.ctx.recover(e, <$ty>::default())?,
+            };
             visitor.$visit(value)
         }
     };
 }
 
+// Types which are always read from the text of a scalar, regardless of
+// what it might otherwise resolve to.
+macro_rules! scalar_as_str {
+    ($($meth:ident)*) => {
+        $(
+            fn $meth<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                visitor.visit_borrowed_str(self.node.as_str())
+            }
+        )*
+    };
+}
+
 impl<'de> Deserializer<'de> for MarkedScalarNodeDeserializer<'de> {
     type Error = Error;
 
@@ -1017,21 +2384,46 @@ impl<'de> Deserializer<'de> for MarkedScalarNodeDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        self.node
-            .deref()
-            .into_deserializer()
-            .deserialize_any(visitor)
-    }
-
-    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+        self.ctx.note_buffered();
+        if self.ctx.options.strict
+            && self.node.may_coerce()
+            && yaml11_integer(self.number_str()).is_some()
+        {
+            return visitor.visit_borrowed_str(self.node.as_str());
+        }
+        if let Some(value) = self.yaml11_integer().and_then(|v| v.parse::<i64>().ok()) {
+            return visitor.visit_i64(value);
+        }
+        match self.node.resolve() {
+            ResolvedScalar::Null => visitor.visit_unit(),
+            ResolvedScalar::Bool(b) => visitor.visit_bool(b),
+            ResolvedScalar::Int(i) => visitor.visit_i64(i),
+            ResolvedScalar::UInt(u) => visitor.visit_u64(u),
+            ResolvedScalar::Float(f) => visitor.visit_f64(f),
+            ResolvedScalar::Str(s) => visitor.visit_borrowed_str(s),
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_bool(
-            self.node
-                .as_bool()
-                .ok_or(Error::NotBoolean(*self.node.span()))?,
-        )
+        let numeric = if self.ctx.options.numeric_bools {
+            match self.node.as_str() {
+                "0" => Some(false),
+                "1" => Some(true),
+                _ => None,
+            }
+        } else {
+            None
+        };
+        let value = match self.node.as_bool().or(numeric) {
+            Some(value) => value,
+            None => self
+                .ctx
+                .recover(Error::NotBoolean(*self.node.span()), false)?,
+        };
+        visitor.visit_bool(value)
     }
 
     scalar_fromstr!();
@@ -1046,7 +2438,7 @@ impl<'de> Deserializer<'de> for MarkedScalarNodeDeserializer<'de> {
         V: Visitor<'de>,
     {
         if name == SPANNED_TYPE && fields == SPANNED_FIELDS {
-            return visitor.visit_map(SpannedDeserializer::new(self.node));
+            return visitor.visit_map(SpannedDeserializer::new(self.node, self.ctx));
         }
 
         self.deserialize_any(visitor)
@@ -1056,26 +2448,96 @@ impl<'de> Deserializer<'de> for MarkedScalarNodeDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        // Since we're here, there is no none, so visit as a some
-        visitor.visit_some(self)
+        // Only a plain null scalar counts as none, quoted ones are strings
+        if let ResolvedScalar::Null = self.node.resolve() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.check_ambiguous_string()?;
+        visitor.visit_borrowed_str(self.node.as_str())
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
     }
 
     fn deserialize_enum<V>(
         self,
         _name: &'static str,
-        _variants: &'static [&'static str],
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        visitor.visit_enum(MarkedScalarNodeEnumAccess { node: self.node })
+        visitor.visit_enum(MarkedScalarNodeEnumAccess {
+            node: self.node,
+            variant: find_variant(self.node, variants, &self.ctx),
+            ctx: self.ctx,
+        })
+    }
+
+    scalar_as_str!(deserialize_char deserialize_identifier);
+
+    // Scalars are handed over as their UTF-8 bytes, borrowed from the node,
+    // so `&[u8]` fields need not copy them, unless they're tagged `!!binary`
+    // in which case they're decoded from base64.  Byte sequences can also be
+    // written as sequences of integers.
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if !self.node.is_binary() {
+            return visitor.visit_borrowed_bytes(self.node.as_str().as_bytes());
+        }
+        match decode_base64(self.node.as_str()) {
+            Some(bytes) => visitor.visit_byte_buf(bytes),
+            None => Err(<Error as serde::de::Error>::invalid_value(
+                Unexpected::Str(self.node.as_str()),
+                &"base64 encoded binary data",
+            )),
+        }
+        .map_err(|e: Error| e.with_default_span(*self.node.span()))
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    ignore_without_walking!();
+
+    recover_mismatch! {
+        "scalar",
+        deserialize_seq("sequence") => visit_seq(SequenceAccess::new(&[], DeserializerContext::default())),
+        deserialize_map("mapping") => visit_map(no_entries()),
     }
 
     forward_to_deserialize_any! [
-        char str string bytes byte_buf
-        unit unit_struct newtype_struct seq tuple tuple_struct map
-        identifier ignored_any
+        unit unit_struct tuple tuple_struct
     ];
 }
 
@@ -1084,16 +2546,51 @@ impl<'de> Deserializer<'de> for MarkedScalarNodeDeserializer<'de> {
 type MappingValueSeq<'de> = linked_hash_map::Iter<'de, MarkedScalarNode, Node>;
 struct MappingAccess<'de> {
     items: Peekable<MappingValueSeq<'de>>,
+    consumed: Vec<&'de MarkedScalarNode>,
+    ctx: DeserializerContext,
 }
 
 impl<'de> MappingAccess<'de> {
-    fn new(items: MappingValueSeq<'de>) -> Self {
+    fn new(items: MappingValueSeq<'de>, ctx: DeserializerContext) -> Self {
         Self {
             items: items.peekable(),
+            consumed: Vec::new(),
+            ctx,
+        }
+    }
+
+    // Structs with flattened fields gather up every entry they don't
+    // recognise for those fields to pick from.  If such a struct also denies
+    // unknown fields, it reports an entry which none of them took only once
+    // the whole mapping has been read, as a custom error with no span.  By
+    // then every key has been handed over, so point the error at the one it
+    // names.  serde gives no other way to tell which entries were taken.
+    fn locate_leftover(&mut self, error: Error) -> Error {
+        if !matches!(&error, Error::Other(_, span) if span.is_blank())
+            || self.items.peek().is_some()
+        {
+            return error;
+        }
+        let message = error.to_string();
+        let leftover = self
+            .consumed
+            .iter()
+            .find(|key| message == leftover_field_message(key.as_str()));
+        match leftover {
+            Some(key) => error.with_default_span(*key.span()),
+            None => error,
         }
     }
 }
 
+// The message serde_derive gives the custom error for a leftover field of a
+// struct with flattened fields which denies unknown fields.  This must match
+// serde_derive's text exactly, or leftover fields will go unlocated; the
+// `serde_leftover_field_message` test checks it against serde itself.
+fn leftover_field_message(key: &str) -> String {
+    format!("unknown field `{key}`")
+}
+
 impl<'de> MapAccess<'de> for MappingAccess<'de> {
     type Error = Error;
 
@@ -1102,7 +2599,7 @@ impl<'de> MapAccess<'de> for MappingAccess<'de> {
         K: serde::de::DeserializeSeed<'de>,
     {
         if let Some(next_key) = self.items.peek().map(|(k, _v)| k) {
-            seed.deserialize(next_key.into_deserializer()).map(Some)
+            deserialize_key(next_key, self.ctx.clone(), seed).map(Some)
         } else {
             Ok(None)
         }
@@ -1112,28 +2609,53 @@ impl<'de> MapAccess<'de> for MappingAccess<'de> {
     where
         V: serde::de::DeserializeSeed<'de>,
     {
-        seed.deserialize(
-            self.items
-                .next()
-                .expect("next_value_seed called before next_key_seed")
-                .1
-                .into_deserializer(),
-        )
+        let value = match self.items.next() {
+            Some((key, value)) => {
+                self.consumed.push(key);
+                value
+            }
+            None => {
+                return Err(serde::de::Error::custom(
+                    "next_value_seed called before next_key_seed",
+                ))
+            }
+        };
+        seed.deserialize(value.into_optioned_deserializer(self.ctx.clone()))
+            .map_err(|e| e.with_default_span(*value.span()))
     }
 }
 
+// Complex keys are deserialized from the collection they were written as,
+// rather than from the flow style text they load as
+fn deserialize_key<'de, T>(
+    key: &'de MarkedScalarNode,
+    ctx: DeserializerContext,
+    seed: T,
+) -> Result<T::Value, Error>
+where
+    T: serde::de::DeserializeSeed<'de>,
+{
+    match key.complex_key() {
+        Some(node) => seed.deserialize(node.into_optioned_deserializer(ctx)),
+        None => seed.deserialize(key.into_optioned_deserializer(ctx)),
+    }
+    .map_err(|e| e.with_default_span(*key.span()))
+}
+
 // -------------------------------------------------------------------------------
 
 struct MarkedMappingNodeEnumAccess<'de> {
     node: &'de MarkedMappingNode,
+    variants: &'static [&'static str],
+    ctx: DeserializerContext,
 }
 
 impl<'de> MarkedMappingNodeEnumAccess<'de> {
-    fn first(&self) -> &'de Node {
+    fn first(&self) -> Result<&'de Node, Error> {
         self.node
             .values()
             .next()
-            .expect("variant accessed before variant seed")
+            .ok_or_else(|| serde::de::Error::custom("variant accessed before variant seed"))
     }
 }
 
@@ -1146,8 +2668,8 @@ impl<'de> EnumAccess<'de> for MarkedMappingNodeEnumAccess<'de> {
         V: serde::de::DeserializeSeed<'de>,
     {
         if let Some(first) = self.node.keys().next() {
-            seed.deserialize(first.into_deserializer())
-                .map(|v| (v, self))
+            let variant = find_variant(first, self.variants, &self.ctx);
+            deserialize_variant(first, variant, self.ctx.clone(), seed).map(|v| (v, self))
         } else {
             Err(serde::de::Error::custom(
                 "Unexpected empty map when looking for enum variant",
@@ -1167,14 +2689,20 @@ impl<'de> VariantAccess<'de> for MarkedMappingNodeEnumAccess<'de> {
     where
         T: serde::de::DeserializeSeed<'de>,
     {
-        seed.deserialize(self.first().into_deserializer())
+        let value = self.first()?;
+        seed.deserialize(value.into_optioned_deserializer(self.ctx))
+            .map_err(|e| e.with_default_span(*value.span()))
     }
 
     fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        self.first().into_deserializer().deserialize_seq(visitor)
+        let value = self.first()?;
+        value
+            .into_optioned_deserializer(self.ctx)
+            .deserialize_seq(visitor)
+            .map_err(|e| e.with_default_span(*value.span()))
     }
 
     fn struct_variant<V>(
@@ -1185,7 +2713,11 @@ impl<'de> VariantAccess<'de> for MarkedMappingNodeEnumAccess<'de> {
     where
         V: Visitor<'de>,
     {
-        self.first().into_deserializer().deserialize_map(visitor)
+        let value = self.first()?;
+        value
+            .into_optioned_deserializer(self.ctx)
+            .deserialize_map(visitor)
+            .map_err(|e| e.with_default_span(*value.span()))
     }
 }
 
@@ -1195,13 +2727,22 @@ impl<'de> IntoDeserializer<'de, Error> for &'de MarkedMappingNode {
     type Deserializer = MarkedMappingNodeDeserializer<'de>;
 
     fn into_deserializer(self) -> Self::Deserializer {
-        MarkedMappingNodeDeserializer { node: self }
+        self.into_optioned_deserializer(DeserializerContext::default())
+    }
+}
+
+impl<'de> IntoOptionedDeserializer<'de> for &'de MarkedMappingNode {
+    type Deserializer = MarkedMappingNodeDeserializer<'de>;
+
+    fn into_optioned_deserializer(self, ctx: DeserializerContext) -> Self::Deserializer {
+        MarkedMappingNodeDeserializer { node: self, ctx }
     }
 }
 
 /// Deserializer for mapping nodes
 pub struct MarkedMappingNodeDeserializer<'de> {
     node: &'de MarkedMappingNode,
+    ctx: DeserializerContext,
 }
 
 impl<'de> Deserializer<'de> for MarkedMappingNodeDeserializer<'de> {
@@ -1211,7 +2752,25 @@ impl<'de> Deserializer<'de> for MarkedMappingNodeDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_map(MappingAccess::new(self.node.iter()))
+        self.ctx.note_buffered();
+        visitor.visit_map(MappingAccess::new(self.node.iter(), self.ctx))
+    }
+
+    // serde_derive reads structs with flattened fields as maps rather than
+    // as structs, so this is the only way in for the leftover fields which
+    // `locate_leftover()` looks for
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.ctx.note_buffered();
+        let mut access = MappingAccess::new(self.node.iter(), self.ctx);
+        visitor
+            .visit_map(&mut access)
+            // Finding the leftover field is best-effort, since it relies on
+            // the text of serde_derive's error, and if the lookup fails
+            // the error is simply left as it is
+            .map_err(|e| access.locate_leftover(e))
     }
 
     fn deserialize_struct<V>(
@@ -1224,7 +2783,7 @@ impl<'de> Deserializer<'de> for MarkedMappingNodeDeserializer<'de> {
         V: Visitor<'de>,
     {
         if name == SPANNED_TYPE && fields == SPANNED_FIELDS {
-            return visitor.visit_map(SpannedDeserializer::new(self.node));
+            return visitor.visit_map(SpannedDeserializer::new(self.node, self.ctx));
         }
 
         self.deserialize_any(visitor)
@@ -1238,10 +2797,21 @@ impl<'de> Deserializer<'de> for MarkedMappingNodeDeserializer<'de> {
         visitor.visit_some(self)
     }
 
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
     fn deserialize_enum<V>(
         self,
         _name: &'static str,
-        _variants: &'static [&'static str],
+        variants: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
@@ -1251,30 +2821,117 @@ impl<'de> Deserializer<'de> for MarkedMappingNodeDeserializer<'de> {
             0 => Err(serde::de::Error::custom(
                 "Expected map with one value, got empty map",
             )),
-            1 => visitor.visit_enum(MarkedMappingNodeEnumAccess { node: self.node }),
+            1 => visitor.visit_enum(MarkedMappingNodeEnumAccess {
+                node: self.node,
+                variants,
+                ctx: self.ctx,
+            }),
             n => Err(serde::de::Error::custom(format!(
                 "Expected map with one value, got {n} values"
             ))),
         }
     }
 
+    ignore_without_walking!();
+
+    // Mappings tagged `!!set` are sets, and since serde drives sets as
+    // sequences we present their keys as one.  Untagged mappings are never
+    // sequences, even if their values are all null.
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.node.is_set() {
+            visitor.visit_seq(MappingKeysAccess {
+                keys: self.node.keys(),
+                ctx: self.ctx,
+            })
+        } else if self.ctx.collect_errors {
+            let span = *self.node.span();
+            self.ctx
+                .recover(Error::TypeMismatch("sequence", "mapping", span), ())?;
+            visitor.visit_seq(SequenceAccess::new(&[], self.ctx))
+        } else {
+            self.deserialize_any(visitor)
+        }
+    }
+
+    recover_mismatch! {
+        "mapping",
+        deserialize_bool("scalar") => visit_bool(false),
+        deserialize_i8("scalar") => visit_i8(0),
+        deserialize_i16("scalar") => visit_i16(0),
+        deserialize_i32("scalar") => visit_i32(0),
+        deserialize_i64("scalar") => visit_i64(0),
+        deserialize_u8("scalar") => visit_u8(0),
+        deserialize_u16("scalar") => visit_u16(0),
+        deserialize_u32("scalar") => visit_u32(0),
+        deserialize_u64("scalar") => visit_u64(0),
+        deserialize_f32("scalar") => visit_f32(0.0),
+        deserialize_f64("scalar") => visit_f64(0.0),
+        deserialize_str("scalar") => visit_borrowed_str(""),
+        deserialize_string("scalar") => visit_borrowed_str(""),
+        deserialize_bytes("scalar") => visit_borrowed_bytes(&[]),
+        deserialize_byte_buf("scalar") => visit_borrowed_bytes(&[]),
+        deserialize_unit("scalar") => visit_unit(),
+    }
+
     forward_to_deserialize_any! [
-        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf
-        unit unit_struct newtype_struct seq tuple tuple_struct
-        map identifier ignored_any
+        char unit_struct tuple tuple_struct
+        identifier
     ];
 }
 
+struct MappingKeysAccess<I> {
+    keys: I,
+    ctx: DeserializerContext,
+}
+
+impl<'de, I> SeqAccess<'de> for MappingKeysAccess<I>
+where
+    I: ExactSizeIterator<Item = &'de MarkedScalarNode>,
+{
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Self::Error>
+    where
+        T: serde::de::DeserializeSeed<'de>,
+    {
+        match self.keys.next() {
+            Some(key) => deserialize_key(key, self.ctx.clone(), seed).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.keys.len())
+    }
+}
+
 // -------------------------------------------------------------------------------
 
 struct SequenceAccess<'de> {
     items: &'de [Node],
     pos: usize,
+    ctx: DeserializerContext,
 }
 
 impl<'de> SequenceAccess<'de> {
-    fn new(items: &'de [Node]) -> Self {
-        Self { items, pos: 0 }
+    fn new(items: &'de [Node], ctx: DeserializerContext) -> Self {
+        Self { items, pos: 0, ctx }
+    }
+
+    // Visitors for fixed length things such as tuples stop asking for
+    // elements once they have enough, so leftovers mean it was too long
+    fn end(self) -> Result<(), Error> {
+        if self.pos == self.items.len() {
+            Ok(())
+        } else {
+            Err(serde::de::Error::invalid_length(
+                self.items.len(),
+                &format!("{} elements in sequence", self.pos).as_str(),
+            ))
+        }
     }
 }
 
@@ -1291,8 +2948,10 @@ impl<'de> SeqAccess<'de> for SequenceAccess<'de> {
         let pos = self.pos;
         self.pos += 1;
 
-        seed.deserialize(self.items[pos].into_deserializer())
+        let value = &self.items[pos];
+        seed.deserialize(value.into_optioned_deserializer(self.ctx.clone()))
             .map(Some)
+            .map_err(|e| e.with_default_span(*value.span()))
     }
 }
 
@@ -1302,13 +2961,22 @@ impl<'de> IntoDeserializer<'de, Error> for &'de MarkedSequenceNode {
     type Deserializer = MarkedSequenceNodeDeserializer<'de>;
 
     fn into_deserializer(self) -> Self::Deserializer {
-        MarkedSequenceNodeDeserializer { node: self }
+        self.into_optioned_deserializer(DeserializerContext::default())
+    }
+}
+
+impl<'de> IntoOptionedDeserializer<'de> for &'de MarkedSequenceNode {
+    type Deserializer = MarkedSequenceNodeDeserializer<'de>;
+
+    fn into_optioned_deserializer(self, ctx: DeserializerContext) -> Self::Deserializer {
+        MarkedSequenceNodeDeserializer { node: self, ctx }
     }
 }
 
 /// Deserializer for sequence nodes
 pub struct MarkedSequenceNodeDeserializer<'de> {
     node: &'de MarkedSequenceNode,
+    ctx: DeserializerContext,
 }
 
 impl<'de> Deserializer<'de> for MarkedSequenceNodeDeserializer<'de> {
@@ -1318,7 +2986,13 @@ impl<'de> Deserializer<'de> for MarkedSequenceNodeDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_seq(SequenceAccess::new(self.node.as_slice()))
+        self.ctx.note_buffered();
+        let mut access = SequenceAccess::new(self.node.as_slice(), self.ctx);
+        let value = visitor.visit_seq(&mut access)?;
+        access
+            .end()
+            .map_err(|e| e.with_default_span(*self.node.span()))?;
+        Ok(value)
     }
 
     fn deserialize_struct<V>(
@@ -1331,7 +3005,7 @@ impl<'de> Deserializer<'de> for MarkedSequenceNodeDeserializer<'de> {
         V: Visitor<'de>,
     {
         if name == SPANNED_TYPE && fields == SPANNED_FIELDS {
-            return visitor.visit_map(SpannedDeserializer::new(self.node));
+            return visitor.visit_map(SpannedDeserializer::new(self.node, self.ctx));
         }
 
         self.deserialize_any(visitor)
@@ -1345,10 +3019,43 @@ impl<'de> Deserializer<'de> for MarkedSequenceNodeDeserializer<'de> {
         visitor.visit_some(self)
     }
 
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    ignore_without_walking!();
+
+    recover_mismatch! {
+        "sequence",
+        deserialize_bool("scalar") => visit_bool(false),
+        deserialize_i8("scalar") => visit_i8(0),
+        deserialize_i16("scalar") => visit_i16(0),
+        deserialize_i32("scalar") => visit_i32(0),
+        deserialize_i64("scalar") => visit_i64(0),
+        deserialize_u8("scalar") => visit_u8(0),
+        deserialize_u16("scalar") => visit_u16(0),
+        deserialize_u32("scalar") => visit_u32(0),
+        deserialize_u64("scalar") => visit_u64(0),
+        deserialize_f32("scalar") => visit_f32(0.0),
+        deserialize_f64("scalar") => visit_f64(0.0),
+        deserialize_str("scalar") => visit_borrowed_str(""),
+        deserialize_string("scalar") => visit_borrowed_str(""),
+        deserialize_bytes("scalar") => visit_borrowed_bytes(&[]),
+        deserialize_byte_buf("scalar") => visit_borrowed_bytes(&[]),
+        deserialize_unit("scalar") => visit_unit(),
+        deserialize_map("mapping") => visit_map(no_entries()),
+    }
+
     forward_to_deserialize_any! [
-        bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf
-        unit unit_struct newtype_struct seq tuple tuple_struct
-        map enum identifier ignored_any
+        char unit_struct seq tuple tuple_struct
+        enum identifier
     ];
 }
 
@@ -1356,7 +3063,7 @@ impl<'de> Deserializer<'de> for MarkedSequenceNodeDeserializer<'de> {
 
 #[cfg(test)]
 mod test {
-    use std::collections::HashMap;
+    use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
     use super::*;
 
@@ -1513,4 +3220,1678 @@ shouting: TRUE
         };
         assert!(matches!(&*err, Error::UnknownFieldError(_, _, _)));
     }
+
+    const UNTAGGED_DOC: &str = r#"first: [ a, b ]
+second: { other: thing }
+"#;
+
+    #[test]
+    #[allow(dead_code)]
+    fn prefer_start_only_spans() {
+        #[derive(Deserialize, Debug)]
+        struct TestDoc {
+            second: Untagged,
+        }
+        let node = crate::parse_yaml(0, UNTAGGED_DOC).unwrap();
+        let err = from_node::<TestDoc>(&node).err().unwrap();
+        assert!(err.span().end().is_some());
+        let options = FromNodeOptions {
+            prefer_start_only_spans: true,
+            ..FromNodeOptions::default()
+        };
+        let err = from_node_with_options::<TestDoc>(&node, options)
+            .err()
+            .unwrap();
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 9)));
+        assert_eq!(err.span().end(), None);
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(untagged)]
+    enum Untagged {
+        Named { name: String },
+        Listed(Vec<String>),
+    }
+
+    #[test]
+    fn untagged_enum_second_variant() {
+        #[derive(Deserialize)]
+        struct TestDoc {
+            first: Untagged,
+        }
+        let node = crate::parse_yaml(0, UNTAGGED_DOC).unwrap();
+        let doc: TestDoc = from_node(&node).unwrap();
+        assert_eq!(
+            doc.first,
+            Untagged::Listed(vec!["a".to_string(), "b".to_string()])
+        );
+    }
+
+    #[test]
+    #[allow(dead_code)]
+    fn untagged_enum_no_variant() {
+        #[derive(Deserialize, Debug)]
+        struct TestDoc {
+            second: Untagged,
+        }
+        let node = crate::parse_yaml(0, UNTAGGED_DOC).unwrap();
+        let err = from_node::<TestDoc>(&node).err().unwrap();
+        assert!(matches!(&*err, Error::Other(_, _)));
+        assert!(!err.span().is_blank());
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 9)));
+    }
+
+    #[test]
+    fn untagged_enum_at_root() {
+        struct Refuses;
+        impl<'de> Deserialize<'de> for Refuses {
+            fn deserialize<D: Deserializer<'de>>(_: D) -> Result<Self, D::Error> {
+                Err(serde::de::Error::custom("refused"))
+            }
+        }
+        let node = crate::parse_yaml(0, UNTAGGED_DOC).unwrap();
+        let err = from_node::<Untagged>(&node).err().unwrap();
+        assert_eq!(err.span(), node.span());
+        // Only buffered content is blamed on the root node
+        let err = from_node::<Refuses>(&node).err().unwrap();
+        assert!(err.span().is_blank());
+    }
+
+    #[test]
+    fn trim_numbers() {
+        #[derive(Deserialize, Debug)]
+        struct TestDoc {
+            padded: Spanned<u64>,
+            float: f64,
+        }
+        const DOC: &str = "padded: ' 42 '\nfloat: \"  1.5\"\n";
+        let node = crate::parse_yaml(0, DOC).unwrap();
+        let err = from_node::<TestDoc>(&node).err().unwrap();
+        assert!(matches!(&*err, Error::IntegerParseFailure(_, _)));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 9)));
+        let options = FromNodeOptions {
+            trim_numbers: true,
+            ..FromNodeOptions::default()
+        };
+        let doc: TestDoc = from_node_with_options(&node, options).unwrap();
+        assert_eq!(*doc.padded, 42);
+        assert_eq!(doc.padded.span().start(), Some(&Marker::new(0, 1, 9)));
+        assert_eq!(doc.float, 1.5);
+    }
+
+    #[test]
+    #[allow(dead_code)]
+    fn renamed_and_aliased_fields() {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct TestDoc {
+            #[serde(rename = "display-name", alias = "title")]
+            name: String,
+            count: u8,
+        }
+        let node = crate::parse_yaml(0, "title: x\ncount: 1\nname: y\n").unwrap();
+        let err = from_node::<TestDoc>(&node).err().unwrap();
+        assert!(matches!(&*err, Error::UnknownFieldError(field, _, _) if field == "name"));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 3, 1)));
+        #[cfg(feature = "serde-path")]
+        assert_eq!(err.path(), Some("name"));
+        let node = crate::parse_yaml(0, "display-name: x\ncount: 1\n").unwrap();
+        assert!(from_node::<TestDoc>(&node).is_ok());
+        let node = crate::parse_yaml(0, "count: 1\ntitle: x\n").unwrap();
+        assert!(from_node::<TestDoc>(&node).is_ok());
+    }
+
+    #[test]
+    fn typed_getters() {
+        let node = crate::parse_yaml(0, "server:\n  port: 8080\n  host: [a]\n").unwrap();
+        let map = node.as_mapping().unwrap().get_mapping("server").unwrap();
+        let port: Spanned<u16> = map.get_typed("port").unwrap();
+        assert_eq!(*port, 8080);
+        assert_eq!(port.span().start(), Some(&Marker::new(0, 2, 9)));
+        let port: u32 = map.get_typed("port").unwrap();
+        assert_eq!(port, 8080);
+
+        let err = map.get_typed::<u8>("port").err().unwrap();
+        assert!(matches!(err, Error::IntegerParseFailure(_, _)));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 9)));
+        let err = map.get_typed::<String>("host").err().unwrap();
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 3, 9)));
+
+        let key = String::from("timeout");
+        let err = map.get_typed::<u16>(&key).err().unwrap();
+        assert!(matches!(&err, Error::MissingField(field, _) if *field == key));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 7)));
+    }
+
+    #[test]
+    fn identifier_errors() {
+        #[derive(PartialEq, Eq, Hash, Debug)]
+        struct Name(String);
+        impl<'de> Deserialize<'de> for Name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct NameVisitor;
+                impl<'de> Visitor<'de> for NameVisitor {
+                    type Value = Name;
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        f.write_str("a name without dashes")
+                    }
+                    fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Name, E> {
+                        if v.contains('-') {
+                            Err(E::invalid_value(Unexpected::Str(v), &self))
+                        } else {
+                            Ok(Name(v.to_string()))
+                        }
+                    }
+                }
+                deserializer.deserialize_identifier(NameVisitor)
+            }
+        }
+        let node = crate::parse_yaml(0, "good: 1\n42: 2\nbad-name: 3\n").unwrap();
+        let err = from_node::<HashMap<Name, u8>>(&node).err().unwrap();
+        assert!(err.to_string().contains("bad-name"));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 3, 1)));
+
+        // Identifiers are always names, even if they look like numbers
+        #[derive(Deserialize, Debug)]
+        #[serde(deny_unknown_fields)]
+        #[allow(dead_code)]
+        struct TestDoc {
+            first: u8,
+        }
+        let node = crate::parse_yaml(0, "first: 1\n0: 2\n").unwrap();
+        let err = from_node::<TestDoc>(&node).err().unwrap();
+        assert!(matches!(&*err, Error::UnknownFieldError(field, _, _) if field == "0"));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 1)));
+    }
+
+    #[test]
+    fn case_insensitive_variants() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        enum Mode {
+            Fast,
+            Slow,
+            Custom(u8),
+        }
+        #[derive(Deserialize, Debug)]
+        struct TestDoc {
+            mode: Mode,
+            other: Mode,
+        }
+        let node = crate::parse_yaml(0, "mode: FAST\nother: {custom: 3}\n").unwrap();
+        let err = from_node::<TestDoc>(&node).err().unwrap();
+        assert!(matches!(&*err, Error::UnknownVariant(v, _, _) if v == "FAST"));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 7)));
+        assert!(err
+            .to_string()
+            .ends_with("Unknown variant `FAST`, expected one of `Fast`, `Slow`, or `Custom`"));
+        let options = FromNodeOptions {
+            case_insensitive_variants: true,
+            ..FromNodeOptions::default()
+        };
+        let doc: TestDoc = from_node_with_options(&node, options).unwrap();
+        assert_eq!(doc.mode, Mode::Fast);
+        assert_eq!(doc.other, Mode::Custom(3));
+        let node = crate::parse_yaml(0, "mode: Slow\nother: quick\n").unwrap();
+        let err = from_node_with_options::<TestDoc>(&node, options)
+            .err()
+            .unwrap();
+        assert!(matches!(&*err, Error::UnknownVariant(v, _, _) if v == "quick"));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 8)));
+    }
+
+    #[test]
+    fn detached_subtrees() {
+        #[derive(Deserialize, Debug)]
+        struct Database {
+            host: Spanned<String>,
+            port: u16,
+        }
+        let node =
+            crate::parse_yaml(0, "name: app\ndb:\n  host: localhost\n  port: 5432\n").unwrap();
+        let db = node.as_mapping().unwrap().get_node("db").unwrap().clone();
+        drop(node);
+        let db: Database = from_node(&db).unwrap();
+        assert_eq!(db.host.as_str(), "localhost");
+        assert_eq!(db.host.span().start(), Some(&Marker::new(0, 3, 9)));
+        assert_eq!(db.port, 5432);
+    }
+
+    #[test]
+    fn lazy_sequences() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Server {
+            name: String,
+            port: u16,
+        }
+        let options = LoaderOptions {
+            allow_non_mapping_top_level: true,
+            ..LoaderOptions::default()
+        };
+        let node = crate::parse_yaml_with_options(
+            0,
+            "- {name: a, port: 1}\n- {name: b, port: many}\n- {name: c, port: 3}\n",
+            options,
+        )
+        .unwrap();
+        let mut servers = from_node_seq::<Server>(&node);
+        let first = servers.next().unwrap().unwrap();
+        assert_eq!(first.name, "a");
+        let err = servers.next().unwrap().err().unwrap();
+        assert!(matches!(err, Error::IntegerParseFailure(_, _)));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 19)));
+        let last = servers.next().unwrap().unwrap();
+        assert_eq!(last.port, 3);
+        assert!(servers.next().is_none());
+
+        let node = crate::parse_yaml(0, "name: a\n").unwrap();
+        let results: Vec<_> = from_node_seq::<Server>(&node).collect();
+        assert_eq!(results.len(), 1);
+        assert!(matches!(
+            results[0],
+            Err(Error::TypeMismatch("sequence", "mapping", _))
+        ));
+    }
+
+    #[test]
+    fn conflict_errors() {
+        #[derive(Deserialize)]
+        struct Source {
+            file: Option<Spanned<String>>,
+            url: Option<Spanned<String>>,
+        }
+        let node = crate::parse_yaml(0, "file: a.txt\nurl: https://example.com/\n").unwrap();
+        let source: Source = from_node(&node).unwrap();
+        let err = match (&source.file, &source.url) {
+            (Some(file), Some(url)) => Error::conflict(
+                "`file` and `url` may not both be given",
+                [*file.span(), *url.span()],
+            ),
+            _ => unreachable!(),
+        };
+        assert_eq!(err.to_string(), "`file` and `url` may not both be given");
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 7)));
+        let rendered: Vec<_> = err
+            .spans()
+            .iter()
+            .map(|span| span.start().unwrap().render(|_| "source.yaml").to_string())
+            .collect();
+        assert_eq!(rendered, ["source.yaml:1:7", "source.yaml:2:6"]);
+
+        // A conflict without any spans can still be located later on
+        let err = Error::conflict("oops", None).with_default_span(*node.span());
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 5)));
+        assert_eq!(err.spans().len(), 1);
+    }
+
+    #[test]
+    fn flatten_with_deny_unknown_fields() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Connection {
+            host: String,
+            port: u16,
+        }
+        #[derive(Deserialize, Debug)]
+        #[serde(deny_unknown_fields)]
+        struct Strict {
+            name: Spanned<String>,
+            #[serde(flatten)]
+            connection: Connection,
+        }
+        // Leftover keys are only reported once every entry has been read, so
+        // serde's ordering would otherwise leave the error without a span
+        let node =
+            crate::parse_yaml(0, "extra: 1\nname: db\nhost: localhost\nport: 5432\n").unwrap();
+        let err = from_node::<Strict>(&node).err().unwrap();
+        assert_eq!((*err).to_string(), "unknown field `extra`");
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 1)));
+        let node =
+            crate::parse_yaml(0, "name: db\nhost: localhost\nport: 5432\nextra: 1\n").unwrap();
+        let err = from_node::<Strict>(&node).err().unwrap();
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 4, 1)));
+        // Fields of the outer struct are read directly, so keep their spans
+        let node = crate::parse_yaml(0, "name: db\nhost: localhost\nport: 5432\n").unwrap();
+        let strict: Strict = from_node(&node).unwrap();
+        assert_eq!(strict.name.span().start(), Some(&Marker::new(0, 1, 7)));
+        assert_eq!(strict.connection.port, 5432);
+        let node = crate::parse_yaml(0, "name: [db]\nhost: localhost\nport: 5432\n").unwrap();
+        let err = from_node::<Strict>(&node).err().unwrap();
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 7)));
+        // Flattened fields are read from buffered values, so their errors can
+        // only be located at the mapping as a whole
+        let node = crate::parse_yaml(0, "name: db\nhost: localhost\nport: many\n").unwrap();
+        let err = from_node::<Strict>(&node).err().unwrap();
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 5)));
+
+        #[derive(Deserialize, Debug)]
+        struct CatchAll {
+            name: Spanned<String>,
+            #[serde(flatten)]
+            rest: HashMap<String, String>,
+        }
+        let node = crate::parse_yaml(0, "colour: red\nname: db\nsize: large\n").unwrap();
+        let catch_all: CatchAll = from_node(&node).unwrap();
+        assert_eq!(catch_all.name.span().start(), Some(&Marker::new(0, 2, 7)));
+        assert_eq!(catch_all.rest.len(), 2);
+        assert_eq!(catch_all.rest["size"], "large");
+
+        // serde doesn't remove the entries a flattened map collects, so even
+        // those count as unknown when the outer struct denies unknown fields
+        #[derive(Deserialize, Debug)]
+        #[serde(deny_unknown_fields)]
+        #[allow(dead_code)]
+        struct Denying {
+            name: String,
+            #[serde(flatten)]
+            rest: HashMap<String, String>,
+        }
+        let err = from_node::<Denying>(&node).err().unwrap();
+        assert_eq!((*err).to_string(), "unknown field `colour`");
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 1)));
+
+        // Types read as structs have no flattened fields, so an error which
+        // merely looks like a leftover field is left at the mapping
+        #[derive(Debug)]
+        struct Fussy;
+        impl<'de> Deserialize<'de> for Fussy {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                struct FussyVisitor;
+                impl<'de> Visitor<'de> for FussyVisitor {
+                    type Value = Fussy;
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        f.write_str("nothing at all")
+                    }
+                    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Fussy, A::Error> {
+                        while map
+                            .next_entry::<serde::de::IgnoredAny, serde::de::IgnoredAny>()?
+                            .is_some()
+                        {}
+                        Err(serde::de::Error::custom("unknown field `extra`"))
+                    }
+                }
+                deserializer.deserialize_struct("Fussy", &[], FussyVisitor)
+            }
+        }
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Holder {
+            fussy: Fussy,
+        }
+        let node = crate::parse_yaml(0, "fussy: {extra: 1}\n").unwrap();
+        let err = from_node::<Holder>(&node).err().unwrap();
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 8)));
+    }
+
+    #[test]
+    fn serde_leftover_field_message() {
+        // Locating leftover fields relies on the exact text of serde_derive's
+        // error, so check it against what serde's own deserializers report
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Inner {
+            kept: String,
+        }
+        #[derive(Deserialize, Debug)]
+        #[serde(deny_unknown_fields)]
+        #[allow(dead_code)]
+        struct Outer {
+            #[serde(flatten)]
+            inner: Inner,
+        }
+        let entries = [("kept", "1"), ("extra", "2")];
+        let deserializer = serde::de::value::MapDeserializer::<_, serde::de::value::Error>::new(
+            entries.into_iter(),
+        );
+        let err = Outer::deserialize(deserializer).unwrap_err();
+        assert_eq!(err.to_string(), "unknown field `extra`");
+        assert_eq!(err.to_string(), leftover_field_message("extra"));
+    }
+    #[test]
+    fn adjacently_tagged_enums() {
+        #[derive(Deserialize, Debug)]
+        #[serde(tag = "kind", content = "data")]
+        enum Shape {
+            Circle { radius: Spanned<f64> },
+            Square(Spanned<u32>),
+            Empty,
+        }
+        #[derive(Deserialize, Debug)]
+        struct TestDoc {
+            shapes: Vec<Spanned<Shape>>,
+        }
+        const DOC: &str = r#"shapes:
+  - kind: Circle
+    data: {radius: 2.5}
+  - kind: Square
+    data: 4
+  - kind: Empty
+"#;
+        let node = crate::parse_yaml(0, DOC).unwrap();
+        let doc: TestDoc = from_node(&node).unwrap();
+        assert_eq!(doc.shapes[0].span().start(), Some(&Marker::new(0, 2, 9)));
+        match &*doc.shapes[0] {
+            Shape::Circle { radius } => {
+                assert_eq!(**radius, 2.5);
+                assert_eq!(radius.span().start(), Some(&Marker::new(0, 3, 20)));
+            }
+            other => panic!("Unexpected shape {other:?}"),
+        }
+        match &*doc.shapes[1] {
+            Shape::Square(side) => {
+                assert_eq!(**side, 4);
+                assert_eq!(side.span().start(), Some(&Marker::new(0, 5, 11)));
+            }
+            other => panic!("Unexpected shape {other:?}"),
+        }
+        assert!(matches!(*doc.shapes[2], Shape::Empty));
+        assert_eq!(doc.shapes[2].span().start(), Some(&Marker::new(0, 6, 9)));
+
+        let node = crate::parse_yaml(0, "shapes:\n  - kind: Triangle\n    data: 3\n").unwrap();
+        let err = from_node::<TestDoc>(&node).err().unwrap();
+        assert!(err.to_string().contains("Triangle"));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 11)));
+    }
+
+    #[test]
+    fn lazy_mappings() {
+        #[derive(Deserialize, Debug)]
+        struct Service {
+            image: String,
+            #[serde(default)]
+            replicas: u8,
+        }
+        const DOC: &str = "web: {image: nginx, replicas: 3}\ndb: {image: postgres, replicas: -1}\n42: {image: redis}\n";
+        let node = crate::parse_yaml(0, DOC).unwrap();
+        let mut services = from_node_map::<Spanned<String>, Service>(&node);
+        let (name, web) = services.next().unwrap().unwrap();
+        assert_eq!(name.as_str(), "web");
+        assert_eq!(web.replicas, 3);
+        let err = services.next().unwrap().err().unwrap();
+        assert!(matches!(err, Error::IntegerParseFailure(_, _)));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 33)));
+        let (name, cache) = services.next().unwrap().unwrap();
+        assert_eq!(name.span().start(), Some(&Marker::new(0, 3, 1)));
+        assert_eq!(cache.image, "redis");
+        assert!(services.next().is_none());
+
+        // Keys which fail are located at the key
+        let mut ports = from_node_map::<u16, String>(&node);
+        assert!(ports.next().unwrap().is_err());
+        let err = ports.nth(1).unwrap().err().unwrap();
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 3, 5)));
+        let err = from_node_map::<u16, u16>(&node)
+            .next()
+            .unwrap()
+            .err()
+            .unwrap();
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 1)));
+
+        let results: Vec<_> = from_node_map::<String, String>(&Node::from("x")).collect();
+        assert!(matches!(
+            results[..],
+            [Err(Error::TypeMismatch("mapping", "scalar", _))]
+        ));
+    }
+
+    #[test]
+    fn io_errors() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct TestDoc {
+            ratio: f64,
+        }
+        let node = crate::parse_yaml(0, "ratio: half\n").unwrap();
+        let err = from_node::<TestDoc>(&node).err().unwrap().into_inner();
+        assert!(matches!(err, Error::FloatParseFailure(_, _)));
+        let ioerr = std::io::Error::from(err.clone());
+        assert_eq!(ioerr.kind(), std::io::ErrorKind::InvalidData);
+        assert_eq!(ioerr.to_string(), format!("1:8: {err}"));
+
+        fn run(node: &Node) -> std::io::Result<f64> {
+            let doc: TestDoc = from_node(node)?;
+            Ok(doc.ratio)
+        }
+        let ioerr = run(&node).err().unwrap();
+        assert!(ioerr.to_string().starts_with("1:8: "));
+        let node = crate::parse_yaml(0, "ratio: 0.5\n").unwrap();
+        assert_eq!(run(&node).unwrap(), 0.5);
+
+        let unlocated = Error::Other(message("oops"), Span::new_blank());
+        assert_eq!(std::io::Error::from(unlocated).to_string(), "oops");
+    }
+
+    #[test]
+    fn yaml11_numbers() {
+        #[derive(Deserialize, Debug)]
+        struct TestDoc {
+            mode: u32,
+            duration: i64,
+            ratio: f64,
+            name: String,
+        }
+        const DOC: &str = "mode: 0755\nduration: -1:30\nratio: 0_17\nname: '0755'\n";
+        let node = crate::parse_yaml(0, DOC).unwrap();
+        let err = from_node::<TestDoc>(&node).err().unwrap();
+        assert!(matches!(&*err, Error::IntegerParseFailure(_, _)));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 11)));
+        let options = FromNodeOptions {
+            yaml11_numbers: true,
+            ..FromNodeOptions::default()
+        };
+        let doc: TestDoc = from_node_with_options(&node, options).unwrap();
+        assert_eq!(doc.mode, 0o755);
+        assert_eq!(doc.duration, -90);
+        assert_eq!(doc.ratio, 15.0);
+        assert_eq!(doc.name, "0755");
+
+        // Under YAML 1.2 rules a leading zero is still decimal
+        let node = crate::parse_yaml(0, "mode: 0755\nduration: 1\nratio: 1\nname: a\n").unwrap();
+        let doc: TestDoc = from_node(&node).unwrap();
+        assert_eq!(doc.mode, 755);
+
+        let node = crate::parse_yaml(0, "a: 1:30\nb: 1:60\nc: 0789\nd: 1:2:3\ne: 0\n").unwrap();
+        let doc: HashMap<String, Any> = from_node(&node).unwrap();
+        assert_eq!(doc["a"], Any::Str("1:30".to_string()));
+        let doc: HashMap<String, Any> = from_node_with_options(&node, options).unwrap();
+        assert_eq!(doc["a"], Any::Int(90));
+        assert_eq!(doc["b"], Any::Str("1:60".to_string()));
+        assert_eq!(doc["c"], Any::Int(789));
+        assert_eq!(doc["d"], Any::Int(3723));
+        assert_eq!(doc["e"], Any::Int(0));
+
+        // Strict mode refuses the legacy forms under either set of rules
+        #[derive(Deserialize, Debug)]
+        struct Numbers {
+            value: i64,
+        }
+        for text in ["value: 0755\n", "value: 1:30\n", "value: -0_17\n"] {
+            let node = crate::parse_yaml(0, text).unwrap();
+            for yaml11_numbers in [false, true] {
+                let options = FromNodeOptions {
+                    strict: true,
+                    yaml11_numbers,
+                    ..FromNodeOptions::default()
+                };
+                let err = from_node_with_options::<Numbers>(&node, options)
+                    .err()
+                    .unwrap();
+                assert!(matches!(&*err, Error::Other(_, _)), "{text}: {err:?}");
+                assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 8)));
+                let any: HashMap<String, Any> = from_node_with_options(&node, options).unwrap();
+                assert_eq!(any["value"], Any::Str(text[7..text.len() - 1].to_string()));
+            }
+        }
+        let node = crate::parse_yaml(0, "value: 0\n").unwrap();
+        let options = FromNodeOptions {
+            strict: true,
+            yaml11_numbers: true,
+            ..FromNodeOptions::default()
+        };
+        assert_eq!(
+            from_node_with_options::<Numbers>(&node, options)
+                .unwrap()
+                .value,
+            0
+        );
+    }
+
+    #[test]
+    fn numeric_bools() {
+        #[derive(Deserialize)]
+        struct TestDoc {
+            flag: bool,
+            other: Spanned<bool>,
+        }
+        let node = crate::parse_yaml(0, "flag: 1\nother: 0\n").unwrap();
+        let err = from_node::<TestDoc>(&node).err().unwrap();
+        assert!(matches!(&*err, Error::NotBoolean(_)));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 7)));
+        let options = FromNodeOptions {
+            numeric_bools: true,
+            ..FromNodeOptions::default()
+        };
+        let doc: TestDoc = from_node_with_options(&node, options).unwrap();
+        assert!(doc.flag);
+        assert!(!*doc.other);
+        assert_eq!(doc.other.span().start(), Some(&Marker::new(0, 2, 8)));
+        let node = crate::parse_yaml(0, "flag: true\nother: 2\n").unwrap();
+        let err = from_node_with_options::<TestDoc>(&node, options)
+            .err()
+            .unwrap();
+        assert!(matches!(&*err, Error::NotBoolean(_)));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 8)));
+    }
+
+    #[test]
+    #[cfg(feature = "serde-yaml")]
+    fn serde_yaml_value_round_trip() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct TestDoc {
+            hello: String,
+            some: Vec<String>,
+            says: HashMap<String, String>,
+            numbers: Vec<u16>,
+            success: bool,
+            failure: bool,
+            shouting: bool,
+        }
+        let node = crate::parse_yaml(0, TEST_DOC).unwrap();
+        let direct: TestDoc = from_node(&node).unwrap();
+        let bridged: TestDoc = serde_yaml::from_value(node.to_serde_yaml_value()).unwrap();
+        assert_eq!(direct, bridged);
+    }
+
+    #[test]
+    fn strict_mode() {
+        #[derive(Deserialize, Debug)]
+        struct TestDoc {
+            country: String,
+            ratio: f64,
+        }
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Flag {
+            country: bool,
+        }
+        const DOC: &str = "country: NO\nratio: inf\n";
+        let node = crate::parse_yaml(0, DOC).unwrap();
+        let doc: TestDoc = from_node(&node).unwrap();
+        assert_eq!(doc.country, "NO");
+        assert!(doc.ratio.is_infinite());
+        let options = FromNodeOptions {
+            strict: true,
+            ..FromNodeOptions::default()
+        };
+        let err = from_node_with_options::<TestDoc>(&node, options)
+            .err()
+            .unwrap();
+        assert!(matches!(&*err, Error::Other(_, _)));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 8)));
+        let err = from_node_with_options::<Flag>(&node, options)
+            .err()
+            .unwrap();
+        assert!(matches!(&*err, Error::NotBoolean(_)));
+        #[derive(Deserialize, Debug)]
+        struct Country {
+            country: String,
+        }
+        let doc: Country = from_node_with_options(&node, options).unwrap();
+        assert_eq!(doc.country, "NO");
+    }
+
+    #[test]
+    fn ambiguous_strings() {
+        #[derive(Deserialize, Debug)]
+        struct TestDoc {
+            country: String,
+            quoted: String,
+            version: Spanned<String>,
+            name: Option<String>,
+        }
+        const DOC: &str = "country: NO\nquoted: \"NO\"\nversion: 1.10\nname: Norway\n";
+        let node = crate::parse_yaml(0, DOC).unwrap();
+        let (doc, warnings): (TestDoc, _) =
+            from_node_with_warnings(&node, FromNodeOptions::default()).unwrap();
+        assert_eq!(doc.country, "NO");
+        assert_eq!(doc.quoted, "NO");
+        assert_eq!(*doc.version, "1.10");
+        assert_eq!(doc.name.as_deref(), Some("Norway"));
+        assert!(warnings.is_empty());
+        let options = FromNodeOptions {
+            ambiguous_strings: AmbiguousStrings::Warn,
+            ..FromNodeOptions::default()
+        };
+        let (doc, warnings): (TestDoc, _) = from_node_with_warnings(&node, options).unwrap();
+        assert_eq!(doc.country, "NO");
+        assert_eq!(*doc.version, "1.10");
+        assert_eq!(
+            warnings,
+            vec![
+                Warning::new(
+                    Span::new_start(Marker::new(0, 1, 10)),
+                    WarningKind::AmbiguousString
+                ),
+                Warning::new(
+                    Span::new_start(Marker::new(0, 3, 10)),
+                    WarningKind::AmbiguousString
+                ),
+            ]
+        );
+        let options = FromNodeOptions {
+            ambiguous_strings: AmbiguousStrings::Reject,
+            ..FromNodeOptions::default()
+        };
+        let err = from_node_with_options::<TestDoc>(&node, options)
+            .err()
+            .unwrap();
+        assert!(matches!(&*err, Error::Other(_, _)));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 10)));
+    }
+
+    #[test]
+    fn collect_errors() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct TestDoc {
+            name: String,
+            port: u16,
+            ratio: Spanned<f64>,
+            verbose: bool,
+        }
+        const DOC: &str = "name: test\nport: 99999\nratio: 0.5\nverbose: maybe\n";
+        let node = crate::parse_yaml(0, DOC).unwrap();
+        let errors = from_node_collect_errors::<TestDoc>(&node, FromNodeOptions::default())
+            .err()
+            .unwrap();
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(&errors[0], Error::IntegerParseFailure(_, _)));
+        assert_eq!(errors[0].start_mark(), Some(Marker::new(0, 2, 7)));
+        assert!(matches!(&errors[1], Error::NotBoolean(_)));
+        assert_eq!(errors[1].start_mark(), Some(Marker::new(0, 4, 10)));
+
+        const BAD_STRUCTURE: &str = "name: [oops]\nport: big\n";
+        let node = crate::parse_yaml(0, BAD_STRUCTURE).unwrap();
+        let errors = from_node_collect_errors::<TestDoc>(&node, FromNodeOptions::default())
+            .err()
+            .unwrap();
+        assert_eq!(errors.len(), 3);
+        assert!(matches!(
+            &errors[0],
+            Error::TypeMismatch("scalar", "sequence", _)
+        ));
+        assert_eq!(errors[0].start_mark(), Some(Marker::new(0, 1, 7)));
+        assert!(matches!(&errors[1], Error::IntegerParseFailure(_, _)));
+        assert_eq!(errors[1].start_mark(), Some(Marker::new(0, 2, 7)));
+        assert_eq!(errors[2].to_string(), "missing field `ratio`");
+
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Containers {
+            tags: Vec<String>,
+            env: HashMap<String, String>,
+            port: u16,
+            name: Option<String>,
+        }
+        const WRONG_CONTAINERS: &str = "tags: { a: b }\nenv: [a, b]\nport: high\nname: [c]\n";
+        let node = crate::parse_yaml(0, WRONG_CONTAINERS).unwrap();
+        let errors = from_node_collect_errors::<Containers>(&node, FromNodeOptions::default())
+            .err()
+            .unwrap();
+        assert_eq!(errors.len(), 4);
+        assert!(matches!(
+            &errors[0],
+            Error::TypeMismatch("sequence", "mapping", _)
+        ));
+        assert_eq!(errors[0].start_mark(), Some(Marker::new(0, 1, 7)));
+        assert!(matches!(
+            &errors[1],
+            Error::TypeMismatch("mapping", "sequence", _)
+        ));
+        assert_eq!(errors[1].start_mark(), Some(Marker::new(0, 2, 6)));
+        assert!(matches!(&errors[2], Error::IntegerParseFailure(_, _)));
+        assert_eq!(errors[2].start_mark(), Some(Marker::new(0, 3, 7)));
+        assert!(matches!(
+            &errors[3],
+            Error::TypeMismatch("scalar", "sequence", _)
+        ));
+        assert_eq!(errors[3].start_mark(), Some(Marker::new(0, 4, 7)));
+
+        // Without collecting, the first mismatch is still the only error
+        let err = from_node::<Containers>(&node).err().unwrap();
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 7)));
+
+        const GOOD: &str = "name: test\nport: 80\nratio: 0.5\nverbose: true\n";
+        let node = crate::parse_yaml(0, GOOD).unwrap();
+        let doc = from_node_collect_errors::<TestDoc>(&node, FromNodeOptions::default()).unwrap();
+        assert_eq!(doc.port, 80);
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Any {
+        Null,
+        Bool(bool),
+        Int(i64),
+        UInt(u64),
+        Float(f64),
+        Str(String),
+    }
+
+    impl<'de> Deserialize<'de> for Any {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct AnyVisitor;
+            impl<'de> Visitor<'de> for AnyVisitor {
+                type Value = Any;
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("any scalar")
+                }
+                fn visit_unit<E>(self) -> Result<Any, E> {
+                    Ok(Any::Null)
+                }
+                fn visit_bool<E>(self, v: bool) -> Result<Any, E> {
+                    Ok(Any::Bool(v))
+                }
+                fn visit_i64<E>(self, v: i64) -> Result<Any, E> {
+                    Ok(Any::Int(v))
+                }
+                fn visit_u64<E>(self, v: u64) -> Result<Any, E> {
+                    Ok(Any::UInt(v))
+                }
+                fn visit_f64<E>(self, v: f64) -> Result<Any, E> {
+                    Ok(Any::Float(v))
+                }
+                fn visit_str<E>(self, v: &str) -> Result<Any, E> {
+                    Ok(Any::Str(v.to_string()))
+                }
+            }
+            deserializer.deserialize_any(AnyVisitor)
+        }
+    }
+
+    mod name_or_server {
+        use super::*;
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        pub struct Server {
+            pub name: String,
+            pub port: u16,
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Server, D::Error> {
+            struct ServerVisitor;
+            impl<'de> Visitor<'de> for ServerVisitor {
+                type Value = Server;
+                fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    f.write_str("a server name or description")
+                }
+                fn visit_str<E>(self, v: &str) -> Result<Server, E> {
+                    Ok(Server {
+                        name: v.to_string(),
+                        port: 80,
+                    })
+                }
+                fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Server, A::Error> {
+                    Server::deserialize(serde::de::value::MapAccessDeserializer::new(map))
+                }
+            }
+            deserializer.deserialize_any(ServerVisitor)
+        }
+    }
+
+    #[test]
+    fn deserialize_any_resolves_scalars() {
+        const DOC: &str = r#"
+null: ~
+empty:
+bool: true
+int: -3
+uint: 18446744073709551615
+float: 2.5
+answer: 42
+ratio: 1.75
+exponent: 1e3
+str: hello
+quoted: "42"
+"#;
+        let node = crate::parse_yaml(0, DOC).unwrap();
+        let doc: HashMap<String, Any> = from_node(&node).unwrap();
+        assert_eq!(doc["null"], Any::Null);
+        assert_eq!(doc["empty"], Any::Null);
+        assert_eq!(doc["bool"], Any::Bool(true));
+        assert_eq!(doc["int"], Any::Int(-3));
+        assert_eq!(doc["uint"], Any::UInt(u64::MAX));
+        assert_eq!(doc["float"], Any::Float(2.5));
+        assert_eq!(doc["answer"], Any::Int(42));
+        assert_eq!(doc["ratio"], Any::Float(1.75));
+        assert_eq!(doc["exponent"], Any::Float(1000.0));
+        assert_eq!(doc["str"], Any::Str("hello".to_string()));
+        assert_eq!(doc["quoted"], Any::Str("42".to_string()));
+
+        #[derive(Deserialize, Debug)]
+        struct TestDoc {
+            #[serde(with = "name_or_server")]
+            short: name_or_server::Server,
+            #[serde(with = "name_or_server")]
+            long: name_or_server::Server,
+        }
+        const SERVERS: &str = "short: example.com\nlong: {name: example.org, port: 8080}\n";
+        let node = crate::parse_yaml(0, SERVERS).unwrap();
+        let doc: TestDoc = from_node(&node).unwrap();
+        assert_eq!(doc.short.name, "example.com");
+        assert_eq!(doc.short.port, 80);
+        assert_eq!(doc.long.name, "example.org");
+        assert_eq!(doc.long.port, 8080);
+    }
+
+    #[test]
+    fn btreemap_keys() {
+        const DOC: &str = "zebra: 1\napple: 2\nmango: 3\n";
+        let node = crate::parse_yaml(0, DOC).unwrap();
+        let map: BTreeMap<Spanned<String>, Spanned<i64>> = from_node(&node).unwrap();
+        let found: Vec<_> = map
+            .iter()
+            .map(|(k, v)| {
+                (
+                    k.as_str(),
+                    k.span().start().copied(),
+                    **v,
+                    v.span().start().copied(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (
+                    "apple",
+                    Some(Marker::new(0, 2, 1)),
+                    2,
+                    Some(Marker::new(0, 2, 8))
+                ),
+                (
+                    "mango",
+                    Some(Marker::new(0, 3, 1)),
+                    3,
+                    Some(Marker::new(0, 3, 8))
+                ),
+                (
+                    "zebra",
+                    Some(Marker::new(0, 1, 1)),
+                    1,
+                    Some(Marker::new(0, 1, 8))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn base64_decoding() {
+        assert_eq!(decode_base64("").unwrap(), b"");
+        assert_eq!(decode_base64("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(decode_base64("aGVs\n  bG8h").unwrap(), b"hell\x6f!");
+        assert_eq!(decode_base64("aGk").unwrap(), b"hi");
+        assert_eq!(decode_base64("+/+/").unwrap(), [0xfb, 0xff, 0xbf]);
+        for bad in ["a", "aGk=a", "aG!k", "aGVsbG8==="] {
+            assert_eq!(decode_base64(bad), None, "{bad:?}");
+        }
+    }
+
+    #[test]
+    fn borrowed_bytes() {
+        #[derive(Deserialize, Debug)]
+        struct TestDoc<'a> {
+            blob: &'a [u8],
+            name: &'a str,
+        }
+        const DOC: &str = "blob: some bytes\nname: \"quoted\"\n";
+        let node = crate::parse_yaml(0, DOC).unwrap();
+        let doc: TestDoc = from_node(&node).unwrap();
+        assert_eq!(doc.blob, b"some bytes");
+        assert_eq!(doc.name, "quoted");
+        let blob = node.as_mapping().unwrap().get_scalar("blob").unwrap();
+        assert_eq!(doc.blob.as_ptr(), blob.as_str().as_ptr());
+    }
+
+    #[test]
+    fn f32_parses_natively() {
+        #[derive(Deserialize, Debug)]
+        struct TestDoc {
+            values: Vec<f32>,
+        }
+        // The last of these rounds differently if it goes via an f64
+        const TEXTS: [&str; 3] = ["0.1", "3.4028235e38", "1.00000017881393432617187499"];
+        let yaml = format!("values: [{}]\n", TEXTS.join(", "));
+        let doc: TestDoc = from_yaml(0, &yaml).unwrap();
+        for (value, text) in doc.values.iter().zip(TEXTS) {
+            assert_eq!(value.to_bits(), text.parse::<f32>().unwrap().to_bits());
+        }
+    }
+
+    #[test]
+    fn errors_clone() {
+        let node = crate::parse_yaml(0, "number: big\nflag: yes\n").unwrap();
+        let options = FromNodeOptions {
+            ambiguous_strings: AmbiguousStrings::Reject,
+            ..FromNodeOptions::default()
+        };
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct TestDoc {
+            number: u8,
+            flag: String,
+        }
+        let errors = from_node_collect_errors::<TestDoc>(&node, options)
+            .err()
+            .unwrap();
+        assert_eq!(errors.len(), 2);
+        let cloned = errors.clone();
+        for (error, clone) in errors.iter().zip(cloned.iter()) {
+            assert_eq!(error.to_string(), clone.to_string());
+            assert_eq!(error.start_mark(), clone.start_mark());
+        }
+        assert!(matches!(cloned[1], Error::Other(_, _)));
+
+        let err = from_node::<TestDoc>(&node).err().unwrap();
+        let clone = err.clone();
+        assert_eq!(err.to_string(), clone.to_string());
+        assert_eq!(err.path(), clone.path());
+    }
+
+    #[test]
+    fn errors_are_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Error>();
+        assert_send_sync::<FromNodeError>();
+        assert_send_sync::<FromYamlError>();
+    }
+
+    #[test]
+    fn spanned_display() {
+        #[derive(Deserialize)]
+        struct TestDoc {
+            name: Spanned<String>,
+            port: Spanned<u16>,
+        }
+        let node = crate::parse_yaml(0, "name: web\nport:   8080\n").unwrap();
+        let doc: TestDoc = from_node(&node).unwrap();
+        assert_eq!(format!("{}", doc.name), "web");
+        assert_eq!(format!("{:#}", doc.name), "web at 1:7");
+        assert_eq!(format!("{:>6}", doc.port), "  8080");
+        assert_eq!(format!("{:#}", doc.port), "8080 at 2:9");
+        let blank = Spanned::new(Span::new_blank(), "nowhere");
+        assert_eq!(format!("{:#}", blank), "nowhere");
+    }
+
+    #[test]
+    fn tuple_and_struct_variants() {
+        #[derive(Deserialize, Debug)]
+        #[serde(rename_all = "lowercase")]
+        enum Shape {
+            Rect(Spanned<u32>, Spanned<u32>),
+            Circle { radius: Spanned<u32> },
+        }
+        #[derive(Deserialize, Debug)]
+        struct TestDoc {
+            shapes: Vec<Shape>,
+        }
+        let node =
+            crate::parse_yaml(0, "shapes:\n- rect: [3, 4]\n- circle: {radius: 5}\n").unwrap();
+        let doc: TestDoc = from_node(&node).unwrap();
+        match &doc.shapes[0] {
+            Shape::Rect(w, h) => {
+                assert_eq!((**w, **h), (3, 4));
+                assert_eq!(w.span().start(), Some(&Marker::new(0, 2, 10)));
+                assert_eq!(h.span().start(), Some(&Marker::new(0, 2, 13)));
+            }
+            other => panic!("unexpected shape {other:?}"),
+        }
+        match &doc.shapes[1] {
+            Shape::Circle { radius } => {
+                assert_eq!(**radius, 5);
+                assert_eq!(radius.span().start(), Some(&Marker::new(0, 3, 20)));
+            }
+            other => panic!("unexpected shape {other:?}"),
+        }
+
+        for (bad, mark) in [
+            ("shapes:\n- rect: [3]\n", Marker::new(0, 2, 9)),
+            ("shapes:\n- rect: [3, 4, 5]\n", Marker::new(0, 2, 9)),
+            ("shapes:\n- circle: {}\n", Marker::new(0, 2, 11)),
+        ] {
+            let node = crate::parse_yaml(0, bad).unwrap();
+            let err = from_node::<TestDoc>(&node).err().unwrap();
+            assert_eq!(err.start_mark(), Some(mark), "{bad}");
+        }
+    }
+
+    #[test]
+    fn adversarial_input_never_panics() {
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        #[serde(untagged)]
+        enum Any {
+            Bool(bool),
+            Int(i64),
+            Str(String),
+            List(Vec<Spanned<Any>>),
+            Map(HashMap<Spanned<String>, Any>),
+        }
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Strict {
+            hello: Spanned<String>,
+            some: (Spanned<String>, u8),
+            says: HashMap<String, Option<Spanned<bool>>>,
+            numbers: [u16; 4],
+            success: Spanned<Spanned<bool>>,
+        }
+        for end in (0..TEST_DOC.len()).filter(|&i| TEST_DOC.is_char_boundary(i)) {
+            let node = match crate::parse_yaml(0, &TEST_DOC[..end]) {
+                Ok(node) => node,
+                Err(_) => continue,
+            };
+            let _ = from_node::<Any>(&node);
+            let _ = from_node::<Spanned<Any>>(&node);
+            let _ = from_node::<Strict>(&node);
+            let _ = from_node_collect_errors::<Strict>(&node, FromNodeOptions::default());
+        }
+    }
+
+    #[test]
+    fn misbehaving_visitors_get_errors() {
+        // Asks for a value after the map has run out of entries
+        struct Greedy;
+        impl<'de> Visitor<'de> for Greedy {
+            type Value = ();
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("anything")
+            }
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+                while map.next_key::<serde::de::IgnoredAny>()?.is_some() {
+                    map.next_value::<serde::de::IgnoredAny>()?;
+                }
+                map.next_value::<serde::de::IgnoredAny>()?;
+                Ok(())
+            }
+        }
+        let node = crate::parse_yaml(0, "key: value\n").unwrap();
+        assert!(NodeDeserializer::new(&node)
+            .deserialize_map(Greedy)
+            .is_err());
+        assert!(NodeDeserializer::new(&node)
+            .deserialize_struct(SPANNED_TYPE, &SPANNED_FIELDS, Greedy)
+            .is_err());
+    }
+
+    #[test]
+    fn complex_keys() {
+        let options = || LoaderOptions {
+            allow_complex_keys: true,
+            ..LoaderOptions::default()
+        };
+        const DOC: &str = "? [a, b]\n: 1\n[c, d]: 2\n";
+        let node = crate::parse_yaml_with_options(0, DOC, options()).unwrap();
+        let pairs: HashMap<(String, String), u8> = from_node(&node).unwrap();
+        assert_eq!(pairs[&("a".to_string(), "b".to_string())], 1);
+        assert_eq!(pairs[&("c".to_string(), "d".to_string())], 2);
+        let spanned: HashMap<Spanned<(String, String)>, u8> = from_node(&node).unwrap();
+        let key = spanned.keys().find(|key| key.0 == "c").unwrap();
+        assert_eq!(key.span().start(), Some(&Marker::new(0, 3, 1)));
+        // Errors in complex keys are located within them
+        let node = crate::parse_yaml_with_options(0, "[a, b]: 1\n[c, x]: 2\n", options()).unwrap();
+        let err = from_node::<HashMap<(String, u8), u8>>(&node).unwrap_err();
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 5)));
+        // Keys which aren't the right kind of collection are blamed as a whole
+        let node = crate::parse_yaml_with_options(0, "{a: b}: 1\n", options()).unwrap();
+        let err = from_node::<HashMap<(String, String), u8>>(&node).unwrap_err();
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 1)));
+    }
+
+    #[test]
+    fn sets_from_mappings() {
+        #[derive(Deserialize)]
+        struct TestDoc {
+            tagged: HashSet<Spanned<String>>,
+            plain: BTreeSet<String>,
+            listed: BTreeSet<u8>,
+        }
+        const DOC: &str =
+            "tagged: !!set\n  ? alpha\n  ? beta\nplain: !!set {x, y, x2: ~}\nlisted: [3, 1, 2, 1]\n";
+        let node = crate::parse_yaml(0, DOC).unwrap();
+        let doc: TestDoc = from_node(&node).unwrap();
+        let beta = doc.tagged.get("beta").unwrap();
+        assert_eq!(beta.span().start(), Some(&Marker::new(0, 3, 5)));
+        assert_eq!(doc.tagged.len(), 2);
+        assert_eq!(doc.plain.iter().collect::<Vec<_>>(), ["x", "x2", "y"]);
+        assert_eq!(doc.listed.iter().copied().collect::<Vec<_>>(), [1, 2, 3]);
+
+        // Untagged mappings aren't sets, not even empty ones or those whose
+        // values are all null
+        for untagged in ["{a, b}", "{}"] {
+            let yaml = format!("tagged: !!set {{}}\nplain: {untagged}\nlisted: []\n");
+            let node = crate::parse_yaml(0, yaml).unwrap();
+            let err = from_node::<TestDoc>(&node).err().unwrap();
+            assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 8)));
+            let node = node.get_or_null("plain");
+            assert!(from_node::<Vec<String>>(node).is_err());
+        }
+    }
+
+    #[test]
+    fn mixed_tuples() {
+        #[derive(Deserialize)]
+        struct TestDoc {
+            pair: (Spanned<String>, Spanned<u32>),
+        }
+        let node = crate::parse_yaml(0, "pair: [name, 42]\n").unwrap();
+        let doc: TestDoc = from_node(&node).unwrap();
+        assert_eq!(*doc.pair.0, "name");
+        assert_eq!(doc.pair.0.span().start(), Some(&Marker::new(0, 1, 8)));
+        assert_eq!(*doc.pair.1, 42);
+        assert_eq!(doc.pair.1.span().start(), Some(&Marker::new(0, 1, 14)));
+
+        for short in ["pair: [name]\n", "pair: []\n"] {
+            let node = crate::parse_yaml(0, short).unwrap();
+            let err = from_node::<TestDoc>(&node).err().unwrap();
+            assert!(err.to_string().contains("invalid length"));
+            assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 7)));
+        }
+
+        let node = crate::parse_yaml(0, "pair:\n  - name\n  - 42\n  - extra\n").unwrap();
+        let err = from_node::<TestDoc>(&node).err().unwrap();
+        assert!(err.to_string().contains("invalid length 3"));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 3)));
+    }
+
+    #[test]
+    fn spanned_seed() {
+        // A seed which counts the scalars in whatever it is given
+        struct CountScalars;
+        impl<'de> DeserializeSeed<'de> for CountScalars {
+            type Value = usize;
+            fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<usize, D::Error> {
+                struct CountVisitor;
+                impl<'de> Visitor<'de> for CountVisitor {
+                    type Value = usize;
+                    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                        f.write_str("a sequence of scalars")
+                    }
+                    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<usize, A::Error> {
+                        let mut count = 0;
+                        while seq.next_element::<serde::de::IgnoredAny>()?.is_some() {
+                            count += 1;
+                        }
+                        Ok(count)
+                    }
+                }
+                deserializer.deserialize_seq(CountVisitor)
+            }
+        }
+        let node = crate::parse_yaml(0, "items: [a, b, c]\n").unwrap();
+        let items = node.as_mapping().unwrap().get_node("items").unwrap();
+        let counted = SpannedSeed::new(CountScalars)
+            .deserialize(NodeDeserializer::new(items))
+            .unwrap();
+        assert_eq!(*counted, 3);
+        assert_eq!(
+            counted.span(),
+            &Span::new_with_marks(Marker::new(0, 1, 8), Marker::new(0, 1, 16))
+        );
+    }
+
+    #[test]
+    fn ignored_any_does_not_walk() {
+        // Counts the nodes visited, reading every child as ignored
+        #[derive(Clone, Copy)]
+        struct Count<'a>(&'a Cell<usize>);
+        impl Count<'_> {
+            fn visited(self) {
+                self.0.set(self.0.get() + 1);
+            }
+        }
+        impl<'de> DeserializeSeed<'de> for Count<'_> {
+            type Value = ();
+            fn deserialize<D: Deserializer<'de>>(self, d: D) -> Result<(), D::Error> {
+                d.deserialize_ignored_any(self)
+            }
+        }
+        impl<'de> Visitor<'de> for Count<'_> {
+            type Value = ();
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("anything")
+            }
+            fn visit_unit<E>(self) -> Result<(), E> {
+                self.visited();
+                Ok(())
+            }
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+                self.visited();
+                while map.next_key_seed(self)?.is_some() {
+                    map.next_value_seed(self)?;
+                }
+                Ok(())
+            }
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+                self.visited();
+                while seq.next_element_seed(self)?.is_some() {}
+                Ok(())
+            }
+        }
+        let node = crate::parse_yaml(0, "big: [[1, 2], {deep: [3]}]\nsmall: 4\n").unwrap();
+        // Walking a node visits it and its children, but never its grandchildren
+        for (path, expected) in [("", 5), ("/big", 3), ("/big/1", 3)] {
+            let count = Cell::new(0);
+            NodeDeserializer::new(node.pointer(path).unwrap())
+                .deserialize_any(Count(&count))
+                .unwrap();
+            assert_eq!(count.get(), expected, "walking {path:?}");
+        }
+        for path in ["", "/big", "/big/1", "/small"] {
+            let count = Cell::new(0);
+            Count(&count)
+                .deserialize(NodeDeserializer::new(node.pointer(path).unwrap()))
+                .unwrap();
+            assert_eq!(count.get(), 1, "ignoring {path:?}");
+        }
+
+        #[derive(Deserialize)]
+        struct TestDoc {
+            small: u8,
+        }
+        let doc: TestDoc = from_node(&node).unwrap();
+        assert_eq!(doc.small, 4);
+    }
+
+    #[test]
+    fn optional_spans() {
+        #[derive(Deserialize)]
+        struct Outer {
+            #[serde(default)]
+            outer: Option<Spanned<String>>,
+        }
+        #[derive(Deserialize)]
+        struct Inner {
+            inner: Spanned<Option<String>>,
+        }
+
+        let present = crate::parse_yaml(0, "outer: hi\ninner: hi\n").unwrap();
+        let null = crate::parse_yaml(0, "outer: null\ninner: ~\n").unwrap();
+        let quoted = crate::parse_yaml(0, "outer: \"~\"\ninner: \"null\"\n").unwrap();
+        let absent = crate::parse_yaml(0, "other: hi\n").unwrap();
+
+        let doc: Outer = from_node(&present).unwrap();
+        let outer = doc.outer.unwrap();
+        assert_eq!(*outer, "hi");
+        assert_eq!(outer.span().start(), Some(&Marker::new(0, 1, 8)));
+        let doc: Outer = from_node(&null).unwrap();
+        assert!(doc.outer.is_none());
+        let doc: Outer = from_node(&quoted).unwrap();
+        assert_eq!(*doc.outer.unwrap(), "~");
+        let doc: Outer = from_node(&absent).unwrap();
+        assert!(doc.outer.is_none());
+
+        let doc: Inner = from_node(&present).unwrap();
+        assert_eq!(doc.inner.as_deref(), Some("hi"));
+        assert_eq!(doc.inner.span().start(), Some(&Marker::new(0, 2, 8)));
+        let doc: Inner = from_node(&null).unwrap();
+        assert!(doc.inner.is_none());
+        assert_eq!(doc.inner.span().start(), Some(&Marker::new(0, 2, 8)));
+        let doc: Inner = from_node(&quoted).unwrap();
+        assert_eq!(doc.inner.as_deref(), Some("null"));
+        assert!(from_node::<Inner>(&absent).is_err());
+    }
+
+    #[test]
+    fn spanned_newtypes() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Name(String);
+        #[derive(Deserialize, Debug)]
+        struct Wrapped(Spanned<String>);
+        #[derive(Deserialize, Debug)]
+        struct Numbers(Vec<u16>);
+        #[derive(Deserialize, Debug)]
+        struct TestDoc {
+            hello: Spanned<Name>,
+            some: Vec<Wrapped>,
+            numbers: Spanned<Numbers>,
+        }
+        let node = crate::parse_yaml(0, TEST_DOC).unwrap();
+        let doc: TestDoc = from_node(&node).unwrap();
+        assert_eq!(*doc.hello, Name("world".to_string()));
+        assert_eq!(doc.hello.span().start(), Some(&Marker::new(0, 1, 8)));
+        assert_eq!(doc.some[0].0.as_str(), "value");
+        assert_eq!(doc.some[0].0.span().start(), Some(&Marker::new(0, 2, 9)));
+        assert_eq!(doc.numbers.0, vec![1, 2, 3, 500]);
+        assert_eq!(doc.numbers.span().start(), Some(&Marker::new(0, 4, 10)));
+    }
+
+    #[test]
+    fn spanned_units() {
+        #[derive(Deserialize, Debug)]
+        struct TestDoc {
+            flag: Spanned<()>,
+            #[serde(default)]
+            other: Option<Spanned<()>>,
+        }
+        let node = crate::parse_yaml(0, "name: example\nflag:\n").unwrap();
+        let doc: TestDoc = from_node(&node).unwrap();
+        // An empty value is placed wherever the parser found it to be
+        assert!(doc.flag.span().start().is_some());
+        assert!(doc.other.is_none());
+
+        let node = crate::parse_yaml(0, "flag: ~\nother: null\n").unwrap();
+        let doc: TestDoc = from_node(&node).unwrap();
+        assert_eq!(doc.flag.span().start(), Some(&Marker::new(0, 1, 7)));
+        assert!(doc.other.is_none());
+
+        // Only nulls are units, anything else is an error at the value
+        let node = crate::parse_yaml(0, "flag: yes\n").unwrap();
+        let err = from_node::<TestDoc>(&node).err().unwrap();
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 7)));
+    }
+
+    #[test]
+    fn unknown_name_suggestions() {
+        #[derive(Deserialize, Debug)]
+        #[serde(deny_unknown_fields)]
+        #[allow(dead_code)]
+        struct Config {
+            timeout: Option<u32>,
+            retries: Option<u32>,
+            mode: Option<Mode>,
+        }
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        enum Mode {
+            Fast,
+            Thorough,
+        }
+        let node = crate::parse_yaml(0, "timeut: 5\n").unwrap();
+        let err = from_node::<Config>(&node).err().unwrap();
+        assert_eq!(err.suggestion(), Some("timeout"));
+        assert_eq!(
+            (*err).to_string(),
+            "Unknown field `timeut`, expected one of `timeout`, `retries`, or `mode` (did you mean `timeout`?)"
+        );
+        let node = crate::parse_yaml(0, "colour: red\n").unwrap();
+        let err = from_node::<Config>(&node).err().unwrap();
+        assert_eq!(err.suggestion(), None);
+        assert!(!err.to_string().contains("did you mean"));
+        let node = crate::parse_yaml(0, "mode: Thorugh\n").unwrap();
+        let err = from_node::<Config>(&node).err().unwrap();
+        assert_eq!(err.suggestion(), Some("Thorough"));
+        assert!(err.to_string().ends_with("(did you mean `Thorough`?)"));
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(edit_distance("same", "same"), 0);
+    }
+
+    #[test]
+    fn spanned_trees() {
+        let node = crate::parse_yaml(0, TEST_DOC).unwrap();
+        let tree = to_spanned_tree(&node);
+        assert_eq!(tree.span().start(), Some(&Marker::new(0, 1, 6)));
+        let hello = tree.get("hello").unwrap();
+        assert_eq!(hello.as_str(), Some("world"));
+        assert_eq!(hello.span().start(), Some(&Marker::new(0, 1, 8)));
+        let key = tree.as_mapping().unwrap().keys().nth(1).unwrap();
+        assert_eq!(key.as_str(), "some");
+        assert_eq!(key.span().start(), Some(&Marker::new(0, 2, 1)));
+        let other = tree.get("some").and_then(|v| v.get_index(2)).unwrap();
+        assert_eq!(other.as_str(), Some("other"));
+        assert_eq!(other.span().start(), Some(&Marker::new(0, 2, 20)));
+        let grow = tree.get("says").and_then(|v| v.get("grow")).unwrap();
+        assert_eq!(grow.span().start(), Some(&Marker::new(0, 3, 15)));
+        let numbers = tree.get("numbers").unwrap();
+        assert_eq!(numbers.as_sequence().map(<[_]>::len), Some(4));
+        assert_eq!(
+            numbers.get_index(3).and_then(SpannedValue::as_u64),
+            Some(500)
+        );
+        assert_eq!(
+            tree.get("success").and_then(SpannedValue::as_bool),
+            Some(true)
+        );
+        assert_eq!(
+            tree.get("failure").and_then(SpannedValue::as_bool),
+            Some(false)
+        );
+        assert!(tree.get("missing").is_none());
+        assert!(hello.get("anything").is_none());
+
+        let node = crate::parse_yaml(0, "a: ~\nb: '~'\nc: 1.5\n").unwrap();
+        let tree = to_spanned_tree(&node);
+        assert!(tree.get("a").unwrap().is_null());
+        assert_eq!(
+            tree.get("a").unwrap().span().start(),
+            Some(&Marker::new(0, 1, 4))
+        );
+        assert_eq!(tree.get("b").and_then(SpannedValue::as_str), Some("~"));
+        assert_eq!(tree.get("c").and_then(SpannedValue::as_f64), Some(1.5));
+    }
+
+    #[test]
+    fn spanned_transparent() {
+        #[derive(Deserialize, Debug)]
+        #[serde(transparent)]
+        struct Wrapper(Spanned<String>);
+        #[derive(Deserialize, Debug)]
+        #[serde(transparent)]
+        struct Named {
+            name: Spanned<String>,
+        }
+        #[derive(Deserialize, Debug)]
+        struct TestDoc {
+            hello: Wrapper,
+            some: Vec<Named>,
+            says: Spanned<HashMap<String, Wrapper>>,
+        }
+        let node = crate::parse_yaml(0, TEST_DOC).unwrap();
+        let doc: TestDoc = from_node(&node).unwrap();
+        assert_eq!(doc.hello.0.as_str(), "world");
+        assert_eq!(doc.hello.0.span().start(), Some(&Marker::new(0, 1, 8)));
+        assert_eq!(doc.some[2].name.as_str(), "other");
+        assert_eq!(
+            doc.some[2].name.span().start(),
+            Some(&Marker::new(0, 2, 20))
+        );
+        assert_eq!(
+            doc.says["grow"].0.span().start(),
+            Some(&Marker::new(0, 3, 15))
+        );
+        assert_eq!(doc.says.span().start(), Some(&Marker::new(0, 3, 7)));
+    }
+
+    #[test]
+    fn non_mapping_roots() {
+        let options = || LoaderOptions {
+            allow_non_mapping_top_level: true,
+            ..LoaderOptions::default()
+        };
+        let node = crate::parse_yaml_with_options(0, "42\n", options()).unwrap();
+        let value: Spanned<i64> = from_node(&node).unwrap();
+        assert_eq!(*value, 42);
+        assert_eq!(value.span().start(), Some(&Marker::new(0, 1, 1)));
+        let value: u32 = from_yaml_with_options(0, "42\n", options()).unwrap();
+        assert_eq!(value, 42);
+        let node = crate::parse_yaml_with_options(0, "- one\n- two\n", options()).unwrap();
+        let value: Vec<Spanned<String>> = from_node(&node).unwrap();
+        assert_eq!(value, vec!["one".to_string(), "two".to_string()]);
+        assert_eq!(value[1].span().start(), Some(&Marker::new(0, 2, 3)));
+    }
+
+    #[test]
+    fn fallback_spans() {
+        struct Grumpy;
+        impl<'de> Deserialize<'de> for Grumpy {
+            fn deserialize<D: serde::Deserializer<'de>>(_: D) -> Result<Self, D::Error> {
+                Err(serde::de::Error::custom("never happy"))
+            }
+        }
+        let root = Span::new_start(Marker::new(3, 1, 1));
+        let node = Node::from(vec![Node::from("one"), Node::from("two")]);
+        let err = from_node::<Vec<Grumpy>>(&node).err().unwrap();
+        assert!(err.span().is_blank());
+        let err = from_node_with_fallback_span::<Vec<Grumpy>>(&node, root)
+            .err()
+            .unwrap();
+        assert!(err.to_string().ends_with("never happy"));
+        assert_eq!(err.span(), &root);
+
+        let node = crate::parse_yaml(0, "value: 1\n").unwrap();
+        let err = from_node_with_fallback_span::<HashMap<String, Grumpy>>(&node, root)
+            .err()
+            .unwrap();
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 8)));
+
+        // Errors from the root type itself aren't claimed by the root node
+        let err = from_node::<Grumpy>(&node).err().unwrap();
+        assert!(err.span().is_blank());
+        let err = from_node_with_fallback_span::<Grumpy>(&node, root)
+            .err()
+            .unwrap();
+        assert_eq!(err.span(), &root);
+        #[derive(Deserialize, Debug)]
+        #[serde(untagged)]
+        #[allow(dead_code)]
+        enum Picky {
+            Number(u8),
+        }
+        let err = from_node_with_fallback_span::<Picky>(&node, root)
+            .err()
+            .unwrap();
+        assert_eq!(err.span(), &root);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn from_files() {
+        #[derive(Deserialize, Debug)]
+        struct TestDoc {
+            name: Spanned<String>,
+            port: u16,
+        }
+        let dir = std::env::temp_dir().join(format!("marked-yaml-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let good = dir.join("good.yaml");
+        let bad = dir.join("bad.yaml");
+        std::fs::write(&good, "name: web\nport: 80\n").unwrap();
+        std::fs::write(&bad, "name: web\nport: lots\n").unwrap();
+
+        let (doc, registry) = from_file::<TestDoc, _>(&good).unwrap();
+        assert_eq!(doc.port, 80);
+        let start = *doc.name.span().start().unwrap();
+        assert_eq!(
+            registry.render(start).to_string(),
+            format!("{}:1:7", good.display())
+        );
+
+        let err = from_file::<TestDoc, _>(&bad).err().unwrap();
+        assert!(matches!(err, crate::LoadError::Deserialize(_)));
+        assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 7)));
+
+        let missing = dir.join("missing.yaml");
+        let err = from_file::<TestDoc, _>(&missing).err().unwrap();
+        assert!(matches!(&err, crate::LoadError::Io(path, _) if path == &missing));
+        assert_eq!(err.start_mark(), None);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn flatten_spanned() {
+        let node = crate::parse_yaml(0, "value: 12\n").unwrap();
+        let value: HashMap<String, Spanned<Spanned<u8>>> = from_node(&node).unwrap();
+        let value = value.into_iter().next().unwrap().1;
+        assert_eq!(value.span(), value.inner.span());
+        let flat = value.flatten();
+        assert_eq!(*flat, 12);
+        assert_eq!(flat.span().start(), Some(&Marker::new(0, 1, 8)));
+
+        let inner = Spanned::new(Span::new_start(Marker::new(0, 4, 2)), "x");
+        let flat = Spanned::new(Span::new_blank(), inner).flatten();
+        assert_eq!(flat.span().start(), Some(&Marker::new(0, 4, 2)));
+        let inner = Spanned::new(Span::new_start(Marker::new(0, 4, 2)), "x");
+        let flat = Spanned::new(Span::new_start(Marker::new(0, 3, 1)), inner).flatten();
+        assert_eq!(flat.span().start(), Some(&Marker::new(0, 3, 1)));
+    }
 }
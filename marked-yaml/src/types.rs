@@ -4,7 +4,8 @@
 use doc_comment::doc_comment;
 use linked_hash_map::LinkedHashMap;
 use std::borrow::{Borrow, Cow};
-use std::convert::TryFrom;
+use std::cmp::Ordering;
+use std::convert::{Infallible, TryFrom};
 use std::fmt::{self, Display};
 use std::hash::{Hash, Hasher};
 use std::iter::FromIterator;
@@ -58,13 +59,31 @@ where
 /// // the "bar" string started on line 1, column 7 of source ID 100.
 /// assert_eq!(bar.span().start(), Some(&Marker::new(100, 1, 7)));
 /// ```
+///
+/// With the `serde` feature enabled, markers serialize as a structure with
+/// `source`, `line`, and `column` fields.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Marker {
     source: usize,
     line: usize,
     column: usize,
 }
 
+/// The default marker is the very start of source 0
+///
+/// Lines and columns are 1-indexed, so this is line 1, column 1.
+///
+/// ```
+/// # use marked_yaml::Marker;
+/// assert_eq!(Marker::default(), Marker::new(0, 1, 1));
+/// ```
+impl Default for Marker {
+    fn default() -> Self {
+        Self::new(0, 1, 1)
+    }
+}
+
 impl Marker {
     /// Create a new Marker
     ///
@@ -126,6 +145,12 @@ impl Marker {
     /// This is the column number of where this marker resides.  Column numbers
     /// start with 1 to make them more useful to humans.
     ///
+    /// Columns count Unicode scalar values (Rust `char`s), not bytes and not
+    /// grapheme clusters, so `é` written as a single code point is one column
+    /// wide, but written as `e` plus a combining accent it is two.  If you
+    /// need UTF-16 positions, for example for the language server protocol,
+    /// see [`column_utf16()`][Marker::column_utf16].
+    ///
     /// ```
     /// # use marked_yaml::Marker;
     /// # let marker = Marker::new(0, 1, 2);
@@ -135,6 +160,31 @@ impl Marker {
         self.column
     }
 
+    /// The column of this marker in UTF-16 code units, 0-indexed
+    ///
+    /// Editor protocols such as LSP describe positions as a count of UTF-16
+    /// code units from the start of the line, starting at zero.  Given the
+    /// text of the line this marker is on (see [`LineIndex::line_text()`])
+    /// this converts the marker's column into that form.  Characters outside
+    /// the basic multilingual plane, such as most emoji, count as two units.
+    /// Columns beyond the end of the line are clamped to the line's length.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "🦀: crab").unwrap();
+    /// let crab = node.as_mapping().unwrap().get_node("🦀").unwrap();
+    /// let marker = crab.span().start().unwrap();
+    /// assert_eq!(marker.column(), 4);
+    /// assert_eq!(marker.column_utf16("🦀: crab"), 4);
+    /// ```
+    pub fn column_utf16(&self, line_text: &str) -> usize {
+        line_text
+            .chars()
+            .take(self.column.saturating_sub(1))
+            .map(char::len_utf16)
+            .sum()
+    }
+
     /// Render this marker
     ///
     /// Markers have a source identifier, typically as passed to `parse_yaml()`
@@ -198,6 +248,29 @@ impl Marker {
     pub fn set_column(&mut self, column: usize) {
         self.column = column;
     }
+
+    /// A marker the given number of columns further along the same line
+    ///
+    /// This is useful for working out where a token ends when only its
+    /// start is known, for example to build a span covering a plain scalar
+    /// from its start marker and its length in `char`s.  No attempt is made
+    /// to account for newlines, so this is only meaningful when the columns
+    /// advanced over are all on the one line.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "key: value\n").unwrap();
+    /// let value = node.as_mapping().unwrap().get_scalar("key").unwrap();
+    /// let start = *value.span().start().unwrap();
+    /// let end = start.advance(value.as_str().chars().count());
+    /// assert_eq!(end, Marker::new(0, 1, 11));
+    /// ```
+    pub fn advance(&self, columns: usize) -> Marker {
+        Marker {
+            column: self.column + columns,
+            ..*self
+        }
+    }
 }
 
 impl Display for Marker {
@@ -206,6 +279,183 @@ impl Display for Marker {
     }
 }
 
+/// A registry of human readable names for source identifiers
+///
+/// Markers only carry a numeric source identifier.  If you don't already
+/// have somewhere to keep track of what those mean, a registry can
+/// allocate identifiers for you and then render markers with the right
+/// names.
+///
+/// ```
+/// # use marked_yaml::types::*;
+/// let mut registry = SourceRegistry::new();
+/// let source = registry.add("config.yaml");
+/// assert_eq!(registry.name(source), Some("config.yaml"));
+/// let marker = Marker::new(source, 3, 7);
+/// assert_eq!(format!("{}", registry.render(marker)), "config.yaml:3:7");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SourceRegistry {
+    names: Vec<String>,
+}
+
+impl SourceRegistry {
+    /// Create a new empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a name, returning the source identifier allocated for it
+    pub fn add(&mut self, name: impl Into<String>) -> usize {
+        self.names.push(name.into());
+        self.names.len() - 1
+    }
+
+    /// The name registered for the given source identifier, if there is one
+    pub fn name(&self, source: usize) -> Option<&str> {
+        self.names.get(source).map(String::as_str)
+    }
+
+    /// Render a marker using the registered name for its source
+    ///
+    /// Sources which aren't in the registry are rendered as `<unknown>`.
+    pub fn render(&self, marker: Marker) -> RenderedMarker<&str> {
+        marker.render(|source| self.name(source).unwrap_or("<unknown>"))
+    }
+}
+
+/// An index of where lines start in some source text
+///
+/// The parser gives you markers for the nodes it builds, but editors and
+/// other tooling often have byte offsets which they need to turn into
+/// markers, or vice versa.  A line index is built once from the source text
+/// and then converts between the two.  Columns are counted in characters,
+/// exactly as the parser counts them.
+///
+/// ```
+/// # use marked_yaml::types::*;
+/// let text = "name: café\nnext: 1\n";
+/// let index = LineIndex::new(0, text);
+/// let marker = index.offset_to_marker(text.find("next").unwrap());
+/// assert_eq!(marker, Marker::new(0, 2, 1));
+/// assert_eq!(index.marker_to_offset(&Marker::new(0, 1, 10)), Some(text.find('é').unwrap()));
+/// ```
+#[derive(Clone, Debug)]
+pub struct LineIndex<'a> {
+    source: usize,
+    text: &'a str,
+    line_starts: Cow<'a, [usize]>,
+}
+
+impl<'a> LineIndex<'a> {
+    /// Build a line index over the given text
+    ///
+    /// The source identifier is used for the markers produced by
+    /// [`offset_to_marker()`][LineIndex::offset_to_marker] and should match
+    /// the one given to the parser for the same text.
+    pub fn new(source: usize, text: &'a str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect::<Vec<_>>();
+        Self {
+            source,
+            text,
+            line_starts: Cow::Owned(line_starts),
+        }
+    }
+
+    // Reuse line starts which were computed for this text earlier, so that
+    // whatever owns the text can lend out an index without rebuilding it
+    pub(crate) fn with_line_starts(source: usize, text: &'a str, line_starts: &'a [usize]) -> Self {
+        Self {
+            source,
+            text,
+            line_starts: Cow::Borrowed(line_starts),
+        }
+    }
+
+    pub(crate) fn into_line_starts(self) -> Vec<usize> {
+        self.line_starts.into_owned()
+    }
+
+    /// Convert a byte offset into the text into a marker
+    ///
+    /// Offsets past the end of the text are treated as the end of the text,
+    /// and offsets inside a multi-byte character are treated as the start
+    /// of that character.
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// let index = LineIndex::new(3, "ä: ö\n");
+    /// assert_eq!(index.offset_to_marker(4), Marker::new(3, 1, 4));
+    /// ```
+    pub fn offset_to_marker(&self, offset: usize) -> Marker {
+        let mut offset = offset.min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        let start = self.line_starts[line - 1];
+        let column = self.text[start..offset].chars().count() + 1;
+        Marker::new(self.source, line, column)
+    }
+
+    /// Convert a marker into a byte offset into the text
+    ///
+    /// This returns `None` if the marker is for a different source, or if it
+    /// refers to a line or column which doesn't exist in the text.  The
+    /// column just past the end of a line is permitted.
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// let index = LineIndex::new(0, "ä: ö\n");
+    /// assert_eq!(index.marker_to_offset(&Marker::new(0, 1, 4)), Some(4));
+    /// assert_eq!(index.marker_to_offset(&Marker::new(0, 1, 6)), None);
+    /// assert_eq!(index.marker_to_offset(&Marker::new(1, 1, 4)), None);
+    /// ```
+    pub fn marker_to_offset(&self, marker: &Marker) -> Option<usize> {
+        if marker.source() != self.source || marker.column() == 0 {
+            return None;
+        }
+        let start = *self.line_starts.get(marker.line().checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(marker.line())
+            .map(|&next| next - 1)
+            .unwrap_or(self.text.len());
+        self.text[start..end]
+            .char_indices()
+            .map(|(i, _)| start + i)
+            .chain(std::iter::once(end))
+            .nth(marker.column() - 1)
+    }
+
+    /// The text of the given line, 1-indexed, without its line ending
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// let index = LineIndex::new(0, "first\r\nsecond\n");
+    /// assert_eq!(index.line_text(1), Some("first"));
+    /// assert_eq!(index.line_text(2), Some("second"));
+    /// assert_eq!(index.line_text(4), None);
+    /// ```
+    pub fn line_text(&self, line: usize) -> Option<&'a str> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self
+            .line_starts
+            .get(line)
+            .map(|&next| next - 1)
+            .unwrap_or(self.text.len());
+        let text = &self.text[start..end];
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+
+    /// The text this index was built from
+    pub fn text(&self) -> &'a str {
+        self.text
+    }
+}
+
 /// The span for a YAML marked node
 ///
 /// ```
@@ -214,12 +464,31 @@ impl Display for Marker {
 /// let map = node.as_mapping().unwrap();
 /// assert_eq!(map.span(), &Span::new_with_marks(Marker::new(100, 1, 1), Marker::new(100, 1, 10)));
 /// ```
+///
+/// With the `serde` feature enabled, spans serialize as a structure with
+/// `start` and `end` fields, each of which is either a [`Marker`] or null.
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Span {
     start: Option<Marker>,
     end: Option<Marker>,
 }
 
+/// The default span is blank, as from [`Span::new_blank()`]
+///
+/// This is the span most often wanted for nodes which are built by hand
+/// rather than parsed.
+///
+/// ```
+/// # use marked_yaml::Span;
+/// assert!(Span::default().is_blank());
+/// ```
+impl Default for Span {
+    fn default() -> Self {
+        Self::new_blank()
+    }
+}
+
 impl Span {
     /// Create a span with no marker information
     ///
@@ -276,6 +545,30 @@ impl Span {
         }
     }
 
+    fn remap_sources<F>(&mut self, f: &F)
+    where
+        F: Fn(usize) -> usize,
+    {
+        for marker in [&mut self.start, &mut self.end].into_iter().flatten() {
+            marker.set_source(f(marker.source()));
+        }
+    }
+
+    /// Whether this span carries no location information at all
+    ///
+    /// Blank spans are used when we do not know where something came from,
+    /// so renderers can use this to decide not to show a location.  A span
+    /// with either a start or an end marker is not blank.
+    ///
+    /// ```
+    /// # use marked_yaml::{Marker, Span};
+    /// assert!(Span::new_blank().is_blank());
+    /// assert!(!Span::new_start(Marker::new(0, 1, 1)).is_blank());
+    /// ```
+    pub fn is_blank(&self) -> bool {
+        self.start.is_none() && self.end.is_none()
+    }
+
     /// The start of the span
     ///
     /// ```
@@ -347,6 +640,182 @@ impl Span {
     pub fn set_end(&mut self, end: Option<Marker>) {
         self.end = end;
     }
+
+    /// A copy of this span with the end marker dropped
+    ///
+    /// Some renderers only ever want to point at a single location, even
+    /// when the span knows where a node finished.
+    ///
+    /// ```
+    /// # use marked_yaml::{Marker, Span};
+    /// let span = Span::new_with_marks(Marker::new(0, 1, 1), Marker::new(0, 2, 1));
+    /// assert_eq!(span.start_only(), Span::new_start(Marker::new(0, 1, 1)));
+    /// assert_eq!(Span::new_blank().start_only(), Span::new_blank());
+    /// ```
+    pub fn start_only(&self) -> Span {
+        Span {
+            start: self.start,
+            end: None,
+        }
+    }
+
+    // This span, but ending where it starts if its end isn't known
+    fn extent(&self) -> Span {
+        Span {
+            start: self.start,
+            end: self.end.or(self.start),
+        }
+    }
+
+    /// The smallest span covering both this span and another
+    ///
+    /// The result starts at the earlier of the two start markers and ends at
+    /// the later of the two end markers.  If neither span knows where it
+    /// starts (or ends) then neither will the result.
+    ///
+    /// ```
+    /// # use marked_yaml::{Marker, Span};
+    /// let key = Span::new_start(Marker::new(0, 1, 1));
+    /// let value = Span::new_with_marks(Marker::new(0, 1, 6), Marker::new(0, 3, 1));
+    /// assert_eq!(
+    ///     key.union(&value),
+    ///     Span::new_with_marks(Marker::new(0, 1, 1), Marker::new(0, 3, 1))
+    /// );
+    /// ```
+    pub fn union(&self, other: &Span) -> Span {
+        fn pick(a: Option<Marker>, b: Option<Marker>, later: bool) -> Option<Marker> {
+            match (a, b) {
+                (Some(a), Some(b)) => {
+                    let a_first = (a.source, a.line, a.column) <= (b.source, b.line, b.column);
+                    Some(if a_first != later { a } else { b })
+                }
+                (a, b) => a.or(b),
+            }
+        }
+        Span {
+            start: pick(self.start, other.start, false),
+            end: pick(self.end, other.end, true),
+        }
+    }
+
+    /// Compare where two spans start
+    ///
+    /// Start markers are ordered by source, then line, then column.  Spans
+    /// which don't know where they start sort after all those which do.
+    ///
+    /// ```
+    /// # use marked_yaml::{Marker, Span};
+    /// # use std::cmp::Ordering;
+    /// let first = Span::new_start(Marker::new(0, 1, 9));
+    /// let second = Span::new_start(Marker::new(0, 2, 1));
+    /// assert_eq!(first.cmp_start(&second), Ordering::Less);
+    /// assert_eq!(Span::new_blank().cmp_start(&second), Ordering::Greater);
+    /// ```
+    pub fn cmp_start(&self, other: &Span) -> Ordering {
+        match (self.start, other.start) {
+            (Some(a), Some(b)) => (a.source, a.line, a.column).cmp(&(b.source, b.line, b.column)),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+}
+
+/// Sort nodes by where they start in their source
+///
+/// This is useful for reporting on collections of nodes in the order in
+/// which they appear.  See [`Span::cmp_start`] for how spans are ordered.
+/// The sort is stable, so nodes which start at the same place (or nowhere
+/// in particular) keep their relative order.
+///
+/// ```
+/// # use marked_yaml::*;
+/// # use marked_yaml::types::sort_by_span;
+/// let node = parse_yaml(0, "a: 1\nb: 2\n").unwrap();
+/// let map = node.as_mapping().unwrap();
+/// let mut nodes = vec![map.get_node("b").unwrap(), map.get_node("a").unwrap()];
+/// sort_by_span(&mut nodes);
+/// assert_eq!(nodes[0].as_scalar().unwrap().as_str(), "1");
+/// ```
+pub fn sort_by_span(nodes: &mut [&Node]) {
+    nodes.sort_by(|a, b| a.span().cmp_start(b.span()));
+}
+
+/// A warning about something in the input which is legal but suspicious
+///
+/// Warnings never stop a document from being processed, they merely point
+/// at places which a human may wish to look at.
+///
+/// ```
+/// # use marked_yaml::types::*;
+/// let warning = Warning::new(Span::new_start(Marker::new(0, 2, 5)), WarningKind::AmbiguousString);
+/// assert_eq!(warning.kind(), WarningKind::AmbiguousString);
+/// assert_eq!(
+///     format!("{warning}"),
+///     "2:5: Plain scalar might not be a string, quote it if a string is intended"
+/// );
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Warning {
+    span: Span,
+    kind: WarningKind,
+}
+
+/// The kinds of [`Warning`] which can be raised
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum WarningKind {
+    /// A plain scalar was used as a string, but YAML may resolve it to
+    /// something else, such as `NO` being read as a boolean
+    AmbiguousString,
+    /// A key appeared more than once in a mapping, and the later value
+    /// replaced the earlier one
+    DuplicateKey,
+    /// A plain scalar is a boolean in YAML 1.1 but a string in YAML 1.2,
+    /// such as `no` or `On`
+    AmbiguousBoolean,
+}
+
+impl Warning {
+    /// Create a new warning of the given kind
+    pub fn new(span: Span, kind: WarningKind) -> Self {
+        Self { span, kind }
+    }
+
+    /// The span of the node which caused the warning
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    /// What kind of warning this is
+    pub fn kind(&self) -> WarningKind {
+        self.kind
+    }
+}
+
+impl Display for WarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WarningKind::AmbiguousString => {
+                f.write_str("Plain scalar might not be a string, quote it if a string is intended")
+            }
+            WarningKind::DuplicateKey => {
+                f.write_str("Duplicate key in mapping, the earlier value is ignored")
+            }
+            WarningKind::AmbiguousBoolean => f.write_str(
+                "Plain scalar is a boolean in YAML 1.1, quote it if a string is intended",
+            ),
+        }
+    }
+}
+
+impl Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.span.start() {
+            Some(mark) => write!(f, "{}: {}", mark, self.kind),
+            None => write!(f, "{}", self.kind),
+        }
+    }
 }
 
 /// A marked YAML node
@@ -359,6 +828,22 @@ impl Span {
 /// let node = parse_yaml(100, "{foo: bar}").unwrap();
 /// assert!(node.as_mapping().is_some());
 /// ```
+///
+/// Nodes own everything within them and borrow nothing from the text they
+/// were parsed from, so cloning a node found within a larger tree gives an
+/// independent document which can be kept, cached, or processed on its
+/// own.  The clone keeps its spans, which still refer to the original
+/// source identifier and to lines and columns within the original text.
+/// See [`Node::remap_sources`] if the source identifier needs to change.
+///
+/// ```
+/// # use marked_yaml::*;
+/// let node = parse_yaml(0, "outer:\n  inner: value\n").unwrap();
+/// let outer = node.as_mapping().unwrap().get_node("outer").unwrap().clone();
+/// drop(node);
+/// let inner = outer.as_mapping().unwrap().get_scalar("inner").unwrap();
+/// assert_eq!(inner.span().start(), Some(&Marker::new(0, 2, 10)));
+/// ```
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub enum Node {
     /// A YAML scalar
@@ -378,6 +863,41 @@ pub enum Node {
     Sequence(MarkedSequenceNode),
 }
 
+/// The style in which a scalar was written
+///
+/// Scalars created in code are plain unless told otherwise.  The style of
+/// a scalar plays no part in equality, but the emitter can be asked to
+/// respect it (see [`EmitOptions::scalar_styles`][crate::EmitOptions::scalar_styles]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ScalarStyle {
+    /// An unquoted scalar, such as `value`
+    #[default]
+    Plain,
+    /// A single quoted scalar, such as `'value'`
+    SingleQuoted,
+    /// A double quoted scalar, such as `"value"`
+    DoubleQuoted,
+    /// A literal block scalar, introduced by `|`
+    Literal,
+    /// A folded block scalar, introduced by `>`
+    Folded,
+}
+
+/// How the final line breaks of a block scalar are treated
+///
+/// This is given by the chomping indicator in a block scalar's header,
+/// such as the `-` in `|-`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Chomping {
+    /// Final line breaks are removed, indicated by `-`
+    Strip,
+    /// A single final line break is kept, the default with no indicator
+    #[default]
+    Clip,
+    /// All final line breaks are kept, indicated by `+`
+    Keep,
+}
+
 /// A marked scalar YAML node
 ///
 /// Scalar nodes are treated by this crate as strings, though a few special
@@ -400,10 +920,35 @@ pub enum Node {
 pub struct MarkedScalarNode {
     span: Span,
     value: String,
+    may_coerce: bool,
+    style: ScalarStyle,
+    chomping: Chomping,
+    indent: Option<usize>,
+    binary: bool,
+    anchor: Option<String>,
+    alias_of: Option<String>,
+    complex_key: Option<Box<Node>>,
 }
 
 pub(crate) type MappingHash = LinkedHashMap<MarkedScalarNode, Node>;
 
+// The null node which `Node::get_or_null()` gives for missing keys
+static NULL_NODE: Node = Node::Scalar(MarkedScalarNode {
+    span: Span {
+        start: None,
+        end: None,
+    },
+    value: String::new(),
+    may_coerce: true,
+    style: ScalarStyle::Plain,
+    chomping: Chomping::Clip,
+    indent: None,
+    binary: false,
+    anchor: None,
+    alias_of: None,
+    complex_key: None,
+});
+
 /// A marked YAML mapping node
 ///
 /// Mapping nodes in YAML are defined as a key/value mapping where the keys are
@@ -425,6 +970,10 @@ pub(crate) type MappingHash = LinkedHashMap<MarkedScalarNode, Node>;
 pub struct MarkedMappingNode {
     span: Span,
     value: MappingHash,
+    flow: bool,
+    set: bool,
+    anchor: Option<String>,
+    alias_of: Option<String>,
 }
 
 /// A marked YAML sequence node
@@ -446,6 +995,9 @@ pub struct MarkedMappingNode {
 pub struct MarkedSequenceNode {
     span: Span,
     value: Vec<Node>,
+    flow: bool,
+    anchor: Option<String>,
+    alias_of: Option<String>,
 }
 
 macro_rules! basic_traits {
@@ -475,6 +1027,55 @@ basic_traits!(MarkedScalarNode);
 basic_traits!(MarkedSequenceNode);
 basic_traits!(MarkedMappingNode);
 
+macro_rules! anchor_methods {
+    ($t:path) => {
+        impl $t {
+            /// The name of the anchor defined on this node, if it has one
+            ///
+            /// Anchors are only permitted when loading with
+            /// [`LoaderOptions::allow_aliases`][crate::LoaderOptions::allow_aliases]
+            /// set.  See [`Node::anchors()`] to find every anchor in a tree.
+            pub fn anchor(&self) -> Option<&str> {
+                self.anchor.as_deref()
+            }
+
+            /// Set the name of the anchor defined on this node
+            pub fn set_anchor(&mut self, anchor: Option<String>) {
+                self.anchor = anchor;
+            }
+
+            /// The name of the anchor which this node is an alias of, if it
+            /// is one
+            ///
+            /// Aliases are loaded as copies of the node which their anchor
+            /// is defined on, so this is how to tell that they were aliases.
+            pub fn alias_of(&self) -> Option<&str> {
+                self.alias_of.as_deref()
+            }
+
+            /// Set the name of the anchor which this node is an alias of
+            pub fn set_alias_of(&mut self, anchor: Option<String>) {
+                self.alias_of = anchor;
+            }
+        }
+    };
+}
+
+anchor_methods!(MarkedScalarNode);
+anchor_methods!(MarkedSequenceNode);
+anchor_methods!(MarkedMappingNode);
+
+// Strings compare equal to scalars with exactly the same text, so that
+// `"version" == key` works without needing `as_str()`.  As with comparisons
+// between nodes, the span plays no part in this.  It only goes this way
+// round because any other comparison on scalars would stop the type in
+// `scalar == value.into()` from being inferred.
+impl PartialEq<MarkedScalarNode> for str {
+    fn eq(&self, other: &MarkedScalarNode) -> bool {
+        self == other.value
+    }
+}
+
 impl<T> From<T> for Node
 where
     T: Into<MarkedScalarNode>,
@@ -590,6 +1191,93 @@ impl Node {
         }
     }
 
+    /// Whether this node is a scalar
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// let node: Node = "foobar".into();
+    /// assert!(node.is_scalar());
+    /// ```
+    pub fn is_scalar(&self) -> bool {
+        matches!(self, Node::Scalar(_))
+    }
+
+    /// Whether this node is a null scalar
+    ///
+    /// See [`MarkedScalarNode::is_null()`] for what counts as null.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "{foo: null, bar: {}}").unwrap();
+    /// assert!(node.as_mapping().unwrap().get_node("foo").unwrap().is_null());
+    /// assert!(!node.as_mapping().unwrap().get_node("bar").unwrap().is_null());
+    /// ```
+    pub fn is_null(&self) -> bool {
+        matches!(self, Node::Scalar(msn) if msn.is_null())
+    }
+
+    /// Whether this node is a mapping
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "{foo: {bar: 1}, baz: [1]}").unwrap();
+    /// let mappings = node
+    ///     .as_mapping()
+    ///     .unwrap()
+    ///     .values()
+    ///     .filter(|n| n.is_mapping())
+    ///     .count();
+    /// assert!(node.is_mapping());
+    /// assert_eq!(mappings, 1);
+    /// ```
+    pub fn is_mapping(&self) -> bool {
+        matches!(self, Node::Mapping(_))
+    }
+
+    /// Whether this node is a sequence
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// let node: Node = vec!["foobar"].into();
+    /// assert!(node.is_sequence());
+    /// ```
+    pub fn is_sequence(&self) -> bool {
+        matches!(self, Node::Sequence(_))
+    }
+
+    /// The indentation of this node, in columns
+    ///
+    /// For block mappings and sequences this is the number of columns before
+    /// their first key (or their first `-` indicator), which is how far the
+    /// container is indented.  For scalars it is the number of columns before
+    /// the scalar starts.
+    ///
+    /// Flow mappings and sequences (and hence everything within them) are
+    /// not subject to indentation, so flow containers report `None`, as do
+    /// nodes which don't know where they start.  A block sequence which is
+    /// not indented beyond the key which holds it (`key:\n- item`) has no
+    /// marker for its first `-`, so reports the indentation of its first
+    /// item instead.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "outer:\n    inner: [a, b]\n").unwrap();
+    /// assert_eq!(node.indentation(), Some(0));
+    /// assert_eq!(node.pointer("/outer").unwrap().indentation(), Some(4));
+    /// assert_eq!(node.pointer("/outer/inner").unwrap().indentation(), None);
+    /// ```
+    pub fn indentation(&self) -> Option<usize> {
+        let start = match self {
+            Node::Scalar(msn) => msn.span().start(),
+            Node::Mapping(mmn) if mmn.is_flow() => return None,
+            // Block mappings start at the `:` after their first key
+            Node::Mapping(mmn) => mmn.keys().next().and_then(|key| key.span().start()),
+            Node::Sequence(msn) if msn.is_flow() => return None,
+            Node::Sequence(msn) => msn.span().start(),
+        };
+        start.map(|mark| mark.column() - 1)
+    }
+
     /// Retrieve the scalar from this node if there is one
     ///
     /// ```
@@ -635,6 +1323,28 @@ impl Node {
         }
     }
 
+    /// Look up a key, giving a null node if it is missing
+    ///
+    /// If this node is a mapping containing the key then its value is
+    /// returned.  Otherwise, including when this node is not a mapping at
+    /// all, a shared null scalar with a blank span is returned.  This lets
+    /// lookups be chained without dealing with an `Option` at each step.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "server: {port: 8080}\n").unwrap();
+    /// let port = node.get_or_null("server").get_or_null("port");
+    /// assert_eq!(port.as_scalar().unwrap().as_u16(), Some(8080));
+    /// let host = node.get_or_null("server").get_or_null("host");
+    /// assert!(host.is_null());
+    /// assert!(host.span().is_blank());
+    /// ```
+    pub fn get_or_null(&self, key: &str) -> &Node {
+        self.as_mapping()
+            .and_then(|map| map.get_node(key))
+            .unwrap_or(&NULL_NODE)
+    }
+
     /// Retrieve the scalar from this node if there is one, mutably
     ///
     /// ```
@@ -679,33 +1389,1067 @@ impl Node {
             _ => None,
         }
     }
-}
 
-impl MarkedScalarNode {
-    /// Create a new scalar node with no value
+    /// Take this node, leaving a null scalar with a blank span in its place
+    ///
+    /// This moves a subtree out of a tree without cloning it, for when it
+    /// is to be put somewhere else.
     ///
     /// ```
-    /// # use marked_yaml::types::*;
-    /// let node = MarkedScalarNode::new_empty(Span::new_blank());
+    /// # use marked_yaml::*;
+    /// let mut node = parse_yaml(0, "server: {port: 8080}\n").unwrap();
+    /// let server = node.as_mapping_mut().unwrap().get_node_mut("server").unwrap();
+    /// let taken = server.take();
+    /// assert!(server.is_null());
+    /// assert!(taken.as_mapping().unwrap().get_node("port").is_some());
+    /// ```
+    pub fn take(&mut self) -> Node {
+        std::mem::replace(
+            self,
+            Node::Scalar(MarkedScalarNode::new_empty(Span::new_blank())),
+        )
+    }
+
+    /// The name of the anchor defined on this node, if it has one
+    ///
+    /// Anchors are only permitted when loading with
+    /// [`LoaderOptions::allow_aliases`][crate::LoaderOptions::allow_aliases]
+    /// set.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let options = LoaderOptions {
+    ///     allow_aliases: true,
+    ///     ..LoaderOptions::default()
+    /// };
+    /// let node = parse_yaml_with_options(0, "base: &base {a: 1}\n", options).unwrap();
+    /// assert_eq!(node.pointer("/base").unwrap().anchor(), Some("base"));
+    /// assert_eq!(node.anchor(), None);
+    /// ```
+    pub fn anchor(&self) -> Option<&str> {
+        match self {
+            Node::Scalar(msn) => msn.anchor(),
+            Node::Mapping(mmn) => mmn.anchor(),
+            Node::Sequence(msn) => msn.anchor(),
+        }
+    }
+
+    /// Set the name of the anchor defined on this node
+    pub fn set_anchor(&mut self, anchor: Option<String>) {
+        match self {
+            Node::Scalar(msn) => msn.set_anchor(anchor),
+            Node::Mapping(mmn) => mmn.set_anchor(anchor),
+            Node::Sequence(msn) => msn.set_anchor(anchor),
+        }
+    }
+
+    /// The name of the anchor which this node is an alias of, if it is one
+    ///
+    /// An alias is loaded as a copy of the node which its anchor is defined
+    /// on, so it can be used like any other node, but this records that it
+    /// was an alias.  The copy has the span of the alias, whereas the nodes
+    /// within it keep the spans of the anchored node's contents.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let options = LoaderOptions {
+    ///     allow_aliases: true,
+    ///     ..LoaderOptions::default()
+    /// };
+    /// let node = parse_yaml_with_options(0, "base: &base {a: 1}\ncopy: *base\n", options).unwrap();
+    /// let copy = node.pointer("/copy").unwrap();
+    /// assert_eq!(copy.alias_of(), Some("base"));
+    /// assert_eq!(copy, node.pointer("/base").unwrap());
+    /// assert_eq!(node.pointer("/base").unwrap().alias_of(), None);
+    /// ```
+    pub fn alias_of(&self) -> Option<&str> {
+        match self {
+            Node::Scalar(msn) => msn.alias_of(),
+            Node::Mapping(mmn) => mmn.alias_of(),
+            Node::Sequence(msn) => msn.alias_of(),
+        }
+    }
+
+    /// Set the name of the anchor which this node is an alias of
+    pub fn set_alias_of(&mut self, anchor: Option<String>) {
+        match self {
+            Node::Scalar(msn) => msn.set_alias_of(anchor),
+            Node::Mapping(mmn) => mmn.set_alias_of(anchor),
+            Node::Sequence(msn) => msn.set_alias_of(anchor),
+        }
+    }
+
+    /// Find every anchor defined in this node, with the span of the node
+    /// each is defined on
+    ///
+    /// Anchors are given in document order, including any on this node and
+    /// on mapping keys.  The copies which aliases are loaded as are not
+    /// looked inside, since any anchors there are the same definitions
+    /// again.  An anchor name may appear more than once, if it was
+    /// redefined, in which case aliases refer to the closest definition
+    /// before them.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let options = LoaderOptions {
+    ///     allow_aliases: true,
+    ///     ..LoaderOptions::default()
+    /// };
+    /// let yaml = "base: &base {a: &a 1}\ncopy: *base\nlist: [&b x, *a]\n";
+    /// let node = parse_yaml_with_options(0, yaml, options).unwrap();
+    /// let anchors: Vec<_> = node
+    ///     .anchors()
+    ///     .into_iter()
+    ///     .map(|(name, span)| (name, span.start().unwrap().column()))
+    ///     .collect();
+    /// assert_eq!(anchors, [("base", 13), ("a", 20), ("b", 11)]);
+    /// ```
+    pub fn anchors(&self) -> Vec<(&str, &Span)> {
+        let mut anchors = Vec::new();
+        self.collect_anchors(&mut anchors);
+        anchors
+    }
+
+    fn collect_anchors<'a>(&'a self, anchors: &mut Vec<(&'a str, &'a Span)>) {
+        if self.alias_of().is_some() {
+            return;
+        }
+        anchors.extend(self.anchor().map(|anchor| (anchor, self.span())));
+        match self {
+            Node::Scalar(_) => {}
+            Node::Mapping(map) => {
+                for (key, value) in map.iter() {
+                    if key.alias_of().is_none() {
+                        anchors.extend(key.anchor().map(|anchor| (anchor, key.span())));
+                    }
+                    if let Some(key) = key.complex_key() {
+                        key.collect_anchors(anchors);
+                    }
+                    value.collect_anchors(anchors);
+                }
+            }
+            Node::Sequence(seq) => {
+                for item in seq.iter() {
+                    item.collect_anchors(anchors);
+                }
+            }
+        }
+    }
+
+    /// Look up a node by JSON Pointer (RFC 6901)
+    ///
+    /// The pointer is a sequence of `/`-prefixed segments where `~1` stands
+    /// for `/` and `~0` stands for `~`.  Segments index into sequences if
+    /// they are numeric, otherwise they are used as mapping keys.  The empty
+    /// pointer refers to this node itself.
+    ///
+    /// If the pointer is malformed, or any segment cannot be followed, then
+    /// None will be returned.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "{servers: [{host: example.com}], a/b: c}").unwrap();
+    /// assert_eq!(
+    ///     node.pointer("/servers/0/host")
+    ///         .and_then(Node::as_scalar)
+    ///         .map(|s| s.as_str()),
+    ///     Some("example.com")
+    /// );
+    /// assert!(node.pointer("/a~1b").is_some());
+    /// assert!(node.pointer("/servers/1").is_none());
+    /// ```
+    pub fn pointer(&self, ptr: &str) -> Option<&Node> {
+        if ptr.is_empty() {
+            return Some(self);
+        }
+        let ptr = ptr.strip_prefix('/')?;
+        ptr.split('/').try_fold(self, |node, segment| {
+            let segment = segment.replace("~1", "/").replace("~0", "~");
+            match node {
+                Node::Mapping(mmn) => mmn.get_node(&segment),
+                Node::Sequence(msn) => {
+                    // RFC 6901 only permits plain decimal indices, so no
+                    // signs and no leading zeros
+                    let digits = !segment.is_empty() && segment.bytes().all(|b| b.is_ascii_digit());
+                    if !digits || (segment.len() > 1 && segment.starts_with('0')) {
+                        return None;
+                    }
+                    msn.get_node(segment.parse().ok()?)
+                }
+                Node::Scalar(_) => None,
+            }
+        })
+    }
+
+    /// Find the path from this node to the given node
+    ///
+    /// The target is matched by identity rather than by value, so it should
+    /// be a reference to a node within this tree, as found by traversing it.
+    /// If the target is this node then the path is empty.  If the target is
+    /// not within this tree then None is returned.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// # use marked_yaml::types::*;
+    /// let node = parse_yaml(0, "{servers: [{host: example.com}]}").unwrap();
+    /// let host = node.pointer("/servers/0/host").unwrap();
+    /// assert_eq!(
+    ///     node.path_to(host),
+    ///     Some(vec![
+    ///         PathSegment::Key("servers".into()),
+    ///         PathSegment::Index(0),
+    ///         PathSegment::Key("host".into()),
+    ///     ])
+    /// );
+    /// ```
+    pub fn path_to(&self, target: &Node) -> Option<Vec<PathSegment>> {
+        let mut path = Vec::new();
+        if self.path_to_inner(target, &mut path) {
+            Some(path)
+        } else {
+            None
+        }
+    }
+
+    /// Find the differences between two nodes
+    ///
+    /// Mappings are compared key by key, regardless of the order of their
+    /// entries, and sequences are compared item by item.  Anything else
+    /// which differs, including a node whose kind has changed, is reported
+    /// as modified.  As with equality, spans play no part in deciding
+    /// whether something has changed.
+    ///
+    /// Each change carries the path to the value concerned, along with
+    /// spans from whichever side has the value: added values are located
+    /// in `new`, removed values in `old`, and modified values carry the
+    /// span from each side, old first.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// # use marked_yaml::types::*;
+    /// let old = parse_yaml(0, "name: app\nport: 80\n").unwrap();
+    /// let new = parse_yaml(1, "name: app\nport: 8080\n").unwrap();
+    /// let changes = Node::diff(&old, &new);
+    /// assert_eq!(
+    ///     changes,
+    ///     [Change::Modified(
+    ///         vec![PathSegment::Key("port".into())],
+    ///         Span::new_start(Marker::new(0, 2, 7)),
+    ///         Span::new_start(Marker::new(1, 2, 7)),
+    ///     )]
+    /// );
+    /// ```
+    pub fn diff(old: &Node, new: &Node) -> Vec<Change> {
+        let mut changes = Vec::new();
+        old.diff_inner(new, &mut Vec::new(), &mut changes);
+        changes
+    }
+
+    fn diff_inner(&self, new: &Node, path: &mut Vec<PathSegment>, changes: &mut Vec<Change>) {
+        match (self, new) {
+            (Node::Mapping(old), Node::Mapping(new)) => {
+                for (key, value) in old.iter() {
+                    path.push(PathSegment::Key(key.as_str().to_string()));
+                    match new.get_node(key.as_str()) {
+                        Some(other) => value.diff_inner(other, path, changes),
+                        None => changes.push(Change::Removed(path.clone(), *value.span())),
+                    }
+                    path.pop();
+                }
+                for (key, value) in new.iter() {
+                    if old.get_node(key.as_str()).is_none() {
+                        path.push(PathSegment::Key(key.as_str().to_string()));
+                        changes.push(Change::Added(path.clone(), *value.span()));
+                        path.pop();
+                    }
+                }
+            }
+            (Node::Sequence(old), Node::Sequence(new)) => {
+                for index in 0..old.len().max(new.len()) {
+                    path.push(PathSegment::Index(index));
+                    match (old.get(index), new.get(index)) {
+                        (Some(value), Some(other)) => value.diff_inner(other, path, changes),
+                        (Some(value), None) => {
+                            changes.push(Change::Removed(path.clone(), *value.span()))
+                        }
+                        (None, Some(other)) => {
+                            changes.push(Change::Added(path.clone(), *other.span()))
+                        }
+                        (None, None) => unreachable!(),
+                    }
+                    path.pop();
+                }
+            }
+            (old, new) if old != new => {
+                changes.push(Change::Modified(path.clone(), *old.span(), *new.span()))
+            }
+            _ => {}
+        }
+    }
+
+    fn path_to_inner(&self, target: &Node, path: &mut Vec<PathSegment>) -> bool {
+        if std::ptr::eq(self, target) {
+            return true;
+        }
+        match self {
+            Node::Scalar(_) => {}
+            Node::Mapping(mmn) => {
+                for (key, value) in mmn.iter() {
+                    path.push(PathSegment::Key(key.as_str().to_string()));
+                    if value.path_to_inner(target, path) {
+                        return true;
+                    }
+                    path.pop();
+                }
+            }
+            Node::Sequence(msn) => {
+                for (index, value) in msn.iter().enumerate() {
+                    path.push(PathSegment::Index(index));
+                    if value.path_to_inner(target, path) {
+                        return true;
+                    }
+                    path.pop();
+                }
+            }
+        }
+        false
+    }
+
+    /// Build a new tree with every scalar value transformed
+    ///
+    /// The function is called for each scalar which is a value in this tree,
+    /// in document order, and the scalar it returns takes its place.  Mapping
+    /// keys are left untouched, as are the spans of all the sequences and
+    /// mappings, so the structure of the tree is preserved.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// # use marked_yaml::types::*;
+    /// let node = parse_yaml(0, "{greeting: hello, names: [alice, bob]}").unwrap();
+    /// let shouty = node.map_scalars(|s| MarkedScalarNode::new(*s.span(), s.to_uppercase()));
+    /// assert_eq!(
+    ///     shouty.pointer("/names/1").and_then(Node::as_scalar).unwrap().as_str(),
+    ///     "BOB"
+    /// );
+    /// ```
+    pub fn map_scalars<F>(&self, mut f: F) -> Node
+    where
+        F: FnMut(&MarkedScalarNode) -> MarkedScalarNode,
+    {
+        self.try_map_scalars_inner(&mut |s| Ok::<_, Infallible>(f(s)))
+            .unwrap_or_else(|e| match e {})
+    }
+
+    fn try_map_scalars_inner<F, E>(&self, f: &mut F) -> Result<Node, E>
+    where
+        F: FnMut(&MarkedScalarNode) -> Result<MarkedScalarNode, E>,
+    {
+        Ok(match self {
+            Node::Scalar(msn) => Node::Scalar(f(msn)?),
+            Node::Sequence(msn) => {
+                let mut seq = MarkedSequenceNode::new(
+                    *msn.span(),
+                    msn.iter()
+                        .map(|n| n.try_map_scalars_inner(f))
+                        .collect::<Result<_, _>>()?,
+                );
+                seq.set_flow(msn.is_flow());
+                Node::Sequence(seq)
+            }
+            Node::Mapping(mmn) => {
+                let mut map = MarkedMappingNode::new(
+                    *mmn.span(),
+                    mmn.iter()
+                        .map(|(k, v)| Ok((k.clone(), v.try_map_scalars_inner(f)?)))
+                        .collect::<Result<_, _>>()?,
+                );
+                map.set_flow(mmn.is_flow());
+                Node::Mapping(map)
+            }
+        })
+    }
+
+    /// Build a new tree with `${NAME}` references in scalars expanded
+    ///
+    /// Every scalar value in the tree (mapping keys are left alone) has each
+    /// `${NAME}` within it replaced by the result of calling the lookup
+    /// function with `NAME`.  Typically the lookup would consult the process
+    /// environment, but any source of values may be used.  An unterminated
+    /// `${` is left as it is.
+    ///
+    /// If the lookup function returns `None` for a name then, when `strict`
+    /// is set, an error pointing at the scalar is returned.  Otherwise the
+    /// reference is left unexpanded.
+    ///
+    /// All nodes keep their spans, including expanded scalars which keep the
+    /// span of the text they were expanded from.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "home: ${HOME}/src\nshell: ${SHELL}\n").unwrap();
+    /// let lookup = |name: &str| (name == "HOME").then(|| "/home/user".to_string());
+    /// let expanded = node.expand_env(false, lookup).unwrap();
+    /// let map = expanded.as_mapping().unwrap();
+    /// assert_eq!(map.get_scalar("home").unwrap().as_str(), "/home/user/src");
+    /// assert_eq!(map.get_scalar("shell").unwrap().as_str(), "${SHELL}");
+    /// let err = node.expand_env(true, lookup).err().unwrap();
+    /// assert_eq!(err.start_mark(), Some(Marker::new(0, 2, 8)));
+    /// ```
+    pub fn expand_env<F>(&self, strict: bool, lookup: F) -> Result<Node, ExpandEnvError>
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        self.try_map_scalars_inner(&mut |s| {
+            let mut expanded = String::new();
+            let mut rest = s.as_str();
+            while let Some(start) = rest.find("${") {
+                let len = match rest[start + 2..].find('}') {
+                    Some(len) => len,
+                    None => break,
+                };
+                let name = &rest[start + 2..start + 2 + len];
+                let reference = &rest[..start + 2 + len + 1];
+                expanded.push_str(&rest[..start]);
+                match lookup(name) {
+                    Some(value) => expanded.push_str(&value),
+                    None if strict => {
+                        return Err(ExpandEnvError::UndefinedVariable(
+                            name.to_string(),
+                            *s.span(),
+                        ))
+                    }
+                    None => expanded.push_str(&reference[start..]),
+                }
+                rest = &rest[reference.len()..];
+            }
+            if rest.len() == s.len() {
+                return Ok(s.clone());
+            }
+            expanded.push_str(rest);
+            // Only the value changes, so the scalar keeps its span and style
+            let mut node = s.clone();
+            node.value = expanded;
+            Ok(node)
+        })
+    }
+
+    /// Clone this node with every span in the tree blanked
+    ///
+    /// Equality and hashing of nodes already ignore spans, so two trees with
+    /// the same content compare and hash the same regardless of where they
+    /// came from.  This is useful when you also want the trees themselves to
+    /// be indistinguishable, for example when caching documents by content,
+    /// or comparing `Debug` output.  Scalar and container styles are kept.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "{key: [value]}").unwrap();
+    /// let bare = node.clone_without_spans();
+    /// assert_eq!(bare, node);
+    /// assert_eq!(bare.span(), &Span::new_blank());
+    /// assert_eq!(bare.pointer("/key/0").unwrap().span(), &Span::new_blank());
+    /// ```
+    pub fn clone_without_spans(&self) -> Node {
+        match self {
+            Node::Scalar(msn) => Node::Scalar(msn.clone_without_span()),
+            Node::Sequence(msn) => {
+                let mut seq = MarkedSequenceNode::new(
+                    Span::new_blank(),
+                    msn.iter().map(Node::clone_without_spans).collect(),
+                );
+                seq.set_flow(msn.is_flow());
+                Node::Sequence(seq)
+            }
+            Node::Mapping(mmn) => {
+                let mut map = MarkedMappingNode::new(
+                    Span::new_blank(),
+                    mmn.iter()
+                        .map(|(k, v)| (k.clone_without_span(), v.clone_without_spans()))
+                        .collect(),
+                );
+                map.set_flow(mmn.is_flow());
+                Node::Mapping(map)
+            }
+        }
+    }
+
+    /// Build a template from this node, keeping its shape but not its values
+    ///
+    /// Every mapping and sequence is kept, with the same keys and the same
+    /// number of items, but every other scalar is replaced with a null
+    /// (written `~`).  All spans are blank.  Emitting the result, for example
+    /// with [`to_yaml_string_pretty()`][Node::to_yaml_string_pretty], gives
+    /// a starting point for writing a similar document.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "name: web\nports: [80, 443]\n").unwrap();
+    /// let skeleton = node.skeleton();
+    /// assert_eq!(
+    ///     skeleton.to_yaml_string_pretty(&EmitOptions::default()),
+    ///     "name: ~\nports:\n  - ~\n  - ~\n"
+    /// );
+    /// ```
+    pub fn skeleton(&self) -> Node {
+        match self {
+            Node::Scalar(_) => Node::from("~"),
+            Node::Sequence(msn) => {
+                let mut seq = MarkedSequenceNode::new(
+                    Span::new_blank(),
+                    msn.iter().map(Node::skeleton).collect(),
+                );
+                seq.set_flow(msn.is_flow());
+                Node::Sequence(seq)
+            }
+            Node::Mapping(mmn) => {
+                let mut map = MarkedMappingNode::new(
+                    Span::new_blank(),
+                    mmn.iter()
+                        .map(|(k, v)| (k.clone_without_span(), v.skeleton()))
+                        .collect(),
+                );
+                map.set_flow(mmn.is_flow());
+                Node::Mapping(map)
+            }
+        }
+    }
+
+    /// Iterate over this node and every node within it, with their spans
+    ///
+    /// Nodes are visited in pre-order, which is document order: a container
+    /// is yielded before its contents, and the contents are yielded in the
+    /// order they appear.  Mapping keys are scalars rather than nodes, so
+    /// only mapping values are yielded.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "a: [b, c]\nd: e\n").unwrap();
+    /// let lines: Vec<_> = node
+    ///     .iter_with_spans()
+    ///     .filter(|(node, _)| node.is_scalar())
+    ///     .map(|(_, span)| span.start().unwrap().line())
+    ///     .collect();
+    /// assert_eq!(lines, [1, 1, 2]);
+    /// ```
+    pub fn iter_with_spans(&self) -> impl Iterator<Item = (&Node, &Span)> {
+        let mut stack = vec![self];
+        std::iter::from_fn(move || {
+            let node = stack.pop()?;
+            match node {
+                Node::Scalar(_) => {}
+                Node::Mapping(map) => stack.extend(map.values().rev()),
+                Node::Sequence(seq) => stack.extend(seq.iter().rev()),
+            }
+            Some((node, node.span()))
+        })
+    }
+
+    /// Count the scalars, mappings, and sequences in this node
+    ///
+    /// The counts include this node itself, and mapping keys are counted
+    /// as scalars, or as what they were written as for complex keys, so the total is the number of nodes which
+    /// [`LoaderOptions::max_nodes`][crate::LoaderOptions::max_nodes] would
+    /// count when loading the same document.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "a: [b, c]\nd: {e: f}\n").unwrap();
+    /// assert_eq!(node.count_by_kind(), (6, 2, 1));
+    /// ```
+    pub fn count_by_kind(&self) -> (usize, usize, usize) {
+        self.iter_with_spans()
+            .fold(
+                (0, 0, 0),
+                |(scalars, mappings, sequences), (node, _)| match node {
+                    Node::Scalar(_) => (scalars + 1, mappings, sequences),
+                    Node::Mapping(map) => map.keys().fold(
+                        (scalars, mappings + 1, sequences),
+                        |(scalars, mappings, sequences), key| match key.complex_key() {
+                            Some(key) => {
+                                let (s, m, q) = key.count_by_kind();
+                                (scalars + s, mappings + m, sequences + q)
+                            }
+                            None => (scalars + 1, mappings, sequences),
+                        },
+                    ),
+                    Node::Sequence(_) => (scalars, mappings, sequences + 1),
+                },
+            )
+    }
+
+    /// Rewrite the source identifier of every marker in this node
+    ///
+    /// This is useful when combining trees from separate parses whose
+    /// source identifiers clash, for example when moving them into a single
+    /// [`SourceRegistry`].  The function is given each marker's current
+    /// source identifier and returns the new one.  Mapping keys are
+    /// remapped too.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let mut node = parse_yaml(0, "key: value\n").unwrap();
+    /// node.remap_sources(|source| source + 5);
+    /// let value = node.pointer("/key").unwrap();
+    /// assert_eq!(value.span().start().unwrap().source(), 5);
+    /// ```
+    pub fn remap_sources<F>(&mut self, f: F)
+    where
+        F: Fn(usize) -> usize,
+    {
+        self.remap_sources_inner(&f)
+    }
+
+    fn remap_sources_inner<F>(&mut self, f: &F)
+    where
+        F: Fn(usize) -> usize,
+    {
+        self.span_mut().remap_sources(f);
+        match self {
+            Node::Scalar(_) => {}
+            Node::Mapping(map) => {
+                // Keys can't be changed in place, but since spans take no
+                // part in hashing the rebuilt mapping is equivalent
+                map.value = std::mem::take(&mut map.value)
+                    .into_iter()
+                    .map(|(mut key, mut value)| {
+                        key.span_mut().remap_sources(f);
+                        if let Some(key) = key.complex_key.as_deref_mut() {
+                            key.remap_sources_inner(f);
+                        }
+                        value.remap_sources_inner(f);
+                        (key, value)
+                    })
+                    .collect();
+            }
+            Node::Sequence(seq) => {
+                for value in seq.iter_mut() {
+                    value.remap_sources_inner(f);
+                }
+            }
+        }
+    }
+
+    /// A compact rendering of this node for debugging
+    ///
+    /// The derived [`Debug`] output shows every field of every node, which
+    /// gets hard to read very quickly.  The value returned here renders
+    /// each node as its kind and content followed by where it starts, which
+    /// is usually all that's wanted when a test fails.  The alternate form
+    /// (`{:#?}`) spreads containers over several lines.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "{key: [value]}\n").unwrap();
+    /// assert_eq!(
+    ///     format!("{:?}", node.debug_compact()),
+    ///     r#"mapping@1:1 {scalar("key")@1:2: sequence@1:7 [scalar("value")@1:8]}"#
+    /// );
+    /// ```
+    pub fn debug_compact(&self) -> impl fmt::Debug + '_ {
+        CompactNode(self)
+    }
+
+    /// Retain only the mapping entries for which the predicate returns true,
+    /// throughout this node
+    ///
+    /// Every mapping in the tree, including those inside sequences, has
+    /// [`MarkedMappingNode::retain`] applied to it.  Mappings are filtered
+    /// before their surviving values are visited, so the predicate never
+    /// sees anything inside an entry which has been removed.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let mut node = parse_yaml(0, "paths:\n  /a: {get: {}, x-draft: true}\n").unwrap();
+    /// node.retain_recursive(|key, _| !key.as_str().starts_with("x-"));
+    /// assert!(node.pointer("/paths/~1a/x-draft").is_none());
+    /// assert!(node.pointer("/paths/~1a/get").is_some());
+    /// ```
+    pub fn retain_recursive<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&MarkedScalarNode, &Node) -> bool,
+    {
+        self.retain_recursive_inner(&mut f)
+    }
+
+    fn retain_recursive_inner<F>(&mut self, f: &mut F)
+    where
+        F: FnMut(&MarkedScalarNode, &Node) -> bool,
+    {
+        match self {
+            Node::Scalar(_) => {}
+            Node::Mapping(map) => {
+                map.retain(&mut *f);
+                for (_, value) in map.iter_mut() {
+                    value.retain_recursive_inner(f);
+                }
+            }
+            Node::Sequence(seq) => {
+                for value in seq.iter_mut() {
+                    value.retain_recursive_inner(f);
+                }
+            }
+        }
+    }
+
+    /// Deep merge another node over this one
+    ///
+    /// This is intended for layering configuration, where `other` contains
+    /// overrides for this node.  Where both nodes are mappings, each entry
+    /// in `other` is merged into the entry with the same key in this node,
+    /// or added at the end if there is no such entry.  Otherwise this node
+    /// is replaced by a copy of `other`, spans and all.
+    ///
+    /// Sequences are replaced wholesale, see [`Node::merge_with`] if you
+    /// want them concatenated instead.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let mut base = parse_yaml(0, "server: {host: localhost, port: 80}\n").unwrap();
+    /// let overrides = parse_yaml(1, "server: {port: 8080}\n").unwrap();
+    /// base.merge(&overrides);
+    /// let port = base.pointer("/server/port").unwrap();
+    /// assert_eq!(port.as_scalar().unwrap().as_str(), "8080");
+    /// assert_eq!(port.span().start().unwrap().source(), 1);
+    /// assert_eq!(base.pointer("/server/host").unwrap().as_scalar().unwrap().as_str(), "localhost");
+    /// ```
+    pub fn merge(&mut self, other: &Node) {
+        self.merge_with(other, SequenceMerge::Replace)
+    }
+
+    /// Deep merge another node over this one, choosing how to merge sequences
+    ///
+    /// This is as [`Node::merge`] except that where both nodes are sequences
+    /// they are combined according to the given strategy.  When sequences
+    /// are concatenated, the span of the sequence in this node is kept.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// # use marked_yaml::types::SequenceMerge;
+    /// let mut base = parse_yaml(0, "paths: [/usr/lib]\n").unwrap();
+    /// let overrides = parse_yaml(1, "paths: [/opt/lib]\n").unwrap();
+    /// base.merge_with(&overrides, SequenceMerge::Concat);
+    /// let paths = base.pointer("/paths").unwrap().as_sequence().unwrap();
+    /// assert_eq!(paths.len(), 2);
+    /// ```
+    pub fn merge_with(&mut self, other: &Node, sequences: SequenceMerge) {
+        match (self, other) {
+            (Node::Mapping(base), Node::Mapping(overrides)) => {
+                for (key, value) in overrides.iter() {
+                    match base.get_mut(key) {
+                        Some(existing) => existing.merge_with(value, sequences),
+                        None => {
+                            base.insert(key.clone(), value.clone());
+                        }
+                    }
+                }
+            }
+            (Node::Sequence(base), Node::Sequence(overrides))
+                if sequences == SequenceMerge::Concat =>
+            {
+                base.extend(overrides.iter().cloned());
+            }
+            (this, other) => *this = other.clone(),
+        }
+    }
+}
+
+/// A step along the path to a node, as found by [`Node::path_to`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PathSegment {
+    /// The value of the given key in a mapping
+    Key(String),
+    /// The item at the given index in a sequence
+    Index(usize),
+}
+
+/// A difference between two nodes, as found by [`Node::diff`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// A value is only in the new node, the span is from the new node
+    Added(Vec<PathSegment>, Span),
+    /// A value is only in the old node, the span is from the old node
+    Removed(Vec<PathSegment>, Span),
+    /// A value differs between the nodes, the spans are from the old and
+    /// the new node respectively
+    Modified(Vec<PathSegment>, Span, Span),
+}
+
+/// How sequences are combined by [`Node::merge_with`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum SequenceMerge {
+    /// The overriding sequence replaces the base sequence
+    #[default]
+    Replace,
+    /// The overriding sequence is appended to the base sequence
+    Concat,
+}
+
+/// Errors which can occur while expanding references with [`Node::expand_env`]
+#[derive(Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ExpandEnvError {
+    /// A reference was made to a variable which isn't defined
+    UndefinedVariable(String, Span),
+}
+
+impl ExpandEnvError {
+    /// Retrieve the start marker of the scalar which caused the error, if
+    /// there is one
+    pub fn start_mark(&self) -> Option<Marker> {
+        match self {
+            ExpandEnvError::UndefinedVariable(_, span) => span.start().copied(),
+        }
+    }
+}
+
+impl Display for ExpandEnvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(mark) = self.start_mark() {
+            write!(f, "{}: ", mark)?;
+        }
+        match self {
+            ExpandEnvError::UndefinedVariable(name, _) => {
+                write!(f, "Variable `{}` is not defined", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ExpandEnvError {}
+
+impl MarkedScalarNode {
+    /// Create a new scalar node with no value
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// let node = MarkedScalarNode::new_empty(Span::new_blank());
     /// ```
     pub fn new_empty(span: Span) -> Self {
         Self {
             span,
             value: String::new(),
+            may_coerce: true,
+            style: ScalarStyle::Plain,
+            chomping: Chomping::Clip,
+            indent: None,
+            binary: false,
+            anchor: None,
+            alias_of: None,
+            complex_key: None,
         }
     }
 
-    /// Create a new scalar node
+    /// Create a new scalar node
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// let node = MarkedScalarNode::new(Span::new_blank(), "foobar");
+    /// ```
+    pub fn new<'a, S: Into<Cow<'a, str>>>(span: Span, content: S) -> Self {
+        Self {
+            span,
+            value: content.into().into_owned(),
+            may_coerce: true,
+            style: ScalarStyle::Plain,
+            chomping: Chomping::Clip,
+            indent: None,
+            binary: false,
+            anchor: None,
+            alias_of: None,
+            complex_key: None,
+        }
+    }
+
+    fn clone_without_span(&self) -> Self {
+        let mut scalar = self.clone();
+        scalar.span = Span::new_blank();
+        scalar
+    }
+
+    /// Whether this scalar may be coerced to a non-string type
+    ///
+    /// Plain scalars may be read as booleans, numbers, etc. whereas quoted
+    /// and block scalars are always strings.  Scalars created in code may
+    /// be coerced unless told otherwise.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "{plain: NO, quoted: \"NO\"}").unwrap();
+    /// let map = node.as_mapping().unwrap();
+    /// assert!(map.get_scalar("plain").unwrap().may_coerce());
+    /// assert!(!map.get_scalar("quoted").unwrap().may_coerce());
+    /// ```
+    pub fn may_coerce(&self) -> bool {
+        self.may_coerce
+    }
+
+    /// Set whether this scalar may be coerced to a non-string type
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// let mut node = MarkedScalarNode::new(Span::new_blank(), "NO");
+    /// node.set_coerce(false);
+    /// assert!(!node.may_coerce());
+    /// ```
+    pub fn set_coerce(&mut self, may_coerce: bool) {
+        self.may_coerce = may_coerce;
+    }
+
+    /// The style in which this scalar was written
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// # use marked_yaml::types::ScalarStyle;
+    /// let node = parse_yaml(0, "{plain: a, single: 'b', double: \"c\"}").unwrap();
+    /// let map = node.as_mapping().unwrap();
+    /// assert_eq!(map.get_scalar("plain").unwrap().style(), ScalarStyle::Plain);
+    /// assert_eq!(map.get_scalar("single").unwrap().style(), ScalarStyle::SingleQuoted);
+    /// assert_eq!(map.get_scalar("double").unwrap().style(), ScalarStyle::DoubleQuoted);
+    /// ```
+    pub fn style(&self) -> ScalarStyle {
+        self.style
+    }
+
+    /// Set the style in which this scalar should be written
+    ///
+    /// This does not change whether the scalar may be coerced, see
+    /// [`set_coerce()`][MarkedScalarNode::set_coerce] for that.
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// let mut node = MarkedScalarNode::new(Span::new_blank(), "NO");
+    /// node.set_style(ScalarStyle::DoubleQuoted);
+    /// assert_eq!(node.style(), ScalarStyle::DoubleQuoted);
+    /// ```
+    pub fn set_style(&mut self, style: ScalarStyle) {
+        self.style = style;
+    }
+
+    fn is_block(&self) -> bool {
+        matches!(self.style, ScalarStyle::Literal | ScalarStyle::Folded)
+    }
+
+    /// The chomping indicator of a block scalar
+    ///
+    /// This is `None` unless the scalar's [style][MarkedScalarNode::style]
+    /// is literal or folded.  Block scalars without an explicit indicator
+    /// use [`Chomping::Clip`].
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// # use marked_yaml::types::Chomping;
+    /// let node = parse_yaml(0, "strip: |-\n  text\nplain: text\n").unwrap();
+    /// let map = node.as_mapping().unwrap();
+    /// assert_eq!(map.get_scalar("strip").unwrap().block_chomping(), Some(Chomping::Strip));
+    /// assert_eq!(map.get_scalar("plain").unwrap().block_chomping(), None);
+    /// ```
+    pub fn block_chomping(&self) -> Option<Chomping> {
+        self.is_block().then_some(self.chomping)
+    }
+
+    /// Set the chomping indicator to use if this is a block scalar
+    pub fn set_block_chomping(&mut self, chomping: Chomping) {
+        self.chomping = chomping;
+    }
+
+    /// The explicit indentation indicator of a block scalar, if it has one
+    ///
+    /// Block scalars usually have their indentation detected from their
+    /// first line, but a header such as `|2` gives it explicitly.  This is
+    /// `None` for scalars which are not literal or folded, and for block
+    /// scalars without an indentation indicator.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "text: |+2\n   indented\n").unwrap();
+    /// let text = node.as_mapping().unwrap().get_scalar("text").unwrap();
+    /// assert_eq!(text.explicit_indent(), Some(2));
+    /// assert_eq!(text.as_str(), " indented\n");
+    /// ```
+    pub fn explicit_indent(&self) -> Option<usize> {
+        self.indent.filter(|_| self.is_block())
+    }
+
+    /// Set the explicit indentation indicator to use if this is a block
+    /// scalar
+    pub fn set_explicit_indent(&mut self, indent: Option<usize>) {
+        self.indent = indent;
+    }
+
+    /// Whether this scalar was tagged `!!binary`
+    ///
+    /// Binary scalars hold base64 encoded data, which is decoded when they
+    /// are deserialized as bytes.  Like the style, this plays no part in
+    /// equality.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "data: !!binary aGVsbG8=\ntext: aGVsbG8=\n").unwrap();
+    /// let map = node.as_mapping().unwrap();
+    /// assert!(map.get_scalar("data").unwrap().is_binary());
+    /// assert!(!map.get_scalar("text").unwrap().is_binary());
+    /// ```
+    pub fn is_binary(&self) -> bool {
+        self.binary
+    }
+
+    /// Set whether this scalar is tagged `!!binary`
+    pub fn set_binary(&mut self, binary: bool) {
+        self.binary = binary;
+    }
+
+    /// The collection which this mapping key was written as, if it is a
+    /// complex key
+    ///
+    /// Complex keys (e.g. `? [a, b]`) are only permitted when loading with
+    /// [`LoaderOptions::allow_complex_keys`][crate::LoaderOptions::allow_complex_keys]
+    /// set.  They load as a scalar whose value is the collection written in
+    /// flow style, which is what they compare and hash by, and whose span
+    /// is that of the collection.  When deserializing, the collection is
+    /// used instead of the scalar.
     ///
     /// ```
-    /// # use marked_yaml::types::*;
-    /// let node = MarkedScalarNode::new(Span::new_blank(), "foobar");
+    /// # use marked_yaml::*;
+    /// let options = LoaderOptions {
+    ///     allow_complex_keys: true,
+    ///     ..LoaderOptions::default()
+    /// };
+    /// let node = parse_yaml_with_options(0, "? [a, 'b']\n: c\n", options).unwrap();
+    /// let (key, _) = node.as_mapping().unwrap().iter().next().unwrap();
+    /// assert_eq!(key.as_str(), "[a, b]");
+    /// assert_eq!(key.complex_key().unwrap().as_sequence().unwrap().len(), 2);
+    /// assert_eq!(key.span().start(), Some(&Marker::new(0, 1, 3)));
     /// ```
-    pub fn new<'a, S: Into<Cow<'a, str>>>(span: Span, content: S) -> Self {
-        Self {
-            span,
-            value: content.into().into_owned(),
-        }
+    pub fn complex_key(&self) -> Option<&Node> {
+        self.complex_key.as_deref()
+    }
+
+    /// Set the collection which this mapping key was written as
+    ///
+    /// This does not change the value of the scalar, which is what the key
+    /// compares and hashes by.
+    pub fn set_complex_key(&mut self, key: Option<Node>) {
+        self.complex_key = key.map(Box::new);
+    }
+
+    /// Replace the value of this scalar
+    ///
+    /// The style of the scalar, and whether it may be coerced, are kept so
+    /// that the new value is written the same way as the old one.  Since
+    /// the old span no longer describes the value, it is blanked.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let mut node = parse_yaml(0, "name: \"old\"\n").unwrap();
+    /// let name = node.as_mapping_mut().unwrap().get_scalar_mut("name").unwrap();
+    /// name.set_str("new");
+    /// assert_eq!(name.as_str(), "new");
+    /// assert!(name.span().is_blank());
+    /// ```
+    pub fn set_str(&mut self, value: &str) {
+        self.value.clear();
+        self.value.push_str(value);
+        self.span = Span::new_blank();
     }
 
     /// Treat the scalar node as a string
@@ -721,6 +2465,43 @@ impl MarkedScalarNode {
         self.value.as_str()
     }
 
+    /// Treat the scalar node as a string, without surrounding whitespace
+    ///
+    /// Leading and trailing whitespace, as defined by [`str::trim`], is
+    /// removed.  This includes any newlines left by block scalars.  Use this
+    /// when comparing values where stray whitespace should not matter.
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// let node: MarkedScalarNode = "  padded\n".into();
+    /// assert_eq!(node.trimmed_str(), "padded");
+    /// ```
+    pub fn trimmed_str(&self) -> &str {
+        self.value.trim()
+    }
+
+    /// Treat the scalar node as a string, without a final line break
+    ///
+    /// Block scalars (`|` and `>`) keep a single trailing line break unless
+    /// they use the strip (`-`) chomping indicator.  This removes exactly one
+    /// trailing `\n` or `\r\n`, if there is one, and leaves any other
+    /// whitespace alone.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "text: >\n  folded\n  lines\n").unwrap();
+    /// let text = node.as_mapping().unwrap().get_scalar("text").unwrap();
+    /// assert_eq!(text.as_str(), "folded lines\n");
+    /// assert_eq!(text.chomped_str(), "folded lines");
+    /// ```
+    pub fn chomped_str(&self) -> &str {
+        let value = self.value.as_str();
+        value
+            .strip_suffix("\r\n")
+            .or_else(|| value.strip_suffix('\n'))
+            .unwrap_or(value)
+    }
+
     /// Treat the scalar node as a boolean
     ///
     /// If the scalar contains any of the following then it is true:
@@ -753,6 +2534,23 @@ impl MarkedScalarNode {
             _ => None,
         }
     }
+
+    /// Whether this scalar is a null
+    ///
+    /// Plain scalars which are empty, `~`, `null`, `Null`, or `NULL` are
+    /// nulls.  Quoted scalars are always strings, and so never null.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "a: ~\nb: 'null'\nc:\n").unwrap();
+    /// let map = node.as_mapping().unwrap();
+    /// assert!(map.get_scalar("a").unwrap().is_null());
+    /// assert!(!map.get_scalar("b").unwrap().is_null());
+    /// assert!(map.get_scalar("c").unwrap().is_null());
+    /// ```
+    pub fn is_null(&self) -> bool {
+        self.may_coerce && matches!(self.value.as_str(), "" | "~" | "null" | "Null" | "NULL")
+    }
 }
 
 impl<'a> From<&'a str> for MarkedScalarNode {
@@ -797,7 +2595,7 @@ impl From<bool> for MarkedScalarNode {
 }
 
 macro_rules! scalar_from_to_number {
-    ($t:ident, $as:ident) => {
+    ($t:ident, $as:ident, $as_yaml11:ident) => {
         impl From<$t> for MarkedScalarNode {
             doc_comment!(
                 concat!(
@@ -847,22 +2645,104 @@ assert_eq!(node.as_"#,
                     $t::from_str(&self.value).ok()
                 }
             );
+
+            doc_comment!(
+                concat!(
+                    "Treat the scalar node as ",
+                    stringify!($t),
+                    r#", following the YAML 1.1 rules.
+
+As well as everything accepted by [`"#,
+                    stringify!($as),
+                    r#"()`][Self::"#,
+                    stringify!($as),
+                    r#"], plain scalars are read in the
+two legacy integer forms which YAML 1.1 permits, exactly as the
+`yaml11_numbers` deserialisation option reads them: octal with a leading
+zero, and sexagesimal (base 60) with colon separated digits.  Quoted
+scalars are never read in those forms.
+
+```
+# use marked_yaml::*;
+let node = parse_yaml(0, "mode: 017\ntime: 1:30\n").unwrap();
+let map = node.as_mapping().unwrap();
+assert_eq!(map.get_scalar("mode").unwrap().as_"#,
+                    stringify!($t),
+                    r#"(), Some(17));
+assert_eq!(map.get_scalar("mode").unwrap().as_yaml11_"#,
+                    stringify!($t),
+                    r#"(), Some(0o17));
+assert_eq!(map.get_scalar("time").unwrap().as_"#,
+                    stringify!($t),
+                    r#"(), None);
+assert_eq!(map.get_scalar("time").unwrap().as_yaml11_"#,
+                    stringify!($t),
+                    r#"(), Some(90));
+```"#
+                ),
+                pub fn $as_yaml11(&self) -> Option<$t> {
+                    match yaml11_integer(&self.value).filter(|_| self.may_coerce) {
+                        Some(value) => $t::try_from(value).ok(),
+                        None => self.$as(),
+                    }
+                }
+            );
         }
     };
 }
 
-scalar_from_to_number!(i8, as_i8);
-scalar_from_to_number!(i16, as_i16);
-scalar_from_to_number!(i32, as_i32);
-scalar_from_to_number!(i64, as_i64);
-scalar_from_to_number!(i128, as_i128);
-scalar_from_to_number!(isize, as_isize);
-scalar_from_to_number!(u8, as_u8);
-scalar_from_to_number!(u16, as_u16);
-scalar_from_to_number!(u32, as_u32);
-scalar_from_to_number!(u64, as_u64);
-scalar_from_to_number!(u128, as_u128);
-scalar_from_to_number!(usize, as_usize);
+scalar_from_to_number!(i8, as_i8, as_yaml11_i8);
+scalar_from_to_number!(i16, as_i16, as_yaml11_i16);
+scalar_from_to_number!(i32, as_i32, as_yaml11_i32);
+scalar_from_to_number!(i64, as_i64, as_yaml11_i64);
+scalar_from_to_number!(i128, as_i128, as_yaml11_i128);
+scalar_from_to_number!(isize, as_isize, as_yaml11_isize);
+scalar_from_to_number!(u8, as_u8, as_yaml11_u8);
+scalar_from_to_number!(u16, as_u16, as_yaml11_u16);
+scalar_from_to_number!(u32, as_u32, as_yaml11_u32);
+scalar_from_to_number!(u64, as_u64, as_yaml11_u64);
+scalar_from_to_number!(u128, as_u128, as_yaml11_u128);
+scalar_from_to_number!(usize, as_usize, as_yaml11_usize);
+
+// YAML 1.1 reads integers with a leading zero as octal, and integers whose
+// digits are separated by colons as base 60, so `0755` is 493 and `1:30` is
+// 90.  Anything else, including ordinary decimals, gives None and is left to
+// the usual number parsing.
+pub(crate) fn yaml11_integer(s: &str) -> Option<i128> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let digits = digits.replace('_', "");
+    let all_digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    let value = if digits.contains(':') {
+        let mut parts = digits.split(':');
+        let first = parts.next()?;
+        if !all_digits(first) || first.starts_with('0') {
+            return None;
+        }
+        let mut value: i128 = first.parse().ok()?;
+        for part in parts {
+            if !all_digits(part) || part.len() > 2 {
+                return None;
+            }
+            let part: i128 = part.parse().ok()?;
+            if part >= 60 {
+                return None;
+            }
+            value = value.checked_mul(60)?.checked_add(part)?;
+        }
+        value
+    } else if digits.len() > 1
+        && digits.starts_with('0')
+        && digits.bytes().all(|b| (b'0'..=b'7').contains(&b))
+    {
+        i128::from_str_radix(&digits, 8).ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
 
 impl Deref for MarkedScalarNode {
     type Target = str;
@@ -894,10 +2774,7 @@ impl MarkedSequenceNode {
     /// let node = MarkedSequenceNode::new_empty(Span::new_blank());
     /// ```
     pub fn new_empty(span: Span) -> Self {
-        Self {
-            span,
-            value: Vec::new(),
-        }
+        Self::new(span, Vec::new())
     }
 
     /// Create a new sequence node from a vector of nodes
@@ -907,7 +2784,43 @@ impl MarkedSequenceNode {
     /// let node = MarkedSequenceNode::new(Span::new_blank(), Vec::new());
     /// ```
     pub fn new(span: Span, value: Vec<Node>) -> Self {
-        Self { span, value }
+        Self {
+            span,
+            value,
+            flow: false,
+            anchor: None,
+            alias_of: None,
+        }
+    }
+
+    /// Whether this sequence was written in flow style
+    ///
+    /// Flow sequences are written inline, `[like, this]`, whereas block
+    /// sequences have one `- entry` per line.  Everything nested inside a
+    /// flow container is also flow style.  Sequences created in code are
+    /// block style unless told otherwise.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "flow: [a, b]\nblock:\n  - a\n  - b\n").unwrap();
+    /// let map = node.as_mapping().unwrap();
+    /// assert!(map.get_sequence("flow").unwrap().is_flow());
+    /// assert!(!map.get_sequence("block").unwrap().is_flow());
+    /// ```
+    pub fn is_flow(&self) -> bool {
+        self.flow
+    }
+
+    /// Set whether this sequence is flow style
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// let mut node = MarkedSequenceNode::new_empty(Span::new_blank());
+    /// node.set_flow(true);
+    /// assert!(node.is_flow());
+    /// ```
+    pub fn set_flow(&mut self, flow: bool) {
+        self.flow = flow;
     }
 
     /// Get the node at the given index
@@ -976,6 +2889,84 @@ impl MarkedSequenceNode {
     pub fn get_mapping(&self, index: usize) -> Option<&MarkedMappingNode> {
         self.get_node(index).and_then(Node::as_mapping)
     }
+
+    /// Get the node at the given index, mutably
+    ///
+    /// If the index is invalid then None will be returned
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// let mut seq: MarkedSequenceNode = vec!["foobar"].into_iter().collect();
+    /// *seq.get_node_mut(0).unwrap() = Node::from("replaced");
+    /// assert_eq!(seq.get_scalar(0).unwrap().as_str(), "replaced");
+    /// ```
+    pub fn get_node_mut(&mut self, index: usize) -> Option<&mut Node> {
+        self.value.get_mut(index)
+    }
+
+    /// Get the scalar at the given index, mutably
+    ///
+    /// If the index is invalid, or the node at that index is not a scalar
+    /// node, then None will be returned.
+    pub fn get_scalar_mut(&mut self, index: usize) -> Option<&mut MarkedScalarNode> {
+        self.get_node_mut(index).and_then(Node::as_scalar_mut)
+    }
+
+    /// Get the sequence at the given index, mutably
+    ///
+    /// If the index is invalid, or the node at that index is not a sequence
+    /// node, then None will be returned.
+    pub fn get_sequence_mut(&mut self, index: usize) -> Option<&mut MarkedSequenceNode> {
+        self.get_node_mut(index).and_then(Node::as_sequence_mut)
+    }
+
+    /// Get the mapping at the given index, mutably
+    ///
+    /// If the index is invalid, or the node at that index is not a mapping
+    /// node, then None will be returned.
+    pub fn get_mapping_mut(&mut self, index: usize) -> Option<&mut MarkedMappingNode> {
+        self.get_node_mut(index).and_then(Node::as_mapping_mut)
+    }
+
+    /// Remove the node at the given index, returning it
+    ///
+    /// Later items move down to fill the gap, keeping their order.  Unlike
+    /// [`Vec::remove`], which is still reachable through [`Deref`], an
+    /// invalid index gives None rather than panicking.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let mut node = parse_yaml(0, "list: [a, b, c]\n").unwrap();
+    /// let list = node.as_mapping_mut().unwrap().get_sequence_mut("list").unwrap();
+    /// assert_eq!(list.remove_node(1).unwrap().as_scalar().unwrap().as_str(), "b");
+    /// assert_eq!(list.len(), 2);
+    /// assert!(list.remove_node(2).is_none());
+    /// ```
+    pub fn remove_node(&mut self, index: usize) -> Option<Node> {
+        (index < self.value.len()).then(|| self.value.remove(index))
+    }
+
+    /// The span covering every item in this sequence
+    ///
+    /// This runs from the start of the first item to the end of the last.
+    /// Scalars don't know where they end, so if the last item is a scalar
+    /// then this ends where that scalar starts.  An empty sequence has a
+    /// blank content span.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "list:\n  - one\n  - [two]\n").unwrap();
+    /// let list = node.as_mapping().unwrap().get_sequence("list").unwrap();
+    /// assert_eq!(
+    ///     list.content_span(),
+    ///     Span::new_with_marks(Marker::new(0, 2, 5), Marker::new(0, 3, 9))
+    /// );
+    /// ```
+    pub fn content_span(&self) -> Span {
+        self.value
+            .iter()
+            .fold(Span::new_blank(), |span, v| span.union(&v.span().extent()))
+    }
 }
 
 impl Deref for MarkedSequenceNode {
@@ -1011,7 +3002,7 @@ where
                 end: value[value.len() - 1].span().end,
             },
         };
-        Self { span, value }
+        Self::new(span, value)
     }
 }
 
@@ -1036,7 +3027,7 @@ where
                 Span { start, end }
             }
         };
-        Self { span, value }
+        Self::new(span, value)
     }
 }
 
@@ -1048,10 +3039,7 @@ impl MarkedMappingNode {
     /// let node = MarkedMappingNode::new_empty(Span::new_blank());
     /// ```
     pub fn new_empty(span: Span) -> Self {
-        Self {
-            span,
-            value: LinkedHashMap::new(),
-        }
+        Self::new(span, LinkedHashMap::new())
     }
 
     /// Create a new mapping node from the given hash table
@@ -1062,7 +3050,117 @@ impl MarkedMappingNode {
     /// let node = MarkedMappingNode::new(Span::new_blank(), LinkedHashMap::new());
     /// ```
     pub fn new(span: Span, value: MappingHash) -> Self {
-        Self { span, value }
+        Self {
+            span,
+            value,
+            flow: false,
+            set: false,
+            anchor: None,
+            alias_of: None,
+        }
+    }
+
+    /// Whether this mapping was written in flow style
+    ///
+    /// Flow mappings are written inline, `{like: this}`, whereas block
+    /// mappings have one `key: value` per line.  Everything nested inside
+    /// a flow container is also flow style.  Mappings created in code are
+    /// block style unless told otherwise.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "flow: {a: b}\nblock:\n  a: b\n").unwrap();
+    /// let map = node.as_mapping().unwrap();
+    /// assert!(!map.is_flow());
+    /// assert!(map.get_mapping("flow").unwrap().is_flow());
+    /// assert!(!map.get_mapping("block").unwrap().is_flow());
+    /// ```
+    pub fn is_flow(&self) -> bool {
+        self.flow
+    }
+
+    /// Set whether this mapping is flow style
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// let mut node = MarkedMappingNode::new_empty(Span::new_blank());
+    /// node.set_flow(true);
+    /// assert!(node.is_flow());
+    /// ```
+    pub fn set_flow(&mut self, flow: bool) {
+        self.flow = flow;
+    }
+
+    /// Whether this mapping was tagged `!!set`
+    ///
+    /// A set is a mapping whose values are all null, and only its keys
+    /// matter.  Sets can be deserialized into collections such as
+    /// `HashSet`, from their keys.  Like the style, this plays no part in
+    /// equality.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "tagged: !!set {a, b}\nplain: {a, b}\n").unwrap();
+    /// let map = node.as_mapping().unwrap();
+    /// assert!(map.get_mapping("tagged").unwrap().is_set());
+    /// assert!(!map.get_mapping("plain").unwrap().is_set());
+    /// ```
+    pub fn is_set(&self) -> bool {
+        self.set
+    }
+
+    /// Set whether this mapping is tagged `!!set`
+    pub fn set_is_set(&mut self, set: bool) {
+        self.set = set;
+    }
+
+    /// Retain only the entries for which the predicate returns true
+    ///
+    /// Entries which are removed are dropped, and the order of the remaining
+    /// entries is unchanged, as are their spans.  This does not look inside
+    /// the values, see [`Node::retain_recursive`] for that.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let mut node = parse_yaml(0, "name: api\nx-internal: true\n").unwrap();
+    /// let map = node.as_mapping_mut().unwrap();
+    /// map.retain(|key, _| !key.as_str().starts_with("x-"));
+    /// assert_eq!(map.len(), 1);
+    /// assert!(map.get_node("x-internal").is_none());
+    /// ```
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&MarkedScalarNode, &Node) -> bool,
+    {
+        self.value = std::mem::take(&mut self.value)
+            .into_iter()
+            .filter(|(key, value)| f(key, value))
+            .collect();
+    }
+
+    /// Get the entry for the given key, for in-place manipulation
+    ///
+    /// This is the [`linked_hash_map`] entry API, except that the key may be
+    /// anything which converts into a scalar node, such as a string.  New
+    /// entries are added to the end of the mapping, and since they were not
+    /// parsed their keys have blank spans.  Note that replacing a value via
+    /// an occupied entry's `insert` also moves it to the end; assign through
+    /// `get_mut` to keep the original order.
+    ///
+    /// ```
+    /// # use marked_yaml::*;
+    /// let mut node = parse_yaml(0, "name: api\n").unwrap();
+    /// let map = node.as_mapping_mut().unwrap();
+    /// map.entry("retries").or_insert(Node::from("3"));
+    /// map.entry("name").or_insert(Node::from("ignored"));
+    /// assert_eq!(map.get_scalar("retries").unwrap().as_str(), "3");
+    /// assert_eq!(map.get_scalar("name").unwrap().as_str(), "api");
+    /// ```
+    pub fn entry<K>(&mut self, key: K) -> linked_hash_map::Entry<'_, MarkedScalarNode, Node>
+    where
+        K: Into<MarkedScalarNode>,
+    {
+        self.value.entry(key.into())
     }
 
     /// Get the node for the given string key
@@ -1084,63 +3182,170 @@ impl MarkedMappingNode {
         self.value.get(index)
     }
 
-    /// Get the scalar for the given string key
+    /// Get the scalar for the given string key
+    ///
+    /// If the key is not found, or the node for that key is not a scalar
+    /// node, then None will be returned.
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// # use marked_yaml::parse_yaml;
+    /// let node = parse_yaml(0, "{key: value}").unwrap();
+    /// let map = node.as_mapping().unwrap();
+    /// assert_eq!(map.get_scalar("key")
+    ///     .map(MarkedScalarNode::as_str)
+    ///     .unwrap(),
+    ///     "value");
+    /// ```
+    pub fn get_scalar(&self, index: &str) -> Option<&MarkedScalarNode> {
+        self.get_node(index).and_then(Node::as_scalar)
+    }
+
+    /// Get the sequence at the given index
+    ///
+    /// If the key is not found, or the node for that key is not a sequence
+    /// node, then None will be returned.
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// # use marked_yaml::parse_yaml;
+    /// let node = parse_yaml(0, "{key: [value]}").unwrap();
+    /// let map = node.as_mapping().unwrap();
+    /// assert_eq!(map.get_sequence("key")
+    ///     .and_then(|s| s.get_scalar(0))
+    ///     .map(MarkedScalarNode::as_str)
+    ///     .unwrap(),
+    ///     "value");
+    /// ```
+    pub fn get_sequence(&self, index: &str) -> Option<&MarkedSequenceNode> {
+        self.get_node(index).and_then(Node::as_sequence)
+    }
+
+    /// Get the mapping at the given index
+    ///
+    /// If the key is not found, or the node for that key is not a mapping
+    /// node, then None will be returned.
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// # use marked_yaml::parse_yaml;
+    /// let node = parse_yaml(0, "{key: {inner: value}}").unwrap();
+    /// let map = node.as_mapping().unwrap();
+    /// assert_eq!(map.get_mapping("key")
+    ///     .and_then(|m| m.get_scalar("inner"))
+    ///     .map(MarkedScalarNode::as_str)
+    ///     .unwrap(),
+    ///     "value");
+    /// ```
+    pub fn get_mapping(&self, index: &str) -> Option<&MarkedMappingNode> {
+        self.get_node(index).and_then(Node::as_mapping)
+    }
+
+    /// Get the node for the given string key, mutably
+    ///
+    /// If the index is not found then None is returned.
+    ///
+    /// ```
+    /// # use marked_yaml::types::*;
+    /// # use marked_yaml::parse_yaml;
+    /// let mut node = parse_yaml(0, "{key: value}").unwrap();
+    /// let map = node.as_mapping_mut().unwrap();
+    /// *map.get_node_mut("key").unwrap() = Node::from("replaced");
+    /// assert_eq!(map.get_scalar("key").unwrap().as_str(), "replaced");
+    /// ```
+    pub fn get_node_mut(&mut self, index: &str) -> Option<&mut Node> {
+        self.value.get_mut(index)
+    }
+
+    /// Get the scalar for the given string key, mutably
+    ///
+    /// If the key is not found, or the node for that key is not a scalar
+    /// node, then None will be returned.
+    pub fn get_scalar_mut(&mut self, index: &str) -> Option<&mut MarkedScalarNode> {
+        self.get_node_mut(index).and_then(Node::as_scalar_mut)
+    }
+
+    /// Get the sequence for the given string key, mutably
+    ///
+    /// If the key is not found, or the node for that key is not a sequence
+    /// node, then None will be returned.
+    pub fn get_sequence_mut(&mut self, index: &str) -> Option<&mut MarkedSequenceNode> {
+        self.get_node_mut(index).and_then(Node::as_sequence_mut)
+    }
+
+    /// Get the mapping for the given string key, mutably
     ///
-    /// If the key is not found, or the node for that key is not a scalar
+    /// If the key is not found, or the node for that key is not a mapping
     /// node, then None will be returned.
+    pub fn get_mapping_mut(&mut self, index: &str) -> Option<&mut MarkedMappingNode> {
+        self.get_node_mut(index).and_then(Node::as_mapping_mut)
+    }
+
+    /// Remove the entry for the given string key, returning its value
+    ///
+    /// The remaining entries keep their order.  If the key is not found
+    /// then None will be returned.  Unlike [`LinkedHashMap::remove`], which
+    /// is still reachable through [`Deref`], this looks the key up by its
+    /// string value.
     ///
     /// ```
-    /// # use marked_yaml::types::*;
-    /// # use marked_yaml::parse_yaml;
-    /// let node = parse_yaml(0, "{key: value}").unwrap();
-    /// let map = node.as_mapping().unwrap();
-    /// assert_eq!(map.get_scalar("key")
-    ///     .map(MarkedScalarNode::as_str)
-    ///     .unwrap(),
-    ///     "value");
+    /// # use marked_yaml::*;
+    /// let mut node = parse_yaml(0, "a: 1\nb: 2\nc: 3\n").unwrap();
+    /// let map = node.as_mapping_mut().unwrap();
+    /// assert_eq!(map.remove_node("b").unwrap().as_scalar().unwrap().as_str(), "2");
+    /// assert!(map.remove_node("b").is_none());
+    /// assert_eq!(map.keys().map(|k| k.as_str()).collect::<Vec<_>>(), ["a", "c"]);
     /// ```
-    pub fn get_scalar(&self, index: &str) -> Option<&MarkedScalarNode> {
-        self.get_node(index).and_then(Node::as_scalar)
+    pub fn remove_node(&mut self, index: &str) -> Option<Node> {
+        self.value.remove(index)
     }
 
-    /// Get the sequence at the given index
+    /// Iterate the entries of the mapping along with the span of each entry
     ///
-    /// If the key is not found, or the node for that key is not a sequence
-    /// node, then None will be returned.
+    /// The span of an entry is the union of the span of the key and the span
+    /// of the value, which is what you want to underline in a diagnostic
+    /// about the entry as a whole.  Note that scalar values do not know where
+    /// they end, so entries with scalar values only know where they start.
     ///
     /// ```
     /// # use marked_yaml::types::*;
     /// # use marked_yaml::parse_yaml;
     /// let node = parse_yaml(0, "{key: [value]}").unwrap();
     /// let map = node.as_mapping().unwrap();
-    /// assert_eq!(map.get_sequence("key")
-    ///     .and_then(|s| s.get_scalar(0))
-    ///     .map(MarkedScalarNode::as_str)
-    ///     .unwrap(),
-    ///     "value");
+    /// let (key, _value, span) = map.entries_spanned().next().unwrap();
+    /// assert_eq!(key.as_str(), "key");
+    /// assert_eq!(
+    ///     span,
+    ///     Span::new_with_marks(Marker::new(0, 1, 2), Marker::new(0, 1, 13))
+    /// );
     /// ```
-    pub fn get_sequence(&self, index: &str) -> Option<&MarkedSequenceNode> {
-        self.get_node(index).and_then(Node::as_sequence)
+    pub fn entries_spanned(&self) -> impl Iterator<Item = (&MarkedScalarNode, &Node, Span)> {
+        self.value
+            .iter()
+            .map(|(k, v)| (k, v, k.span().union(v.span())))
     }
 
-    /// Get the mapping at the given index
+    /// The span covering every key and value in this mapping
     ///
-    /// If the key is not found, or the node for that key is not a mapping
-    /// node, then None will be returned.
+    /// Where the mapping's own span runs from its first token to wherever
+    /// the parser noticed it had ended, this runs from the first key to the
+    /// end of the last value.  Scalars don't know where they end, so if the
+    /// last value is a scalar then this ends where that scalar starts.  An
+    /// empty mapping has a blank content span.
     ///
     /// ```
-    /// # use marked_yaml::types::*;
-    /// # use marked_yaml::parse_yaml;
-    /// let node = parse_yaml(0, "{key: {inner: value}}").unwrap();
-    /// let map = node.as_mapping().unwrap();
-    /// assert_eq!(map.get_mapping("key")
-    ///     .and_then(|m| m.get_scalar("inner"))
-    ///     .map(MarkedScalarNode::as_str)
-    ///     .unwrap(),
-    ///     "value");
+    /// # use marked_yaml::*;
+    /// let node = parse_yaml(0, "outer:\n  a: 1\n  b: last\nnext: 2\n").unwrap();
+    /// let outer = node.as_mapping().unwrap().get_mapping("outer").unwrap();
+    /// assert_eq!(
+    ///     outer.content_span(),
+    ///     Span::new_with_marks(Marker::new(0, 2, 3), Marker::new(0, 3, 6))
+    /// );
     /// ```
-    pub fn get_mapping(&self, index: &str) -> Option<&MarkedMappingNode> {
-        self.get_node(index).and_then(Node::as_mapping)
+    pub fn content_span(&self) -> Span {
+        self.value.iter().fold(Span::new_blank(), |span, (k, v)| {
+            span.union(&k.span().extent()).union(&v.span().extent())
+        })
     }
 }
 
@@ -1201,7 +3406,7 @@ where
                 Span { start, end }
             }
         };
-        Self { span, value }
+        Self::new(span, value)
     }
 }
 
@@ -1266,7 +3471,10 @@ impl TryFrom<YamlNode> for Node {
 
 impl From<MarkedScalarNode> for YamlNode {
     fn from(value: MarkedScalarNode) -> Self {
-        YamlNode::String(value.value)
+        match value.complex_key {
+            Some(key) => (*key).into(),
+            None => YamlNode::String(value.value),
+        }
     }
 }
 
@@ -1298,6 +3506,179 @@ impl From<Node> for YamlNode {
     }
 }
 
+struct CompactNode<'a>(&'a Node);
+struct CompactScalar<'a>(&'a MarkedScalarNode);
+struct CompactStart<'a>(&'a Span);
+
+impl Display for CompactStart<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0.start() {
+            Some(start) => write!(f, "@{}", start),
+            None => Ok(()),
+        }
+    }
+}
+
+impl fmt::Debug for CompactScalar<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "scalar({:?}){}",
+            self.0.as_str(),
+            CompactStart(self.0.span())
+        )
+    }
+}
+
+impl fmt::Debug for CompactNode<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Node::Scalar(msn) => CompactScalar(msn).fmt(f),
+            Node::Sequence(msn) => {
+                write!(f, "sequence{} ", CompactStart(msn.span()))?;
+                f.debug_list().entries(msn.iter().map(CompactNode)).finish()
+            }
+            Node::Mapping(mmn) => {
+                write!(f, "mapping{} ", CompactStart(mmn.span()))?;
+                f.debug_map()
+                    .entries(mmn.iter().map(|(k, v)| (CompactScalar(k), CompactNode(v))))
+                    .finish()
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde-yaml")]
+impl Node {
+    /// Convert this node into an unmarked `serde_yaml::Value`
+    ///
+    /// This allows code written against `serde_yaml` to be reused on
+    /// data loaded by this crate.  Scalars are resolved in the same way
+    /// as the YAML core schema would: `null` and `~` become nulls, the
+    /// booleans accepted by [`MarkedScalarNode::as_bool`] become booleans,
+    /// and integers and floats become numbers.  Everything else is a
+    /// string.  Mapping keys are always strings, since that is all this
+    /// crate permits.
+    ///
+    /// Naturally all span information is lost in the conversion.
+    ///
+    /// ```
+    /// # use marked_yaml::parse_yaml;
+    /// let node = parse_yaml(0, "{answer: 42, question: unknown}").unwrap();
+    /// let value = node.to_serde_yaml_value();
+    /// assert_eq!(value["answer"].as_u64(), Some(42));
+    /// assert_eq!(value["question"].as_str(), Some("unknown"));
+    /// ```
+    pub fn to_serde_yaml_value(&self) -> serde_yaml::Value {
+        use serde_yaml::Value;
+        match self {
+            Node::Scalar(msn) => msn.to_serde_yaml_value(),
+            Node::Sequence(msn) => {
+                Value::Sequence(msn.iter().map(Node::to_serde_yaml_value).collect())
+            }
+            Node::Mapping(mmn) => Value::Mapping(
+                mmn.iter()
+                    .map(|(k, v)| {
+                        (
+                            Value::String(k.as_str().to_string()),
+                            v.to_serde_yaml_value(),
+                        )
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "serde-yaml")]
+impl MarkedScalarNode {
+    fn to_serde_yaml_value(&self) -> serde_yaml::Value {
+        use serde_yaml::Value;
+        match self.resolve() {
+            ResolvedScalar::Null => Value::Null,
+            ResolvedScalar::Bool(b) => Value::Bool(b),
+            ResolvedScalar::Int(i) => Value::Number(i.into()),
+            ResolvedScalar::UInt(u) => Value::Number(u.into()),
+            ResolvedScalar::Float(f) => Value::Number(f.into()),
+            ResolvedScalar::Str(s) => Value::String(s.to_string()),
+        }
+    }
+}
+
+// The plain scalars which YAML 1.1 reads as booleans.  The YAML 1.2 core
+// schema (and so `MarkedScalarNode::as_bool`) only has `true` and `false`,
+// so the rest are the root of "the Norway problem", where `NO` unexpectedly
+// becomes `false` for tools which still follow YAML 1.1.
+pub(crate) fn is_yaml11_boolean(value: &str) -> bool {
+    matches!(
+        value,
+        "y" | "Y"
+            | "yes"
+            | "Yes"
+            | "YES"
+            | "n"
+            | "N"
+            | "no"
+            | "No"
+            | "NO"
+            | "true"
+            | "True"
+            | "TRUE"
+            | "false"
+            | "False"
+            | "FALSE"
+            | "on"
+            | "On"
+            | "ON"
+            | "off"
+            | "Off"
+            | "OFF"
+    )
+}
+
+/// The value a scalar resolves to, for consumers which don't say what
+/// type they want
+#[cfg(feature = "serde")]
+#[derive(Debug, PartialEq)]
+pub(crate) enum ResolvedScalar<'a> {
+    Null,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Str(&'a str),
+}
+
+#[cfg(feature = "serde")]
+impl MarkedScalarNode {
+    // Resolve plain scalars in the same way as the YAML core schema would:
+    // `null`, `~`, and empty scalars are nulls, the booleans accepted by
+    // `as_bool` are booleans, and integers and floats are numbers.
+    // Everything else, including any quoted scalar, is a string.
+    pub(crate) fn resolve(&self) -> ResolvedScalar<'_> {
+        let s = self.as_str();
+        if !self.may_coerce {
+            ResolvedScalar::Str(s)
+        } else if self.is_null() {
+            ResolvedScalar::Null
+        } else if let Some(b) = self.as_bool() {
+            ResolvedScalar::Bool(b)
+        } else if let Some(i) = self.as_i64() {
+            ResolvedScalar::Int(i)
+        } else if let Some(u) = self.as_u64() {
+            ResolvedScalar::UInt(u)
+        } else if let Some(f) = s
+            .parse::<f64>()
+            .ok()
+            .filter(|_| s.chars().any(|c| c.is_ascii_digit()))
+        {
+            ResolvedScalar::Float(f)
+        } else {
+            ResolvedScalar::Str(s)
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::super::*;
@@ -1317,19 +3698,751 @@ mod test {
         assert_eq!(format!("{}", rendered), "name:1:2");
     }
 
+    #[test]
+    fn marker_advance() {
+        let marker = Marker::new(3, 2, 5);
+        assert_eq!(marker.advance(0), marker);
+        assert_eq!(marker.advance(1), Marker::new(3, 2, 6));
+        assert_eq!(marker.advance(4).advance(3), Marker::new(3, 2, 12));
+        let node = crate::parse_yaml(0, "a: [\"é\", bee]\n").unwrap();
+        let seq = node.as_mapping().unwrap().get_sequence("a").unwrap();
+        let bee = seq.get_scalar(1).unwrap();
+        let end = bee
+            .span()
+            .start()
+            .unwrap()
+            .advance(bee.as_str().chars().count());
+        // The end is just after the token, where the closing bracket is
+        assert_eq!(end, Marker::new(0, 1, 13));
+        assert_eq!(seq.span().end(), Some(&end));
+    }
+
+    #[test]
+    fn node_get_or_null() {
+        let node = crate::parse_yaml(0, "a:\n  b: [1]\n  c: ~\n  d: '~'\n").unwrap();
+        assert!(node.get_or_null("a").is_mapping());
+        assert!(node.get_or_null("a").get_or_null("b").is_sequence());
+        assert!(node.get_or_null("a").get_or_null("c").is_null());
+        assert!(!node.get_or_null("a").get_or_null("c").span().is_blank());
+        assert!(!node.get_or_null("a").get_or_null("d").is_null());
+        // Missing keys, and keys into things which aren't mappings
+        let missing = node.get_or_null("x").get_or_null("y").get_or_null("z");
+        assert!(missing.is_null());
+        assert!(missing.span().is_blank());
+        let scalar = node.get_or_null("a").get_or_null("b").get_or_null("0");
+        assert!(scalar.is_null());
+        assert!(std::ptr::eq(missing, scalar));
+    }
+
     #[test]
     fn basic_span_checks() {
         let span = Span::new_blank();
         assert_eq!(span.start(), None);
         assert_eq!(span.end(), None);
+        assert!(span.is_blank());
         let mark = Marker::new(0, 1, 2);
         let mark2 = Marker::new(3, 4, 5);
         let span = Span::new_start(mark);
         assert_eq!(span.start(), Some(&mark));
         assert_eq!(span.end(), None);
+        assert!(!span.is_blank());
         let span = Span::new_with_marks(mark, mark2);
         assert_eq!(span.start(), Some(&mark));
         assert_eq!(span.end(), Some(&mark2));
+        assert!(!span.is_blank());
+        let mut span = Span::new_blank();
+        span.set_end(Some(mark2));
+        assert!(!span.is_blank());
+    }
+
+    #[test]
+    fn default_markers_and_spans() {
+        let marker = Marker::default();
+        assert_eq!(marker.source(), 0);
+        assert_eq!(marker.line(), 1);
+        assert_eq!(marker.column(), 1);
+        let span = Span::default();
+        assert_eq!(span, Span::new_blank());
+        assert_eq!(span.start(), None);
+        assert_eq!(span.end(), None);
+        let node = MarkedScalarNode::new(Span::default(), "value");
+        assert!(node.span().is_blank());
+    }
+
+    #[test]
+    fn span_union() {
+        let blank = Span::new_blank();
+        let a = Marker::new(0, 1, 5);
+        let b = Marker::new(0, 2, 1);
+        let c = Marker::new(0, 3, 7);
+        assert_eq!(blank.union(&blank), blank);
+        assert_eq!(Span::new_start(a).union(&blank), Span::new_start(a));
+        assert_eq!(blank.union(&Span::new_start(a)), Span::new_start(a));
+        assert_eq!(
+            Span::new_with_marks(b, c).union(&Span::new_with_marks(a, b)),
+            Span::new_with_marks(a, c)
+        );
+        assert_eq!(
+            Span::new_start(b).union(&Span::new_start(a)),
+            Span::new_start(a)
+        );
+    }
+
+    #[test]
+    fn node_expand_env() {
+        let lookup = |name: &str| match name {
+            "HOST" => Some("example.com".to_string()),
+            "PORT" => Some("8080".to_string()),
+            _ => None,
+        };
+        let node = parse_yaml(
+            0,
+            "url: http://${HOST}:${PORT}/\nplain: no refs\nodd: ${HOST\nlist: ['${HOST}', '${USER}']\n",
+        )
+        .unwrap();
+
+        let expanded = node.expand_env(false, lookup).unwrap();
+        let map = expanded.as_mapping().unwrap();
+        let url = map.get_scalar("url").unwrap();
+        assert_eq!(url.as_str(), "http://example.com:8080/");
+        assert_eq!(url.span().start(), Some(&Marker::new(0, 1, 6)));
+        assert_eq!(map.get_scalar("plain").unwrap().as_str(), "no refs");
+        assert_eq!(map.get_scalar("odd").unwrap().as_str(), "${HOST");
+        let list = map.get_sequence("list").unwrap();
+        assert_eq!(list.get_scalar(0).unwrap().as_str(), "example.com");
+        assert!(!list.get_scalar(0).unwrap().may_coerce());
+        assert_eq!(
+            list.get_scalar(0).unwrap().style(),
+            ScalarStyle::SingleQuoted
+        );
+        assert_eq!(list.get_scalar(1).unwrap().as_str(), "${USER}");
+        assert_eq!(list.span(), node.pointer("/list").unwrap().span());
+
+        let err = node.expand_env(true, lookup).err().unwrap();
+        assert_eq!(
+            err,
+            ExpandEnvError::UndefinedVariable(
+                "USER".to_string(),
+                Span::new_start(Marker::new(0, 4, 19))
+            )
+        );
+        assert_eq!(format!("{err}"), "4:19: Variable `USER` is not defined");
+
+        let node = parse_yaml(0, "quoted: \"${HOST}\"\nblock: |-2\n   ${PORT}\n").unwrap();
+        let expanded = node.expand_env(true, lookup).unwrap();
+        let map = expanded.as_mapping().unwrap();
+        let quoted = map.get_scalar("quoted").unwrap();
+        assert_eq!(quoted.as_str(), "example.com");
+        assert_eq!(quoted.style(), ScalarStyle::DoubleQuoted);
+        let block = map.get_scalar("block").unwrap();
+        assert_eq!(block.as_str(), " 8080");
+        assert_eq!(block.style(), ScalarStyle::Literal);
+        assert_eq!(block.block_chomping(), Some(Chomping::Strip));
+        assert_eq!(block.explicit_indent(), Some(2));
+    }
+
+    #[test]
+    fn node_merge() {
+        const BASE: &str = r#"
+name: base
+server:
+  host: localhost
+  port: 80
+  tls: {enabled: false}
+paths: [/usr/lib]
+"#;
+        const OVERRIDES: &str = r#"
+server:
+  port: 8080
+  tls: {enabled: true, cert: server.pem}
+paths: [/opt/lib]
+extra: added
+"#;
+        let base = parse_yaml(0, BASE).unwrap();
+        let overrides = parse_yaml(1, OVERRIDES).unwrap();
+
+        let mut merged = base.clone();
+        merged.merge(&overrides);
+        let get = |path| merged.pointer(path).unwrap();
+        assert_eq!(get("/name").as_scalar().unwrap().as_str(), "base");
+        assert_eq!(
+            get("/server/host").as_scalar().unwrap().as_str(),
+            "localhost"
+        );
+        assert_eq!(get("/server/port").as_scalar().unwrap().as_str(), "8080");
+        assert_eq!(
+            get("/server/port").span().start(),
+            Some(&Marker::new(1, 3, 9))
+        );
+        assert_eq!(
+            get("/server/tls/enabled").as_scalar().unwrap().as_str(),
+            "true"
+        );
+        assert_eq!(
+            get("/server/tls/cert").as_scalar().unwrap().as_str(),
+            "server.pem"
+        );
+        assert_eq!(
+            get("/server").span(),
+            base.pointer("/server").unwrap().span()
+        );
+        assert_eq!(get("/paths"), overrides.pointer("/paths").unwrap());
+        assert_eq!(get("/extra").as_scalar().unwrap().as_str(), "added");
+        let keys: Vec<_> = merged
+            .as_mapping()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        assert_eq!(keys, vec!["name", "server", "paths", "extra"]);
+
+        let mut merged = base.clone();
+        merged.merge_with(&overrides, SequenceMerge::Concat);
+        let paths = merged.pointer("/paths").unwrap();
+        assert_eq!(paths.span(), base.pointer("/paths").unwrap().span());
+        let paths: Vec<_> = paths
+            .as_sequence()
+            .unwrap()
+            .iter()
+            .map(|n| n.as_scalar().unwrap().as_str())
+            .collect();
+        assert_eq!(paths, vec!["/usr/lib", "/opt/lib"]);
+
+        let mut scalar: Node = "replaced".into();
+        scalar.merge(&base);
+        assert_eq!(scalar, base);
+    }
+
+    #[test]
+    fn node_indentation() {
+        const DOC: &str =
+            "top:\n  middle:\n      bottom: value\n  list:\n    - item\n  flow: {a: b}\n";
+        let node = parse_yaml(0, DOC).unwrap();
+        let indent = |path| node.pointer(path).unwrap().indentation();
+        assert_eq!(indent(""), Some(0));
+        assert_eq!(indent("/top"), Some(2));
+        assert_eq!(indent("/top/middle"), Some(6));
+        assert_eq!(indent("/top/middle/bottom"), Some(14));
+        assert_eq!(indent("/top/list"), Some(4));
+        assert_eq!(indent("/top/list/0"), Some(6));
+        assert_eq!(indent("/top/flow"), None);
+        let blank: Node = MarkedMappingNode::new_empty(Span::new_blank()).into();
+        assert_eq!(blank.indentation(), None);
+    }
+
+    #[test]
+    fn node_clone_without_spans() {
+        use std::collections::hash_map::DefaultHasher;
+        fn hash_of(node: &Node) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            node.hash(&mut hasher);
+            hasher.finish()
+        }
+        let first = parse_yaml(0, "a: [1, {b: c}]\n").unwrap();
+        let second = parse_yaml(1, "\n\na:\n  - 1\n  - b: c\n").unwrap();
+        assert_eq!(first, second);
+        assert_eq!(hash_of(&first), hash_of(&second));
+
+        let bare_first = first.clone_without_spans();
+        let bare_second = second.clone_without_spans();
+        assert_eq!(hash_of(&bare_first), hash_of(&first));
+        assert_eq!(
+            format!("{:?}", bare_first.pointer("/a/1/b")),
+            format!("{:?}", bare_second.pointer("/a/1/b"))
+        );
+        let map = bare_first.as_mapping().unwrap();
+        let (key, _) = map.iter().next().unwrap();
+        assert_eq!(key.span(), &Span::new_blank());
+        assert!(map.get_sequence("a").unwrap().is_flow());
+    }
+
+    #[test]
+    fn line_index_round_trips() {
+        let text = "ünï: cödé\n\"ß\": [✓, x]\nplain: 🦀 crab\n";
+        let index = LineIndex::new(0, text);
+        let node = parse_yaml(0, text).unwrap();
+        let map = node.as_mapping().unwrap();
+        let nodes = [
+            map.get_node("ünï").unwrap(),
+            map.get_sequence("ß").unwrap().get_node(0).unwrap(),
+            map.get_sequence("ß").unwrap().get_node(1).unwrap(),
+            map.get_node("plain").unwrap(),
+        ];
+        for (node, expected) in nodes.iter().zip(["cödé", "✓", "x", "🦀 crab"]) {
+            let marker = node.span().start().unwrap();
+            let offset = index.marker_to_offset(marker).unwrap();
+            assert!(text[offset..].starts_with(expected));
+            assert_eq!(&index.offset_to_marker(offset), marker);
+        }
+        let seq = map.get_sequence("ß").unwrap();
+        let end = index.marker_to_offset(seq.span().end().unwrap()).unwrap();
+        assert_eq!(&text[end..end + 1], "]");
+
+        // Offsets inside a character snap back to its start, and offsets
+        // past the end are clamped
+        assert_eq!(index.offset_to_marker(1), Marker::new(0, 1, 1));
+        assert_eq!(index.offset_to_marker(text.len() + 5), Marker::new(0, 4, 1));
+        // End of line is allowed, but nothing beyond it
+        assert_eq!(index.marker_to_offset(&Marker::new(0, 1, 10)), Some(13));
+        assert_eq!(index.marker_to_offset(&Marker::new(0, 1, 11)), None);
+        assert_eq!(index.marker_to_offset(&Marker::new(0, 0, 1)), None);
+        assert_eq!(index.marker_to_offset(&Marker::new(0, 5, 1)), None);
+    }
+
+    #[test]
+    fn utf16_columns() {
+        let text = "café: ok\n\"🦀\": e\u{301}t\u{e9}\n";
+        let index = LineIndex::new(0, text);
+        let node = parse_yaml(0, text).unwrap();
+        let map = node.as_mapping().unwrap();
+
+        let ok = map.get_node("café").unwrap().span().start().unwrap();
+        assert_eq!(ok.column(), 7);
+        assert_eq!(ok.column_utf16(index.line_text(1).unwrap()), 6);
+
+        let crab = map.get_node("🦀").unwrap().span().start().unwrap();
+        assert_eq!(crab.column(), 6);
+        assert_eq!(crab.column_utf16(index.line_text(2).unwrap()), 6);
+
+        // The combining accent is a column and a code unit of its own, and
+        // the crab is one column but two code units
+        let end = Marker::new(0, 2, 10);
+        assert_eq!(end.column_utf16(index.line_text(2).unwrap()), 10);
+        assert_eq!(Marker::new(0, 2, 99).column_utf16("🦀"), 2);
+        assert_eq!(Marker::new(0, 1, 1).column_utf16("🦀"), 0);
+    }
+
+    #[test]
+    fn scalar_whitespace() {
+        let node = parse_yaml(
+            0,
+            "folded: >\n  some\n  text\n\nkept: |+\n  kept\n\nquoted: \"  spaced \"\n",
+        )
+        .unwrap();
+        let map = node.as_mapping().unwrap();
+        let folded = map.get_scalar("folded").unwrap();
+        assert_eq!(folded.as_str(), "some text\n");
+        assert_eq!(folded.chomped_str(), "some text");
+        assert_eq!(folded.trimmed_str(), "some text");
+        let kept = map.get_scalar("kept").unwrap();
+        assert_eq!(kept.as_str(), "kept\n\n");
+        assert_eq!(kept.chomped_str(), "kept\n");
+        assert_eq!(kept.trimmed_str(), "kept");
+        let quoted = map.get_scalar("quoted").unwrap();
+        assert_eq!(quoted.chomped_str(), "  spaced ");
+        assert_eq!(quoted.trimmed_str(), "spaced");
+    }
+
+    #[test]
+    fn mapping_entries() {
+        use linked_hash_map::Entry;
+        let mut node = parse_yaml(0, "name: api\nport: 80\n").unwrap();
+        let map = node.as_mapping_mut().unwrap();
+
+        let port = map.entry("port").or_insert(Node::from("8080"));
+        assert_eq!(port.as_scalar().unwrap().as_str(), "80");
+        assert_eq!(port.span().start(), Some(&Marker::new(0, 2, 7)));
+        match map.entry("name") {
+            Entry::Occupied(mut entry) => {
+                assert_eq!(entry.key().span().start(), Some(&Marker::new(0, 1, 1)));
+                *entry.get_mut() = Node::from("web");
+            }
+            Entry::Vacant(_) => panic!("name should be present"),
+        }
+
+        map.entry("retries").or_insert_with(|| Node::from("3"));
+        match map.entry(MarkedScalarNode::from("timeout")) {
+            Entry::Occupied(_) => panic!("timeout should be absent"),
+            Entry::Vacant(entry) => {
+                entry.insert(Node::from("30s"));
+            }
+        }
+
+        let order: Vec<_> = map.keys().map(MarkedScalarNode::as_str).collect();
+        assert_eq!(order, ["name", "port", "retries", "timeout"]);
+        assert_eq!(map.get_scalar("name").unwrap().as_str(), "web");
+        let (key, value) = map.iter().nth(2).unwrap();
+        assert!(key.span().is_blank());
+        assert_eq!(value.as_scalar().unwrap().as_str(), "3");
+    }
+
+    #[test]
+    fn node_path_to() {
+        let node = parse_yaml(
+            0,
+            "servers:\n  - host: a\n  - host: b\n    ports: [80, 443]\nother: [80, 443]\n",
+        )
+        .unwrap();
+        let port = node.pointer("/servers/1/ports/1").unwrap();
+        assert_eq!(
+            node.path_to(port),
+            Some(vec![
+                PathSegment::Key("servers".into()),
+                PathSegment::Index(1),
+                PathSegment::Key("ports".into()),
+                PathSegment::Index(1),
+            ])
+        );
+        assert_eq!(node.path_to(&node), Some(vec![]));
+        // Matching is by identity, so an equal node elsewhere isn't found
+        let other = node.pointer("/other").unwrap();
+        let servers = node.pointer("/servers").unwrap();
+        assert_eq!(servers.path_to(other), None);
+        assert_eq!(other, node.pointer("/servers/1/ports").unwrap());
+        let copy = port.clone();
+        assert_eq!(node.path_to(&copy), None);
+    }
+
+    #[test]
+    fn node_diff() {
+        let old = parse_yaml(
+            0,
+            "name: app\nport: 80\ndebug: true\nhosts: [a, b, c]\nlimits: {cpu: 1}\n",
+        )
+        .unwrap();
+        let new = parse_yaml(
+            1,
+            "port: 8080\nname: app\nhosts: [a, x]\nlimits: [1]\nenv: {}\n",
+        )
+        .unwrap();
+        let key = |k: &str| PathSegment::Key(k.into());
+        let at = |source, line, column| Span::new_start(Marker::new(source, line, column));
+        let changes = Node::diff(&old, &new);
+        assert_eq!(
+            changes,
+            [
+                Change::Modified(vec![key("port")], at(0, 2, 7), at(1, 1, 7)),
+                Change::Removed(vec![key("debug")], at(0, 3, 8)),
+                Change::Modified(
+                    vec![key("hosts"), PathSegment::Index(1)],
+                    at(0, 4, 12),
+                    at(1, 3, 12)
+                ),
+                Change::Removed(vec![key("hosts"), PathSegment::Index(2)], at(0, 4, 15)),
+                Change::Modified(
+                    vec![key("limits")],
+                    *old.pointer("/limits").unwrap().span(),
+                    *new.pointer("/limits").unwrap().span(),
+                ),
+                Change::Added(vec![key("env")], *new.pointer("/env").unwrap().span()),
+            ]
+        );
+        assert!(Node::diff(&old, &old.clone_without_spans()).is_empty());
+        assert_eq!(
+            Node::diff(&Node::from("a"), &Node::from("b")),
+            [Change::Modified(
+                vec![],
+                Span::new_blank(),
+                Span::new_blank()
+            )]
+        );
+    }
+
+    #[test]
+    fn node_skeleton() {
+        let node = parse_yaml(
+            0,
+            "name: web\nports: [80, 443]\nservers:\n  - host: a\n    tags: []\nempty: {}\n",
+        )
+        .unwrap();
+        let skeleton = node.skeleton();
+        let expected = parse_yaml(
+            0,
+            "name: ~\nports: [~, ~]\nservers:\n  - host: ~\n    tags: []\nempty: {}\n",
+        )
+        .unwrap();
+        assert_eq!(skeleton, expected);
+        let keys: Vec<_> = skeleton
+            .as_mapping()
+            .unwrap()
+            .keys()
+            .map(|k| k.as_str())
+            .collect();
+        assert_eq!(keys, ["name", "ports", "servers", "empty"]);
+        assert!(skeleton
+            .pointer("/ports")
+            .unwrap()
+            .as_sequence()
+            .unwrap()
+            .is_flow());
+        assert!(skeleton.pointer("/servers/0/host").unwrap().is_null());
+        assert!(skeleton.iter_with_spans().all(|(_, span)| span.is_blank()));
+        assert_eq!(Node::from("value").skeleton(), Node::from("~"));
+    }
+
+    #[test]
+    fn content_spans() {
+        let node = parse_yaml(
+            0,
+            "outer:\n  a: 1\n  b: [x, y]\n  c: last\nnext: {}\nempty: []\n",
+        )
+        .unwrap();
+        let map = node.as_mapping().unwrap();
+        let outer = map.get_mapping("outer").unwrap();
+        assert_eq!(
+            outer.content_span(),
+            Span::new_with_marks(Marker::new(0, 2, 3), Marker::new(0, 4, 6))
+        );
+        assert_eq!(
+            map.content_span(),
+            Span::new_with_marks(Marker::new(0, 1, 1), Marker::new(0, 6, 9))
+        );
+        let seq = outer.get_sequence("b").unwrap();
+        assert_eq!(
+            seq.content_span(),
+            Span::new_with_marks(Marker::new(0, 3, 7), Marker::new(0, 3, 10))
+        );
+        assert!(map.get_sequence("empty").unwrap().content_span().is_blank());
+        assert!(map.get_mapping("next").unwrap().content_span().is_blank());
+    }
+
+    #[test]
+    fn collecting_nodes() {
+        let seq: MarkedSequenceNode = (1..=3).map(|n| Node::from(n.to_string())).collect();
+        assert_eq!(seq.len(), 3);
+        assert_eq!(seq.get_scalar(2).unwrap().as_str(), "3");
+        assert!(seq.span().is_blank());
+
+        let keys = ["zebra", "apple", "mango"];
+        let map: MarkedMappingNode = keys
+            .iter()
+            .map(|k| (MarkedScalarNode::from(*k), Node::from(k.len().to_string())))
+            .collect();
+        let order: Vec<_> = map.keys().map(MarkedScalarNode::as_str).collect();
+        assert_eq!(order, keys);
+        assert_eq!(map.get_scalar("apple").unwrap().as_str(), "5");
+        assert!(map.span().is_blank());
+        assert!(map.keys().all(|k| k.span().is_blank()));
+    }
+
+    #[test]
+    fn mutable_access() {
+        let mut node = parse_yaml(0, "servers:\n  - name: web\n    ports: [80]\n").unwrap();
+        let servers = node
+            .as_mapping_mut()
+            .and_then(|map| map.get_sequence_mut("servers"))
+            .unwrap();
+        let server = servers.get_mapping_mut(0).unwrap();
+        server
+            .get_sequence_mut("ports")
+            .unwrap()
+            .push(Node::from("443"));
+        *server.get_node_mut("name").unwrap() = Node::from("proxy");
+        assert!(server.get_scalar_mut("ports").is_none());
+        servers.push(Node::from(vec!["extra"]));
+
+        let servers = node.as_mapping().unwrap().get_sequence("servers").unwrap();
+        let server = servers.get_mapping(0).unwrap();
+        assert_eq!(server.get_scalar("name").unwrap().as_str(), "proxy");
+        assert!(server.get_scalar("name").unwrap().span().is_blank());
+        let ports = server.get_sequence("ports").unwrap();
+        assert_eq!(ports.get_scalar(0).unwrap().as_str(), "80");
+        assert_eq!(
+            ports.get_scalar(0).unwrap().span().start(),
+            Some(&Marker::new(0, 3, 13))
+        );
+        assert_eq!(ports.get_scalar(1).unwrap().as_str(), "443");
+        assert!(ports.get_scalar(1).unwrap().span().is_blank());
+        assert_eq!(server.span().start(), Some(&Marker::new(0, 2, 9)));
+        assert!(servers.get_sequence(1).unwrap().span().is_blank());
+    }
+
+    #[test]
+    fn node_debug_compact() {
+        let node = parse_yaml(0, "a: [b, {c: d}]\ne: {}\nf: g\n").unwrap();
+        assert_eq!(
+            format!("{:?}", node.debug_compact()),
+            concat!(
+                r#"mapping@1:2 {scalar("a")@1:1: sequence@1:4 [scalar("b")@1:5, "#,
+                r#"mapping@1:8 {scalar("c")@1:9: scalar("d")@1:12}], "#,
+                r#"scalar("e")@2:1: mapping@2:4 {}, scalar("f")@3:1: scalar("g")@3:4}"#,
+            )
+        );
+        assert_eq!(
+            format!("{:#?}", node.debug_compact()),
+            r#"mapping@1:2 {
+    scalar("a")@1:1: sequence@1:4 [
+        scalar("b")@1:5,
+        mapping@1:8 {
+            scalar("c")@1:9: scalar("d")@1:12,
+        },
+    ],
+    scalar("e")@2:1: mapping@2:4 {},
+    scalar("f")@3:1: scalar("g")@3:4,
+}"#
+        );
+        let node = Node::from(vec!["x\"y"]);
+        assert_eq!(
+            format!("{:?}", node.debug_compact()),
+            r#"sequence [scalar("x\"y")]"#
+        );
+    }
+
+    #[test]
+    fn node_iter_with_spans() {
+        let node = parse_yaml(0, "a: [b, {c: d}]\ne: {}\nf: g\n").unwrap();
+        let visited: Vec<_> = node
+            .iter_with_spans()
+            .map(|(node, span)| {
+                let what = match node {
+                    Node::Scalar(s) => s.as_str(),
+                    Node::Mapping(_) => "{}",
+                    Node::Sequence(_) => "[]",
+                };
+                let start = span.start().unwrap();
+                (what, start.line(), start.column())
+            })
+            .collect();
+        assert_eq!(
+            visited,
+            [
+                ("{}", 1, 2),
+                ("[]", 1, 4),
+                ("b", 1, 5),
+                ("{}", 1, 8),
+                ("d", 1, 12),
+                ("{}", 2, 4),
+                ("g", 3, 4),
+            ]
+        );
+        assert_eq!(Node::from("lonely").iter_with_spans().count(), 1);
+    }
+
+    #[test]
+    fn node_count_by_kind() {
+        let node = crate::parse_yaml(
+            0,
+            "name: example\nports: [80, 443]\nenv:\n  - {a: 1}\n  - []\n  - {}\n",
+        )
+        .unwrap();
+        // Keys: name ports env a, values: example 80 443 1
+        assert_eq!(node.count_by_kind(), (8, 3, 3));
+        let scalar = Node::from("lonely");
+        assert_eq!(scalar.count_by_kind(), (1, 0, 0));
+        let empty = crate::parse_yaml(0, "").unwrap();
+        assert_eq!(empty.count_by_kind(), (0, 1, 0));
+    }
+
+    #[test]
+    fn node_remap_sources() {
+        let mut node = parse_yaml(0, "a: [b, {c: d}]\ne: f\n").unwrap();
+        let before = node.clone();
+        node.remap_sources(|source| if source == 0 { 5 } else { source });
+        assert_eq!(node, before);
+
+        fn check(node: &Node, spans: &mut usize) {
+            for marker in [node.span().start(), node.span().end()]
+                .into_iter()
+                .flatten()
+            {
+                assert_eq!(marker.source(), 5);
+                *spans += 1;
+            }
+            match node {
+                Node::Scalar(_) => {}
+                Node::Mapping(map) => {
+                    for (key, value) in map.iter() {
+                        assert_eq!(key.span().start().unwrap().source(), 5);
+                        check(value, spans);
+                    }
+                }
+                Node::Sequence(seq) => seq.iter().for_each(|value| check(value, spans)),
+            }
+        }
+        let mut spans = 0;
+        check(&node, &mut spans);
+        // Three containers with two markers each, and three scalar values
+        assert_eq!(spans, 9);
+        assert_eq!(
+            node.pointer("/a/1/c").unwrap().span().start(),
+            Some(&Marker::new(5, 1, 12))
+        );
+    }
+
+    #[test]
+    fn node_retain() {
+        const DOC: &str = "name: api\nx-owner: team\nservers:\n  - url: a\n    x-region: eu\n  - {url: b, x-region: us}\ninfo: {title: t, x-logo: {url: c}}\n";
+        let mut node = parse_yaml(0, DOC).unwrap();
+        let mut seen = Vec::new();
+        node.retain_recursive(|key, _| {
+            seen.push(key.as_str().to_string());
+            !key.as_str().starts_with("x-")
+        });
+        // Nothing inside x-logo was visited since it was removed first
+        assert_eq!(seen.iter().filter(|k| *k == "url").count(), 2);
+
+        let map = node.as_mapping().unwrap();
+        assert_eq!(
+            map.keys().map(|k| k.as_str()).collect::<Vec<_>>(),
+            ["name", "servers", "info"]
+        );
+        assert!(node.pointer("/servers/0/x-region").is_none());
+        assert!(node.pointer("/servers/1/x-region").is_none());
+        assert!(node.pointer("/info/x-logo").is_none());
+        assert_eq!(
+            node.pointer("/servers/1/url").unwrap().span().start(),
+            Some(&Marker::new(0, 6, 11))
+        );
+        assert_eq!(
+            node.pointer("/info/title").unwrap().span().start(),
+            Some(&Marker::new(0, 7, 15))
+        );
+        assert_eq!(
+            map.get_node("servers").unwrap().span().start(),
+            Some(&Marker::new(0, 4, 3))
+        );
+    }
+
+    #[test]
+    fn span_ordering() {
+        let blank = Span::new_blank();
+        let early = Span::new_start(Marker::new(0, 1, 5));
+        let later_column = Span::new_start(Marker::new(0, 1, 6));
+        let later_line = Span::new_with_marks(Marker::new(0, 2, 1), Marker::new(0, 2, 3));
+        let other_source = Span::new_start(Marker::new(1, 1, 1));
+        assert_eq!(early.cmp_start(&later_column), Ordering::Less);
+        assert_eq!(later_line.cmp_start(&later_column), Ordering::Greater);
+        assert_eq!(
+            early.cmp_start(&Span::new_start(Marker::new(0, 1, 5))),
+            Ordering::Equal
+        );
+        assert_eq!(other_source.cmp_start(&later_line), Ordering::Greater);
+        assert_eq!(blank.cmp_start(&early), Ordering::Greater);
+        assert_eq!(blank.cmp_start(&blank), Ordering::Equal);
+
+        let node = parse_yaml(0, "x: [c, b]\ny: {z: a}\n").unwrap();
+        let map = node.as_mapping().unwrap();
+        let made_up: Node = "made up".into();
+        let seq = map.get_sequence("x").unwrap();
+        let mut nodes = vec![
+            &made_up,
+            map.get_mapping("y").unwrap().get_node("z").unwrap(),
+            seq.get_node(1).unwrap(),
+            seq.get_node(0).unwrap(),
+        ];
+        sort_by_span(&mut nodes);
+        let order: Vec<_> = nodes
+            .iter()
+            .map(|n| n.as_scalar().unwrap().as_str())
+            .collect();
+        assert_eq!(order, vec!["c", "b", "a", "made up"]);
+    }
+
+    #[test]
+    fn mapping_entries_spanned() {
+        let node = parse_yaml(0, "key: value\nlist:\n  - item\nmap: { a: b }\n").unwrap();
+        let map = node.as_mapping().unwrap();
+        let entries: Vec<_> = map.entries_spanned().collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].0.as_str(), "key");
+        assert_eq!(entries[0].2, Span::new_start(Marker::new(0, 1, 1)));
+        let (key, value, span) = entries[2];
+        assert_eq!(span.start(), key.span().start());
+        assert_eq!(span.end(), value.span().end());
+        assert_eq!(
+            span,
+            Span::new_with_marks(Marker::new(0, 4, 1), Marker::new(0, 4, 13))
+        );
     }
 
     #[test]
@@ -1377,6 +4490,38 @@ mod test {
         assert_eq!(zero.as_usize(), Some(0));
         assert_eq!(zero, 0usize.into());
         assert_eq!(&*zero, "0");
+        assert_eq!(zero.as_yaml11_usize(), Some(0));
+    }
+
+    #[test]
+    fn scalar_yaml11_numbers() {
+        let node = crate::parse_yaml(0, "a: 0755\nb: -1:30\nc: '0755'\nd: 1:60\ne: 0789\nf: 12\n")
+            .unwrap();
+        let map = node.as_mapping().unwrap();
+        let scalar = |key| map.get_scalar(key).unwrap();
+        assert_eq!(scalar("a").as_u32(), Some(755));
+        assert_eq!(scalar("a").as_yaml11_u32(), Some(0o755));
+        assert_eq!(scalar("a").as_yaml11_u8(), None);
+        assert_eq!(scalar("b").as_i64(), None);
+        assert_eq!(scalar("b").as_yaml11_i64(), Some(-90));
+        assert_eq!(scalar("b").as_yaml11_u64(), None);
+        assert_eq!(scalar("c").as_yaml11_u32(), Some(755));
+        assert_eq!(scalar("d").as_yaml11_u32(), None);
+        assert_eq!(scalar("e").as_yaml11_u32(), Some(789));
+        assert_eq!(scalar("f").as_yaml11_i8(), Some(12));
+    }
+
+    #[test]
+    fn scalar_str_comparisons() {
+        let node = crate::parse_yaml(0, "version: \" 2 \"\n").unwrap();
+        let (key, value) = node.as_mapping().unwrap().iter().next().unwrap();
+        assert!(*"version" == *key);
+        assert_eq!("version", key);
+        assert_ne!("Version", key);
+        assert_ne!("version ", key);
+        assert_eq!(" 2 ", value.as_scalar().unwrap());
+        assert_ne!("2", value.as_scalar().unwrap());
+        assert!(*"" == MarkedScalarNode::new_empty(Span::new_blank()));
     }
 
     #[test]
@@ -1419,6 +4564,91 @@ mod test {
         assert!(node.as_mapping().unwrap().is_empty());
     }
 
+    #[test]
+    fn node_pointer() {
+        let node = parse_yaml(0, "{a: [x, {b/c: y, d~e: z}], '': empty, 0: zero}").unwrap();
+        assert_eq!(node.pointer(""), Some(&node));
+        assert_eq!(
+            node.pointer("/a/0")
+                .and_then(Node::as_scalar)
+                .unwrap()
+                .as_str(),
+            "x"
+        );
+        assert_eq!(
+            node.pointer("/a/1/b~1c")
+                .and_then(Node::as_scalar)
+                .unwrap()
+                .as_str(),
+            "y"
+        );
+        assert_eq!(
+            node.pointer("/a/1/d~0e")
+                .and_then(Node::as_scalar)
+                .unwrap()
+                .as_str(),
+            "z"
+        );
+        assert_eq!(
+            node.pointer("/")
+                .and_then(Node::as_scalar)
+                .unwrap()
+                .as_str(),
+            "empty"
+        );
+        assert_eq!(
+            node.pointer("/0")
+                .and_then(Node::as_scalar)
+                .unwrap()
+                .as_str(),
+            "zero"
+        );
+        assert_eq!(node.pointer("a"), None);
+        assert_eq!(node.pointer("/a/2"), None);
+        assert_eq!(node.pointer("/a/01"), None);
+        assert_eq!(node.pointer("/a/00"), None);
+        assert_eq!(node.pointer("/a/+1"), None);
+        assert_eq!(node.pointer("/a/+0"), None);
+        assert_eq!(node.pointer("/a/-1"), None);
+        assert_eq!(node.pointer("/a/ 1"), None);
+        assert_eq!(node.pointer("/a/"), None);
+        assert_eq!(node.pointer("/a/0/x"), None);
+        assert_eq!(node.pointer("/missing"), None);
+    }
+
+    #[test]
+    fn node_map_scalars() {
+        let vars: std::collections::HashMap<&str, &str> =
+            [("HOST", "example.com"), ("PORT", "8080")]
+                .into_iter()
+                .collect();
+        let node = parse_yaml(
+            0,
+            "server:\n  url: http://${HOST}:${PORT}/\n  ${HOST}: ['${PORT}', plain]\n",
+        )
+        .unwrap();
+        let substituted = node.map_scalars(|s| {
+            let mut value = s.as_str().to_string();
+            for (name, replacement) in &vars {
+                value = value.replace(&format!("${{{name}}}"), replacement);
+            }
+            MarkedScalarNode::new(*s.span(), value)
+        });
+        let server = substituted
+            .as_mapping()
+            .unwrap()
+            .get_mapping("server")
+            .unwrap();
+        let url = server.get_scalar("url").unwrap();
+        assert_eq!(url.as_str(), "http://example.com:8080/");
+        assert_eq!(url.span().start(), Some(&Marker::new(0, 2, 8)));
+        let seq = server.get_sequence("${HOST}").unwrap();
+        assert_eq!(seq.get_scalar(0).unwrap().as_str(), "8080");
+        assert_eq!(seq.get_scalar(1).unwrap().as_str(), "plain");
+        assert_eq!(server.span(), node.pointer("/server").unwrap().span());
+        assert_eq!(substituted.span(), node.span());
+    }
+
     #[test]
     fn yaml_conversions() {
         use yaml_rust::YamlLoader;
@@ -1453,6 +4683,55 @@ mod test {
         }
     }
 
+    #[test]
+    fn moving_subtrees() {
+        let mut node = crate::parse_yaml(
+            0,
+            "a:\n  first: 1\n  inner: [1, 2]\n  last: 3\nb: {}\nlist: [x, {moved: y}, z]\n",
+        )
+        .unwrap();
+        let map = node.as_mapping_mut().unwrap();
+
+        let a = map.get_mapping_mut("a").unwrap();
+        let inner = a.remove_node("inner").unwrap();
+        assert_eq!(
+            a.keys().map(|k| k.as_str()).collect::<Vec<_>>(),
+            ["first", "last"]
+        );
+        assert!(a.remove_node("inner").is_none());
+        assert_eq!(inner.span().start(), Some(&Marker::new(0, 3, 10)));
+        map.get_mapping_mut("b")
+            .unwrap()
+            .insert(MarkedScalarNode::from("inner"), inner);
+
+        let list = map.get_sequence_mut("list").unwrap();
+        let moved = list.remove_node(1).unwrap();
+        assert!(list.remove_node(2).is_none());
+        let remaining: Vec<_> = list
+            .iter()
+            .map(|n| n.as_scalar().unwrap().as_str())
+            .collect();
+        assert_eq!(remaining, ["x", "z"]);
+        list.push(moved);
+
+        let b = map.get_node_mut("b").unwrap().take();
+        assert!(map.get_node("b").unwrap().is_null());
+        map.insert(MarkedScalarNode::from("c"), b);
+
+        // Taken nodes are left as empty scalars, which is what `''` loads as
+        let expected = crate::parse_yaml(
+            0,
+            "a: {first: 1, last: 3}\nb: ''\nlist: [x, z, {moved: y}]\nc: {inner: [1, 2]}\n",
+        )
+        .unwrap();
+        assert_eq!(node, expected);
+        let c = node.as_mapping().unwrap().get_mapping("c").unwrap();
+        assert_eq!(
+            c.get_node("inner").unwrap().span().start(),
+            Some(&Marker::new(0, 3, 10))
+        );
+    }
+
     #[test]
     fn back_yaml_conversion() {
         use yaml_rust::YamlLoader;
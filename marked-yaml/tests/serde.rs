@@ -4,7 +4,11 @@
 
 use std::collections::HashMap;
 
-use marked_yaml::{from_node, from_yaml, parse_yaml, Spanned};
+use marked_yaml::{
+    from_node, from_yaml, parse_yaml, Marker, Spanned, SPANNED_FIELDS, SPANNED_TYPE,
+};
+use serde::de::value::BorrowedStrDeserializer;
+use serde::de::{DeserializeSeed, IntoDeserializer, MapAccess, Visitor};
 use serde::Deserialize;
 
 const TEST_DOC: &str = r#"# Line one is a comment
@@ -74,3 +78,231 @@ fn ergonomics() {
     let doc: FullTest = from_yaml(0, TEST_DOC).unwrap();
     assert_eq!(doc.kvs.get("first").map(|s| s.as_str()), Some("one"));
 }
+
+#[test]
+fn spans_as_json() {
+    let nodes = parse_yaml(3, "kvs: {first: one}\n").unwrap();
+    let kvs = nodes.as_mapping().unwrap().get_node("kvs").unwrap();
+    let json = serde_json::to_value(kvs.span()).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "start": {"source": 3, "line": 1, "column": 6},
+            "end": {"source": 3, "line": 1, "column": 17},
+        })
+    );
+    let first = kvs.as_mapping().unwrap().get_node("first").unwrap();
+    let json = serde_json::to_value(first.span()).unwrap();
+    assert_eq!(
+        json,
+        serde_json::json!({
+            "start": {"source": 3, "line": 1, "column": 14},
+            "end": null,
+        })
+    );
+}
+
+// A deserializer from outside this crate which knows the Spanned contract
+struct ThirdParty {
+    line: usize,
+    value: u32,
+}
+
+struct ThirdPartySpan {
+    line: usize,
+    value: u32,
+    pos: usize,
+}
+
+impl<'de> serde::Deserializer<'de> for ThirdParty {
+    type Error = serde::de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        visitor.visit_u32(self.value)
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        if name == SPANNED_TYPE && fields == SPANNED_FIELDS {
+            visitor.visit_map(ThirdPartySpan {
+                line: self.line,
+                value: self.value,
+                pos: 0,
+            })
+        } else {
+            self.deserialize_any(visitor)
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+impl<'de> MapAccess<'de> for ThirdPartySpan {
+    type Error = serde::de::value::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Self::Error> {
+        // Only a start marker is provided, so skip the end marker fields
+        let key = match self.pos {
+            0..=2 => SPANNED_FIELDS[self.pos],
+            3 => SPANNED_FIELDS[6],
+            _ => return Ok(None),
+        };
+        seed.deserialize(BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.pos += 1;
+        match self.pos {
+            1 => seed.deserialize(3usize.into_deserializer()),
+            2 => seed.deserialize(self.line.into_deserializer()),
+            3 => seed.deserialize(1usize.into_deserializer()),
+            _ => seed.deserialize(ThirdParty {
+                line: self.line,
+                value: self.value,
+            }),
+        }
+    }
+}
+
+#[test]
+fn third_party_spanned() {
+    let spanned = Spanned::<u32>::deserialize(ThirdParty { line: 7, value: 42 }).unwrap();
+    assert_eq!(*spanned, 42);
+    assert_eq!(spanned.span().start(), Some(&Marker::new(3, 7, 1)));
+    assert_eq!(spanned.span().end(), None);
+}
+
+#[test]
+fn serde_bytes_interop() {
+    #[derive(Deserialize)]
+    struct Blobs<'a> {
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+        buf: serde_bytes::ByteBuf,
+        #[serde(borrow)]
+        borrowed: &'a serde_bytes::Bytes,
+        numbers: serde_bytes::ByteBuf,
+        spanned: Spanned<serde_bytes::ByteBuf>,
+        #[serde(with = "serde_bytes")]
+        binary: Vec<u8>,
+        block: serde_bytes::ByteBuf,
+    }
+    let node = parse_yaml(
+        0,
+        "data: hello\nbuf: \"world\"\nborrowed: bytes\nnumbers: [1, 2, 255]\nspanned: here\nbinary: !!binary AAH/\nblock: !!binary |\n  aGVs\n  bG8=\n",
+    )
+    .unwrap();
+    let blobs: Blobs = from_node(&node).unwrap();
+    assert_eq!(blobs.data, b"hello");
+    assert_eq!(blobs.buf.as_slice(), b"world");
+    assert_eq!(&**blobs.borrowed, b"bytes");
+    assert_eq!(blobs.numbers.as_slice(), [1, 2, 255]);
+    assert_eq!(blobs.spanned.as_slice(), b"here");
+    assert_eq!(blobs.spanned.span().start(), Some(&Marker::new(0, 5, 10)));
+    assert_eq!(blobs.binary, [0, 1, 255]);
+    assert_eq!(blobs.block.as_slice(), b"hello");
+
+    #[derive(Deserialize, Debug)]
+    #[allow(dead_code)]
+    struct Blob {
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    }
+    let node = parse_yaml(0, "data: !!binary not*base64\n").unwrap();
+    let err = from_node::<Blob>(&node).err().unwrap();
+    assert_eq!(err.start_mark(), Some(Marker::new(0, 1, 16)));
+}
+
+#[test]
+fn dynamic_json_values() {
+    #[derive(Deserialize)]
+    struct Pipeline {
+        name: Spanned<String>,
+        config: serde_json::Value,
+    }
+    let node = parse_yaml(
+        0,
+        "name: build\nconfig:\n  retries: 3\n  ratio: 0.5\n  big: 18446744073709551615\n  enabled: true\n  missing: ~\n  quoted: \"42\"\n  steps: [{run: make, timeout: -10}]\n",
+    )
+    .unwrap();
+    let pipeline: Pipeline = from_node(&node).unwrap();
+    assert_eq!(*pipeline.name, "build");
+    let config = &pipeline.config;
+    assert!(config["retries"].is_u64());
+    assert_eq!(config["retries"], 3);
+    assert_eq!(config["ratio"], 0.5);
+    assert_eq!(config["big"].as_u64(), Some(u64::MAX));
+    assert_eq!(config["enabled"], true);
+    assert!(config["missing"].is_null());
+    assert_eq!(config["quoted"], "42");
+    assert!(config["steps"][0]["timeout"].is_i64());
+    assert_eq!(config["steps"][0]["timeout"], -10);
+    assert_eq!(config["steps"][0]["run"], "make");
+}
+
+#[test]
+fn quoted_numbers() {
+    #[derive(Deserialize)]
+    struct Server {
+        port: u16,
+        quoted_port: Spanned<u16>,
+        ratio: f64,
+        quoted_ratio: f64,
+        extra: serde_json::Value,
+    }
+    let server: Server = from_yaml(
+        0,
+        "port: 8080\nquoted_port: \"8080\"\nratio: 0.5\nquoted_ratio: '0.5'\nextra: {port: 8080, quoted: \"8080\"}\n",
+    )
+    .unwrap();
+    assert_eq!(server.port, 8080);
+    assert_eq!(*server.quoted_port, 8080);
+    assert_eq!(
+        server.quoted_port.span().start(),
+        Some(&Marker::new(0, 2, 14))
+    );
+    assert_eq!(server.ratio, server.quoted_ratio);
+    // Without a target type, YAML's rules apply and quoted scalars are strings
+    assert_eq!(server.extra["port"], 8080);
+    assert_eq!(server.extra["quoted"], "8080");
+    let value: Spanned<serde_json::Value> = from_yaml(0, "port: \"8080\"\n").unwrap();
+    assert_eq!(value["port"], "8080");
+}
+
+#[test]
+fn spanned_deserialize_with() {
+    fn trimmed<'de, D>(deserializer: D) -> Result<Spanned<String>, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let value: Spanned<String> = marked_yaml::spanned::deserialize_with(deserializer)?;
+        Ok(Spanned::new(*value.span(), value.trim().to_string()))
+    }
+    #[derive(Deserialize)]
+    struct Config {
+        #[serde(deserialize_with = "trimmed")]
+        name: Spanned<String>,
+        #[serde(deserialize_with = "marked_yaml::spanned::deserialize_with")]
+        ports: Spanned<Vec<u16>>,
+    }
+    let config: Config = from_yaml(0, "name: '  padded  '\nports: [80, 443]\n").unwrap();
+    assert_eq!(*config.name, "padded");
+    assert_eq!(config.name.span().start(), Some(&Marker::new(0, 1, 7)));
+    assert_eq!(*config.ports, [80, 443]);
+    assert_eq!(config.ports.span().start(), Some(&Marker::new(0, 2, 8)));
+}